@@ -0,0 +1,148 @@
+//! Native definitions of the waPC host imports that `wapc_guest` links against.
+//!
+//! Inside the Wasm Mock Server these symbols are provided by the wasm runtime. When the guest is
+//! compiled for the host target they are resolved here instead, so `host_call` and `__guest_call`
+//! talk to the in-process [`MockHost`](crate::MockHost).
+use std::slice;
+
+use crate::{HostCall, STATE};
+
+unsafe fn read_str(ptr: *const u8, len: usize) -> String {
+    if len == 0 {
+        return String::new();
+    }
+    String::from_utf8_lossy(slice::from_raw_parts(ptr, len)).into_owned()
+}
+
+unsafe fn read_bytes(ptr: *const u8, len: usize) -> Vec<u8> {
+    if len == 0 {
+        return vec![];
+    }
+    slice::from_raw_parts(ptr, len).to_vec()
+}
+
+unsafe fn write_bytes(ptr: *const u8, src: &[u8]) {
+    if !src.is_empty() {
+        std::ptr::copy_nonoverlapping(src.as_ptr(), ptr as *mut u8, src.len());
+    }
+}
+
+/// # Safety
+///
+/// Called by `wapc_guest::console_log` with a valid utf-8 slice.
+#[no_mangle]
+pub unsafe extern "C" fn __console_log(ptr: *const u8, len: usize) {
+    let line = read_str(ptr, len);
+    STATE.with(|s| s.borrow_mut().logs.push(line));
+}
+
+/// # Safety
+///
+/// Called by `wapc_guest::host_call` with valid binding, namespace, operation and payload slices.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn __host_call(
+    bd_ptr: *const u8,
+    bd_len: usize,
+    ns_ptr: *const u8,
+    ns_len: usize,
+    op_ptr: *const u8,
+    op_len: usize,
+    ptr: *const u8,
+    len: usize,
+) -> usize {
+    let call = HostCall {
+        binding: read_str(bd_ptr, bd_len),
+        namespace: read_str(ns_ptr, ns_len),
+        operation: read_str(op_ptr, op_len),
+        payload: read_bytes(ptr, len),
+    };
+    // the reply is resolved before dispatching so user closures may inspect the host freely
+    let reply = STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        s.calls.push(call.clone());
+        s.replies
+            .get(&(call.namespace.clone(), call.operation.clone()))
+            .cloned()
+    });
+    let result = match reply {
+        Some(reply) => reply(&call),
+        None => STATE.with(|s| s.borrow_mut().builtin_reply(&call)),
+    };
+    STATE.with(|s| {
+        let mut s = s.borrow_mut();
+        match result {
+            Ok(res) => {
+                s.host_response = res;
+                s.host_error.clear();
+                1
+            }
+            Err(e) => {
+                s.host_response.clear();
+                s.host_error = e.into_bytes();
+                0
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn __host_response_len() -> usize {
+    STATE.with(|s| s.borrow().host_response.len())
+}
+
+/// # Safety
+///
+/// `ptr` must have room for `__host_response_len()` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn __host_response(ptr: *const u8) {
+    STATE.with(|s| write_bytes(ptr, &s.borrow().host_response));
+}
+
+#[no_mangle]
+pub extern "C" fn __host_error_len() -> usize {
+    STATE.with(|s| s.borrow().host_error.len())
+}
+
+/// # Safety
+///
+/// `ptr` must have room for `__host_error_len()` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn __host_error(ptr: *const u8) {
+    STATE.with(|s| write_bytes(ptr, &s.borrow().host_error));
+}
+
+/// # Safety
+///
+/// `op_ptr` and `ptr` must have room for the operation and payload announced to `__guest_call`.
+#[no_mangle]
+pub unsafe extern "C" fn __guest_request(op_ptr: *const u8, ptr: *const u8) {
+    STATE.with(|s| {
+        let s = s.borrow();
+        write_bytes(op_ptr, &s.guest_operation);
+        write_bytes(ptr, &s.guest_payload);
+    });
+}
+
+/// # Safety
+///
+/// Called by `__guest_call` with the handler's result.
+#[no_mangle]
+pub unsafe extern "C" fn __guest_response(ptr: *const u8, len: usize) {
+    let res = read_bytes(ptr, len);
+    STATE.with(|s| s.borrow_mut().guest_response = res);
+}
+
+/// # Safety
+///
+/// Called by `__guest_call` with the handler's error message.
+#[no_mangle]
+pub unsafe extern "C" fn __guest_error(ptr: *const u8, len: usize) {
+    let err = read_str(ptr, len);
+    STATE.with(|s| s.borrow_mut().guest_error = err);
+}
+
+extern "C" {
+    /// Exported by `wapc_guest`; dispatches to the function registered under the requested operation.
+    pub(crate) fn __guest_call(op_len: i32, req_len: i32) -> i32;
+}
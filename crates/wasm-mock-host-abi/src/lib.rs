@@ -0,0 +1,310 @@
+//! waPC host imports resolved natively, backed by a scriptable per-thread host.
+//!
+//! Inside the Wasm Mock Server the imports of `wapc_guest` (`__host_call`, `__guest_request`, ...) are
+//! provided by the wasm runtime. This crate defines them for the host target, so every `host_call` made
+//! under `cargo test` lands in a [`MockHost`] instead of a running server: canned replies are set per
+//! namespace/operation and every call is recorded.
+//!
+//! It does not depend on `wasm-mock-util`, whose own unit tests link it. `wasm-mock-host` builds the
+//! guest lifecycle (`wapc_init`, `add_functions`, HTTP automation) on top of it.
+//!
+//! Host state is kept per thread, so tests running in parallel do not see each other's replies or calls.
+//!
+//! # Examples
+//!
+//! ```
+//! use wapc_guest::prelude::host_call;
+//! use wasm_mock_host_abi::MockHost;
+//! let host = MockHost::new();
+//! host.reply("foo", "redis_get", b"cached".to_vec());
+//! assert_eq!(host_call("localhost:6379", "foo", "redis_get", b"k").unwrap(), b"cached");
+//! assert_eq!(host.calls_to("redis_get")[0].binding, "localhost:6379");
+//! ```
+#![cfg(not(target_arch = "wasm32"))]
+use byteorder::{ByteOrder, LittleEndian};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+mod abi;
+
+/// A single `host_call` made by the guest
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostCall {
+    pub binding: String,
+    pub namespace: String,
+    pub operation: String,
+    pub payload: Vec<u8>,
+}
+
+impl HostCall {
+    /// Payload as utf-8 text, lossy
+    pub fn payload_str(&self) -> String {
+        String::from_utf8_lossy(&self.payload).into_owned()
+    }
+}
+
+/// Outcome of an `assert_*` or `step_*` host call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    /// `assert` or `step`
+    pub kind: String,
+    pub passed: bool,
+    pub message: String,
+    /// Raw payload, the JSON encoded `AssertionRecord`/`StepRecord` for the macros of `wasm-mock-util`
+    pub payload: Vec<u8>,
+}
+
+type Reply = Rc<dyn Fn(&HostCall) -> Result<Vec<u8>, String>>;
+
+#[derive(Default)]
+pub(crate) struct State {
+    pub(crate) replies: HashMap<(String, String), Reply>,
+    pub(crate) calls: Vec<HostCall>,
+    pub(crate) logs: Vec<String>,
+    indexdb: HashMap<String, Vec<u8>>,
+    files: HashMap<String, Vec<u8>>,
+    index: u64,
+    clock: u64,
+    pub(crate) host_response: Vec<u8>,
+    pub(crate) host_error: Vec<u8>,
+    pub(crate) guest_operation: Vec<u8>,
+    pub(crate) guest_payload: Vec<u8>,
+    pub(crate) guest_response: Vec<u8>,
+    pub(crate) guest_error: String,
+}
+
+thread_local! {
+    pub(crate) static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+impl State {
+    /// Behaviour of the server for operations that have no canned reply
+    pub(crate) fn builtin_reply(&mut self, call: &HostCall) -> Result<Vec<u8>, String> {
+        let mut buf = [0; 8];
+        match (call.namespace.as_str(), call.operation.as_str()) {
+            ("foo", "get_index") => {
+                LittleEndian::write_u64(&mut buf, self.index);
+                Ok(buf.to_vec())
+            }
+            ("foo", "now") | ("default", "now") => {
+                LittleEndian::write_u64(&mut buf, self.clock);
+                Ok(buf.to_vec())
+            }
+            ("foo", "sleep") => {
+                if call.payload.len() == 8 {
+                    self.clock += LittleEndian::read_u64(&call.payload);
+                }
+                Ok(vec![])
+            }
+            ("foo", "assert_pass") | ("foo", "assert_fail") | ("foo", "step_pass") | ("foo", "step_fail") => Ok(vec![]),
+            ("foo", "indexdb_store") => {
+                self.indexdb.insert(call.binding.clone(), call.payload.clone());
+                Ok(vec![])
+            }
+            ("foo", "indexdb_get") => Ok(self.indexdb.get(&call.binding).cloned().unwrap_or_else(|| call.payload.clone())),
+            ("foo", "savefile") => {
+                self.files.insert(call.binding.clone(), call.payload.clone());
+                Ok(vec![])
+            }
+            (ns, op) => Err(format!("wasm-mock-host: no reply set for {}/{} (binding {})", ns, op, call.binding)),
+        }
+    }
+}
+
+/// Handle on the per-thread emulated host
+///
+/// Built-in behaviour, used when no reply is set for an operation:
+/// * `get_index` returns the index set with [`MockHost::set_index`]
+/// * `now` returns a virtual clock in milliseconds which `sleep` advances instead of blocking
+/// * `indexdb_store`/`indexdb_get` and `savefile` are kept in memory
+/// * `assert_*`/`step_*` succeed and are available through [`MockHost::verdicts`]
+///
+/// Any other operation (`http_request`, `tcp_request`, `redis_*`, `memcache_*`, `mysql`, `md5sum`, ...)
+/// fails until a reply is set for it.
+///
+/// Clones are handles on the same host, e.g. for a reply computed with [`MockHost::reply_with`] that moves
+/// the clock on.
+#[derive(Clone)]
+pub struct MockHost {
+    _not_send: std::marker::PhantomData<Rc<()>>,
+}
+
+impl Default for MockHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockHost {
+    /// Returns a handle on a freshly reset host for the current thread
+    pub fn new() -> Self {
+        STATE.with(|s| *s.borrow_mut() = State::default());
+        MockHost {
+            _not_send: std::marker::PhantomData,
+        }
+    }
+    /// Canned reply for every call to `namespace`/`operation`
+    pub fn reply(&self, namespace: &str, operation: &str, payload: Vec<u8>) -> &Self {
+        self.reply_with(namespace, operation, move |_| Ok(payload.clone()))
+    }
+    /// Canned reply serialized as JSON, e.g. an `HttpResponse` for `http_request`
+    pub fn reply_json<T: serde::Serialize>(&self, namespace: &str, operation: &str, value: &T) -> &Self {
+        let payload = serde_json::to_vec(value).unwrap_or_default();
+        self.reply(namespace, operation, payload)
+    }
+    /// Canned error for every call to `namespace`/`operation`
+    pub fn fail(&self, namespace: &str, operation: &str, error: &str) -> &Self {
+        let error = error.to_string();
+        self.reply_with(namespace, operation, move |_| Err(error.clone()))
+    }
+    /// Computes the reply of `namespace`/`operation` from the call itself
+    pub fn reply_with<F>(&self, namespace: &str, operation: &str, f: F) -> &Self
+    where
+        F: Fn(&HostCall) -> Result<Vec<u8>, String> + 'static,
+    {
+        STATE.with(|s| {
+            s.borrow_mut()
+                .replies
+                .insert((namespace.to_string(), operation.to_string()), Rc::new(f))
+        });
+        self
+    }
+    /// Index returned by `get_index`
+    pub fn set_index(&self, index: u64) -> &Self {
+        STATE.with(|s| s.borrow_mut().index = index);
+        self
+    }
+    /// Sets the virtual clock returned by `now`, in milliseconds
+    pub fn set_now(&self, now: u64) -> &Self {
+        STATE.with(|s| s.borrow_mut().clock = now);
+        self
+    }
+    /// Current value of the virtual clock
+    pub fn now(&self) -> u64 {
+        STATE.with(|s| s.borrow().clock)
+    }
+    /// Every host call made so far, in order
+    pub fn calls(&self) -> Vec<HostCall> {
+        STATE.with(|s| s.borrow().calls.clone())
+    }
+    /// Host calls made to `operation`, in order
+    pub fn calls_to(&self, operation: &str) -> Vec<HostCall> {
+        STATE.with(|s| s.borrow().calls.iter().filter(|c| c.operation == operation).cloned().collect())
+    }
+    /// Forgets recorded calls and logs, keeping replies and stored data
+    pub fn clear_calls(&self) {
+        STATE.with(|s| {
+            let mut s = s.borrow_mut();
+            s.calls.clear();
+            s.logs.clear();
+        });
+    }
+    /// Lines sent through `console_log`
+    pub fn logs(&self) -> Vec<String> {
+        STATE.with(|s| s.borrow().logs.clone())
+    }
+    /// Value stored in indexdb under `key`
+    pub fn indexdb(&self, key: &str) -> Option<Vec<u8>> {
+        STATE.with(|s| s.borrow().indexdb.get(key).cloned())
+    }
+    /// Seeds indexdb as if it were stored through `POST /indexdb/store`
+    pub fn set_indexdb(&self, key: &str, value: &[u8]) -> &Self {
+        STATE.with(|s| s.borrow_mut().indexdb.insert(key.to_string(), value.to_vec()));
+        self
+    }
+    /// Bytes saved with `foo_save_file!` under `path`
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        STATE.with(|s| s.borrow().files.get(path).cloned())
+    }
+    /// Assertions and steps reported so far, in order
+    pub fn verdicts(&self) -> Vec<Verdict> {
+        self.calls()
+            .into_iter()
+            .filter_map(|c| {
+                let (kind, passed) = match c.operation.as_str() {
+                    "assert_pass" => ("assert", true),
+                    "assert_fail" => ("assert", false),
+                    "step_pass" => ("step", true),
+                    "step_fail" => ("step", false),
+                    _ => return None,
+                };
+                // assertion and step macros send a JSON record, plain text is kept as is
+                let message = serde_json::from_slice::<serde_json::Value>(&c.payload)
+                    .ok()
+                    .and_then(|record| record.get("message").or_else(|| record.get("description")).cloned())
+                    .and_then(|m| m.as_str().map(String::from))
+                    .unwrap_or_else(|| c.payload_str());
+                Some(Verdict {
+                    kind: kind.to_string(),
+                    passed,
+                    message,
+                    payload: c.payload,
+                })
+            })
+            .collect()
+    }
+    /// Failed assertions and steps
+    pub fn failures(&self) -> Vec<Verdict> {
+        self.verdicts().into_iter().filter(|v| !v.passed).collect()
+    }
+    /// Makes a guest call, as the server does when invoking a registered function
+    pub fn call(&self, operation: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+        STATE.with(|s| {
+            let mut s = s.borrow_mut();
+            s.guest_operation = operation.as_bytes().to_vec();
+            s.guest_payload = payload.to_vec();
+            s.guest_response.clear();
+            s.guest_error.clear();
+        });
+        let ok = unsafe { abi::__guest_call(operation.len() as i32, payload.len() as i32) };
+        STATE.with(|s| {
+            let mut s = s.borrow_mut();
+            if ok == 1 {
+                Ok(std::mem::take(&mut s.guest_response))
+            } else {
+                Err(std::mem::take(&mut s.guest_error))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wapc_guest::prelude::host_call;
+
+    #[test]
+    fn indexdb_round_trip() {
+        let host = MockHost::new();
+        host_call("key", "foo", "indexdb_store", b"value").unwrap();
+        assert_eq!(host_call("key", "foo", "indexdb_get", b"nil").unwrap(), b"value");
+        assert_eq!(host_call("other", "foo", "indexdb_get", b"nil").unwrap(), b"nil");
+        assert_eq!(host.indexdb("key"), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn canned_replies_and_recording() {
+        let host = MockHost::new();
+        host.reply("foo", "redis_get", b"cached".to_vec());
+        host.fail("foo", "mysql", "connection refused");
+        assert_eq!(host_call("localhost:6379", "foo", "redis_get", b"k").unwrap(), b"cached");
+        let err = host_call("db", "foo", "mysql", b"select 1").unwrap_err();
+        assert!(err.to_string().contains("connection refused"));
+        assert!(host_call("db", "foo", "memcache_get", b"k").is_err());
+        let calls = host.calls_to("redis_get");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].binding, "localhost:6379");
+        assert_eq!(host.calls().len(), 3);
+    }
+
+    #[test]
+    fn sleep_advances_virtual_clock() {
+        let host = MockHost::new();
+        host.set_now(1_000);
+        let mut buf = [0; 8];
+        LittleEndian::write_u64(&mut buf, 250);
+        host_call("wasm-mock-host", "foo", "sleep", &buf).unwrap();
+        assert_eq!(LittleEndian::read_u64(&host_call("default", "foo", "now", b"").unwrap()), 1_250);
+    }
+}
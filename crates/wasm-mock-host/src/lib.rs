@@ -0,0 +1,122 @@
+//! Native in-process stand-in for the Wasm Mock Server host.
+//!
+//! Guest code built on `wasm-mock-util` reaches the server through `host_call`. When the same code is
+//! compiled for the host target (e.g. under `cargo test`), the waPC host imports of `wasm-mock-host-abi`
+//! make every `host_call` land in a scriptable [`MockHost`] instead of a running server. Canned replies are
+//! set per namespace/operation, every call is recorded, and the functions queued in `REGISTRY` are
+//! registered through the same `add_functions` guest call the server uses.
+//!
+//! Host state is kept per thread, so tests running in parallel do not see each other's replies or calls.
+//! Guest state (`REGISTRY`, `AT_COUNTER2`, ...) is still global to the test binary.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use wasm_mock_host::MockHost;
+//! use wasm_mock_util::*;
+//! fn setup() {
+//!     // body of the guest's `_start`
+//! }
+//! let host = MockHost::new();
+//! host.reply("foo", "redis_get", b"cached".to_vec());
+//! host.start(setup).unwrap();
+//! host.call("/t.json_http_modify_res", b"{}").ok();
+//! assert!(host.failures().is_empty());
+//! ```
+#![cfg(not(target_arch = "wasm32"))]
+use std::ops::Deref;
+use wasm_mock_util::{HttpRequest, HttpResponse};
+
+pub use wasm_mock_host_abi::{HostCall, Verdict};
+
+/// UID handed to the guest through `save_uid` by [`MockHost::start`]
+pub const MOCK_UID: &str = "wasm-mock-host";
+
+/// Handle on the per-thread emulated host: the replies, calls and clock of [`wasm_mock_host_abi::MockHost`],
+/// plus the guest lifecycle the server drives
+#[derive(Clone, Default)]
+pub struct MockHost(wasm_mock_host_abi::MockHost);
+
+impl Deref for MockHost {
+    type Target = wasm_mock_host_abi::MockHost;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl MockHost {
+    /// Returns a handle on a freshly reset host for the current thread
+    pub fn new() -> Self {
+        MockHost(wasm_mock_host_abi::MockHost::new())
+    }
+    /// Initializes the guest the way the server does after loading the wasm: `wapc_init`, the guest's
+    /// `_start` body, `save_uid` and `add_functions`.
+    ///
+    /// `_start` cannot be linked natively under that name, so the guest passes the function its `_start`
+    /// calls.
+    ///
+    /// # Returns
+    ///
+    /// Names registered by `add_functions`
+    pub fn start(&self, start: fn()) -> Result<Vec<String>, String> {
+        wasm_mock_util::wapc_init();
+        start();
+        self.call("save_uid", MOCK_UID.as_bytes())?;
+        let names = self.call("add_functions", b"")?;
+        Ok(String::from_utf8_lossy(&names)
+            .split(',')
+            .filter(|n| !n.is_empty())
+            .map(String::from)
+            .collect())
+    }
+    /// Runs `loops` iterations of HTTP automation as the server does for the `http` operation: for each
+    /// index the guest's `command` and `request` are fetched, `responder` plays the remote server, and
+    /// the response goes through `response_marshalling` where the assertions run. The suite is ended with
+    /// `end_suite` afterwards, also when an iteration failed.
+    ///
+    /// # Arguments
+    ///
+    /// * `loops` - number of iterations, starting from index 0
+    /// * `responder` - builds the response for a command (url) and request
+    pub fn run_http<F>(&self, loops: u64, responder: F) -> Result<(), String>
+    where
+        F: Fn(&str, &HttpRequest) -> HttpResponse,
+    {
+        let run = (0..loops).try_for_each(|index| {
+            self.set_index(index);
+            let command = String::from_utf8_lossy(&self.call("command", b"")?).into_owned();
+            let request = self.call("request", b"")?;
+            if request.is_empty() {
+                return Ok(());
+            }
+            let request: HttpRequest = serde_json::from_slice(&request).map_err(|e| e.to_string())?;
+            let response = responder(&command, &request);
+            let response = serde_json::to_vec(&response).map_err(|e| e.to_string())?;
+            self.call("response_marshalling", &response).map(|_| ())
+        });
+        self.call("end_suite", b"")?;
+        run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verdicts_read_records() {
+        let host = MockHost::new();
+        wasm_mock_util::foo_assert_eq!(1, 2, "numbers");
+        wasm_mock_util::foo_step!(true, "seeded");
+        let verdicts = host.verdicts();
+        assert_eq!(verdicts.len(), 2);
+        assert!(!verdicts[0].passed);
+        assert!(verdicts[0].message.starts_with("`numbers` assertion failed"));
+        let record: wasm_mock_util::AssertionRecord = serde_json::from_slice(&verdicts[0].payload).unwrap();
+        assert!(record.diff.unwrap().contains("are not equal"));
+        assert_eq!(record.left.unwrap().json, Some(serde_json::json!(1)));
+        assert_eq!(verdicts[1].message, "seeded");
+        assert_eq!(host.failures().len(), 1);
+    }
+}
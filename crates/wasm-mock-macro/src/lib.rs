@@ -16,6 +16,27 @@ macro_rules! modify {
             let mut $param = foo_unmarshall::<RequestReceivedInMock>(msg)?;
             //let mut $param = foo_unmarshall::<$param_ty>(msg)?;
             modify!(@parameters | $($args_and_body)* test_case_failed);
+            // as sent on, which is what the server fills `http_req` of the response with
+            remember_request($name,&$param);
+            let request = serde_json::to_string(&$param)?;
+            Ok(request.as_bytes().to_vec())
+        });
+    };
+    // the request that produced the response is bound to $req, templates of the response are rendered from it
+    ( $(#[$attr:meta])* http_res $name:literal | $param:tt, $req:ident | $($args_and_body:tt)* ) => {
+        pair_requests($name);
+        REGISTRY.lock().unwrap().entry(_wasm_mock_macro__format!("{}_http_modify_req",$name)).or_insert(|msg:&[u8]|->CallResult{
+            let req = foo_unmarshall::<RequestReceivedInMock>(msg)?;
+            remember_request($name,&req);
+            Ok(msg.to_vec())
+        });
+        REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_http_modify_res",$name),|msg:&[u8]|->CallResult{
+            let test_case_failed = ::std::cell::Cell::new(false);
+            let mut $param = foo_unmarshall::<HttpResponse>(msg)?;
+            let $req = request_of($name,&$param)?;
+            modify!(@parameters | $($args_and_body)* test_case_failed);
+            render_response(&mut $param,&$req);
+            inject_fault($name,&mut $param)?;
             let request = serde_json::to_string(&$param)?;
             Ok(request.as_bytes().to_vec())
         });
@@ -25,6 +46,7 @@ macro_rules! modify {
             let test_case_failed = ::std::cell::Cell::new(false);
             let mut $param = foo_unmarshall::<HttpResponse>(msg)?;
             modify!(@parameters | $($args_and_body)* test_case_failed);
+            inject_fault($name,&mut $param)?;
             let request = serde_json::to_string(&$param)?;
             Ok(request.as_bytes().to_vec())
         });
@@ -87,25 +109,54 @@ macro_rules! test {
         test!(@parameters $($remainder)*);
     };
 
-    ( $(#[$attr:meta])* http_get $name:literal | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+    ( @request $method:expr, $name:literal [$($query:expr)?] {$case:expr} | ($headers:expr) | ($payload:expr) | $param:tt $(, $row:ident)? | $body:block ) => {
         if let Ok(mut AC) = AT_COUNTER2.lock(){
+            let case: Option<serde_json::Value> = $case;
+            let fill = |s:&str|->String{
+                match &case{
+                    Some(row)=>fill_case(s,row),
+                    None=>String::from(s),
+                }
+            };
+            let fill_path = |s:&str|->String{
+                match &case{
+                    Some(row)=>fill_case_path(s,row),
+                    None=>String::from(s),
+                }
+            };
+            #[allow(unused_mut)]
+            let mut query: Vec<(String,String)> = Vec::new();
+            $( query.extend($query.into_iter().map(|(k,v)|(fill(&String::from(k)),fill(&String::from(v))))); )?
+            let target = with_query(&fill_path($name),&query_string(query));
             let mut host = HOST_MAP.lock().unwrap().clone();
-            host.push_str($name);
+            host.push_str(&target);
             COMMAND_MAP.lock().unwrap().insert(AC.clone(),host.clone());
             
             let mut headers = $headers;
             let mut req = httparse::Request::new(&mut headers);
-            req.method = Some("GET");
-            let http1x = request_to_http1x(&req);
+            req.method = Some($method);
+            req.path = Some(target.as_str());
+            req.version = Some(1);
+            let http1x = fill(&request_to_http1x(&req));
+            let mut payload: Vec<u8> = $payload;
+            if case.is_some(){
+                if let Ok(p) = std::str::from_utf8(&payload){
+                    payload = fill(p).into_bytes();
+                }
+            }
             let r = HttpRequest{
                 Http1x:http1x.clone(),
-                HttpBody:$payload,
+                HttpBody:payload,
                 ProxyUrl:String::from("")
             };
+            if let Some(row) = case{
+                CASE_MAP.lock().unwrap().insert(AC.clone(),row);
+            }
             REQUEST_MAP.lock().unwrap().insert(AC.clone(),r);
             REQUEST_MAR_MAP.lock().unwrap().insert(AC.clone(),http1x);
             RESPONSE_MAR_MAP.lock().unwrap().insert(AC.clone(),|msg:&[u8]|->CallResult{
                 let $param: HttpResponse = foo_http_response(msg.clone())?;
+                $( let $row: serde_json::Value = current_case(); )?
                 $body
                 Ok(msg.to_vec())
             });
@@ -113,55 +164,51 @@ macro_rules! test {
         }
      
     };
-    ( $(#[$attr:meta])* http_post $name:literal | ($headers:expr) | ($payload:expr)  | $param:tt | $body:block ) => {
-        if let Ok(mut AC) = AT_COUNTER2.lock(){
-            let mut host = HOST_MAP.lock().unwrap().clone();
-            host.push_str($name);
-            COMMAND_MAP.lock().unwrap().insert(AC.clone(),host.clone());
-            
-            let mut headers = $headers;
-            let mut req = httparse::Request::new(&mut headers);
-            req.method = Some("POST");
-            let http1x = request_to_http1x(&req);
-            let r = HttpRequest{
-                Http1x:http1x.clone(),
-                HttpBody:$payload,
-                ProxyUrl:String::from("")
-            };
-            REQUEST_MAP.lock().unwrap().insert(AC.clone(),r);
-            REQUEST_MAR_MAP.lock().unwrap().insert(AC.clone(),http1x);
-            RESPONSE_MAR_MAP.lock().unwrap().insert(AC.clone(),|msg:&[u8]|->CallResult{
-                
-                Ok(msg.to_vec())
-            });
-            *AC+=1;
+    // one automation index per row of $rows
+    ( @cases $method:expr, $name:literal [$($query:expr)?] ($rows:expr) | ($headers:expr) | ($payload:expr) | $param:tt $(, $row:ident)? | $body:block ) => {
+        let rows: Vec<serde_json::Value> = $rows;
+        for row in rows{
+            test!(@request $method, $name [$($query)?] {Some(row)} | ($headers) | ($payload) | $param $(, $row)? | $body);
         }
-     
     };
-    ( $(#[$attr:meta])* http_put $name:literal | ($headers:expr) | ($payload:expr)  | $param:tt | $body:block ) => {
-        if let Ok(mut AC) = AT_COUNTER2.lock(){
-            let mut host = HOST_MAP.lock().unwrap().clone();
-            host.push_str($name);
-            COMMAND_MAP.lock().unwrap().insert(AC.clone(),host.clone());
-            
-            let mut headers = $headers;
-            let mut req = httparse::Request::new(&mut headers);
-            req.method = Some("PUT");
-            let http1x = request_to_http1x(&req);
-            let r = HttpRequest{
-                Http1x:http1x.clone(),
-                HttpBody:$payload,
-                ProxyUrl:String::from("")
-            };
-            REQUEST_MAP.lock().unwrap().insert(AC.clone(),r);
-            REQUEST_MAR_MAP.lock().unwrap().insert(AC.clone(),http1x);
-            RESPONSE_MAR_MAP.lock().unwrap().insert(AC.clone(),|msg:&[u8]|->CallResult{
-                
-                Ok(msg.to_vec())
-            });
-            *AC+=1;
-        }
-     
+    ( @method http_get ) => { "GET" };
+    ( @method http_post ) => { "POST" };
+    ( @method http_put ) => { "PUT" };
+    ( @method http_delete ) => { "DELETE" };
+    ( @method http_patch ) => { "PATCH" };
+    ( @method http_head ) => { "HEAD" };
+    ( @method http_options ) => { "OPTIONS" };
+    // data-driven: test http_get "/users/{{id}}" cases (rows) | ([]) | (vec![]) | res, row | {...}
+    ( $(#[$attr:meta])* http $method:literal $name:literal $(query ($query:expr))? cases ($rows:expr) | ($headers:expr) | ($payload:expr) | $param:tt $(, $row:ident)? | $body:block ) => {
+        test!(@cases $method, $name [$($query)?] ($rows) | ($headers) | ($payload) | $param $(, $row)? | $body);
+    };
+    ( $(#[$attr:meta])* $t:ident $name:literal $(query ($query:expr))? cases ($rows:expr) | ($headers:expr) | ($payload:expr) | $param:tt $(, $row:ident)? | $body:block ) => {
+        test!(@cases test!(@method $t), $name [$($query)?] ($rows) | ($headers) | ($payload) | $param $(, $row)? | $body);
+    };
+    ( $(#[$attr:meta])* http_get $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "GET", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    ( $(#[$attr:meta])* http_post $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "POST", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    ( $(#[$attr:meta])* http_put $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "PUT", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    ( $(#[$attr:meta])* http_delete $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "DELETE", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    ( $(#[$attr:meta])* http_patch $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "PATCH", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    ( $(#[$attr:meta])* http_head $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "HEAD", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    ( $(#[$attr:meta])* http_options $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request "OPTIONS", $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
+    };
+    // custom verbs: test http "PURGE" "/path" ...
+    ( $(#[$attr:meta])* http $method:literal $name:literal $(query ($query:expr))? | ($headers:expr) | ($payload:expr) | $param:tt | $body:block ) => {
+        test!(@request $method, $name [$($query)?] {None} | ($headers) | ($payload) | $param | $body);
     };
 }
  #[macro_export(local_inner_macros)]
@@ -180,6 +227,7 @@ macro_rules! test {
      ( name $name:ident ;host $host:literal; $($remainder:tt)* ) => {
         let mut host = String::from($host);
         *HOST_MAP.lock().unwrap() = host;
+        *SUITE_NAME.lock().unwrap() = String::from(_wasm_mock_macro__stringify!($name));
         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
      };
  
@@ -190,6 +238,40 @@ macro_rules! test {
  }
  #[macro_export(local_inner_macros)]
  macro_rules! __mock_suite_int {
+     // modify ws_res "3335-:3334" { text_regex("^ping") } (msg) { WsAction::Replace(Message::text("pong")) }
+     ( @int modify ws_req $name:literal $(origin ($origin:expr))? $(preview ($preview:expr))? { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             ($msg:ident)
+             $body:block
+             $($remainder:tt)*
+     ) => {
+         $( set_ws_origin($name, $origin); )?
+         $( set_ws_preview($name, WsDirection::Req, $preview); )?
+         report_setup(&_wasm_mock_macro__format!("invalid ws_req rule of {}",$name), add_ws_rule($name, WsDirection::Req, WsRule::new() $(.$key($($arg),*))* .handle(|$msg| Ok($body))));
+         REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_modify_req",$name),|msg:&[u8]|->CallResult{
+             ws_rules_req($name,msg)
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     ( @int modify ws_res $name:literal $(preview ($preview:expr))? { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             ($msg:ident)
+             $body:block
+             $($remainder:tt)*
+     ) => {
+         $( set_ws_preview($name, WsDirection::Res, $preview); )?
+         report_setup(&_wasm_mock_macro__format!("invalid ws_res rule of {}",$name), add_ws_rule($name, WsDirection::Res, WsRule::new() $(.$key($($arg),*))* .handle(|$msg| Ok($body))));
+         REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_modify_res",$name),|msg:&[u8]|->CallResult{
+             ws_rules_res($name,msg)
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* modify $t:ident $name:literal
+             ($param:ident, $req:ident)
+             $body:block
+             $($remainder:tt)*
+     ) => {
+         modify!( $(#[$attr])* $t $name | $param, $req | $body);
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
      ( @int $(#[$attr:meta])* modify $t:ident $name:literal 
              ($param:ident)
              $body:block
@@ -199,6 +281,70 @@ macro_rules! test {
          wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
      };
      
+     // scenario "checkout" { initial("Started") in_indexdb() }
+     ( @int scenario $name:literal { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             $($remainder:tt)*
+     ) => {
+         add_scenario($name, Scenario::new() $(.$key($($arg),*))*);
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     // har "/api" (include_str!("session.har"))
+     ( @int har $name:literal ($text:expr)
+             $($remainder:tt)*
+     ) => {
+         report_setup(&_wasm_mock_macro__format!("invalid HAR of {}",$name), add_har_stubs($name, $text).map(|_| ()));
+         REGISTRY.lock().unwrap().entry(_wasm_mock_macro__format!("{}_http_modify_req",$name)).or_insert(|msg:&[u8]|->CallResult{
+             stub_http_req($name,msg)
+         });
+         REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_http_modify_res",$name),|msg:&[u8]|->CallResult{
+             stub_http_res($name,msg)
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     // fault "/users" { latency_between(100, 2000) status(0.1, 503) seed(42) }
+     ( @int fault $name:literal { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             $($remainder:tt)*
+     ) => {
+         add_fault($name, Fault::new() $(.$key($($arg),*))*);
+         REGISTRY.lock().unwrap().entry(_wasm_mock_macro__format!("{}_http_modify_res",$name)).or_insert(|msg:&[u8]|->CallResult{
+             fault_http_res($name,msg)
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     // compare http "/users" { ignore("/items/*/updatedAt") tolerance(0.01) unordered("/items") }
+     ( @int compare http $name:literal { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             $($remainder:tt)*
+     ) => {
+         REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_http_fiddler_ab",$name),|msg:&[u8]|->CallResult{
+             let ab = foo_fiddler_ab(msg)?;
+             compare::Comparison::new() $(.$key($($arg),*))* .report_http(&ab)?;
+             Ok(Vec::new())
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     ( @int compare tcp $name:literal { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             $($remainder:tt)*
+     ) => {
+         REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_tcp_fiddler_ab",$name),|msg:&[u8]|->CallResult{
+             let ab = tcp_foo_fiddler_ab(msg)?;
+             compare::Comparison::new() $(.$key($($arg),*))* .report_tcp($name, &ab)?;
+             Ok(Vec::new())
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
+     // stub "/users" { method("GET") path_regex("^/users/\\d+$") respond(StubResponse::json(200, json!({}))) }
+     ( @int stub $name:literal { $($key:ident ( $($arg:expr),* $(,)? ))* }
+             $($remainder:tt)*
+     ) => {
+         report_setup(&_wasm_mock_macro__format!("invalid stub of {}",$name), add_stub($name, Stub::new() $(.$key($($arg),*))*));
+         REGISTRY.lock().unwrap().entry(_wasm_mock_macro__format!("{}_http_modify_req",$name)).or_insert(|msg:&[u8]|->CallResult{
+             stub_http_req($name,msg)
+         });
+         REGISTRY.lock().unwrap().insert(_wasm_mock_macro__format!("{}_http_modify_res",$name),|msg:&[u8]|->CallResult{
+             stub_http_res($name,msg)
+         });
+         wasm_mock_macro::__mock_suite_int!(@int $($remainder)*);
+     };
      ( @int $item:item
              $($remainder:tt)*
      ) => {
@@ -212,16 +358,148 @@ macro_rules! test {
  }
  #[macro_export(local_inner_macros)]
  macro_rules! __test_suite_int {
-     ( @int $(#[$attr:meta])* test $t:ident $name:literal
+     ( @int $(#[$attr:meta])* test http $method:literal $name:literal $(query ($query:expr))? cases [$($case:tt),* $(,)?]
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident $(, $row:ident)?)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* http $method $name $(query ($query))? cases (::std::vec::Vec::from([$(serde_json::json!($case)),*])) | ($headers) | ($payload) | $res $(, $row)? | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test $t:ident $name:literal $(query ($query:expr))? cases [$($case:tt),* $(,)?]
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident $(, $row:ident)?)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* $t $name $(query ($query))? cases (::std::vec::Vec::from([$(serde_json::json!($case)),*])) | ($headers) | ($payload) | $res $(, $row)? | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test http $method:literal $name:literal $(query ($query:expr))? from_csv ($csv:expr)
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident $(, $row:ident)?)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* http $method $name $(query ($query))? cases (cases_or_report(cases_from_csv($csv),"csv")) | ($headers) | ($payload) | $res $(, $row)? | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test $t:ident $name:literal $(query ($query:expr))? from_csv ($csv:expr)
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident $(, $row:ident)?)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* $t $name $(query ($query))? cases (cases_or_report(cases_from_csv($csv),"csv")) | ($headers) | ($payload) | $res $(, $row)? | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test http $method:literal $name:literal $(query ($query:expr))? from_json ($json:expr)
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident $(, $row:ident)?)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* http $method $name $(query ($query))? cases (cases_or_report(cases_from_json($json),"json")) | ($headers) | ($payload) | $res $(, $row)? | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test $t:ident $name:literal $(query ($query:expr))? from_json ($json:expr)
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident $(, $row:ident)?)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* $t $name $(query ($query))? cases (cases_or_report(cases_from_json($json),"json")) | ($headers) | ($payload) | $res $(, $row)? | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test http $method:literal $name:literal $(query ($query:expr))?
+            ($headers:expr)
+            ($payload:expr)
+            ($res:ident)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
+            $body2:block
+            $($remainder:tt)*
+     ) => {
+         test!( $(#[$attr])* http $method $name $(query ($query))? | ($headers) | ($payload) | $res | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int $(#[$attr:meta])* test $t:ident $name:literal $(query ($query:expr))?
             ($headers:expr)
             ($payload:expr)
             ($res:ident)
+            $(capture { $($var:ident = $kind:ident $($arg:literal)?),* $(,)? })?
             $body2:block
             $($remainder:tt)*
      ) => {
-         test!( $(#[$attr])* $t $name | ($headers) | ($payload) | $res |$body2);
+         test!( $(#[$attr])* $t $name $(query ($query))? | ($headers) | ($payload) | $res | { wasm_mock_macro::__test_suite_int!(@capture $res [$($($var = $kind $($arg)?),*)?] $body2) });
          wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
      };  
+     // capture { token = body "/data/token", sid = cookie "sid" } ahead of the assertion block
+     ( @capture $res:ident [$($var:ident = $kind:ident $($arg:literal)?),*] $body:block ) => {
+         {
+             $( capture_var(_wasm_mock_macro__stringify!($var),&$res,_wasm_mock_macro__stringify!($kind),_wasm_mock_macro__concat!("" $(,$arg)?))?; )*
+             $body
+         }
+     };
+     ( @int before_all $body:block
+            $($remainder:tt)*
+     ) => {
+         add_hook(Hook::BeforeAll,||->HookResult{
+             $body;
+             Ok(())
+         });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int before_each $body:block
+            $($remainder:tt)*
+     ) => {
+         add_hook(Hook::BeforeEach,||->HookResult{
+             $body;
+             Ok(())
+         });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int after_each $body:block
+            $($remainder:tt)*
+     ) => {
+         add_hook(Hook::AfterEach,||->HookResult{
+             $body;
+             Ok(())
+         });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     ( @int after_all $body:block
+            $($remainder:tt)*
+     ) => {
+         add_hook(Hook::AfterAll,||->HookResult{
+             $body;
+             Ok(())
+         });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
+     // fixture $name { value }: evaluated with before_all, read back with fixture("$name")
+     ( @int fixture $name:ident $body:block
+            $($remainder:tt)*
+     ) => {
+         add_hook(Hook::BeforeAll,||->HookResult{
+             let value = $body;
+             set_fixture(_wasm_mock_macro__stringify!($name),&value)?;
+             Ok(())
+         });
+         wasm_mock_macro::__test_suite_int!( @int $($remainder)*);
+     };
      ( @int $item:item
              $($remainder:tt)*
      ) => {
@@ -0,0 +1,637 @@
+//! Mock and contract-test generation from an OpenAPI 3 spec.
+//!
+//! [`Spec::parse`] reads a JSON or YAML spec and lists its [`Operation`]s with local `$ref`s inlined. Two
+//! crate roots can be written from it, by the `wasm-mock-openapi` binary or from a build script:
+//!
+//! * [`generate_mock`]: a `mock_suite!` with one `stub` per operation, matching its method and path and
+//!   answering with the example of its success response
+//! * [`generate_tests`]: a `test_suite!` calling every operation with the examples of its parameters and
+//!   request body, asserting the status code with `foo_assert_eq!` and the body with
+//!   `foo_assert_json_schema!`
+//!
+//! Examples come from `example`/`examples` of the spec, or are built from the schema when it has none.
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fmt::Write;
+use wasm_mock_replay::source::{literal, raw_literal};
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+/// Parameter of an operation, `location` is `path`, `query`, `header` or `cookie`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub location: String,
+    pub required: bool,
+    pub example: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    /// Upper case
+    pub method: String,
+    /// Path template, e.g. `/users/{id}`
+    pub path: String,
+    /// `operationId`, `METHOD path` without one
+    pub id: String,
+    pub parameters: Vec<Parameter>,
+    /// Media type and example of the request body
+    pub request_body: Option<(String, Value)>,
+    /// Status code of the success response
+    pub status: u16,
+    /// Media type of the success response
+    pub content_type: Option<String>,
+    /// Example of the success response body
+    pub example: Option<Value>,
+    /// JSON Schema of the success response body
+    pub schema: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    pub title: String,
+    /// `url` of the first server
+    pub server: Option<String>,
+    pub operations: Vec<Operation>,
+}
+
+/// `value` with every local `$ref` replaced by its target, a recursive `$ref` by `{}`
+fn inline_refs(doc: &Value, value: &Value, seen: &mut Vec<String>) -> Value {
+    match value {
+        Value::Object(m) => {
+            if let Some(Value::String(r)) = m.get("$ref") {
+                if seen.contains(r) {
+                    return Value::Object(Map::new());
+                }
+                let target = r.strip_prefix('#').and_then(|p| doc.pointer(p));
+                return match target {
+                    Some(target) => {
+                        seen.push(r.clone());
+                        let inlined = inline_refs(doc, target, seen);
+                        seen.pop();
+                        inlined
+                    }
+                    None => value.clone(),
+                };
+            }
+            Value::Object(m.iter().map(|(k, v)| (k.clone(), inline_refs(doc, v, seen))).collect())
+        }
+        Value::Array(a) => Value::Array(a.iter().map(|v| inline_refs(doc, v, seen)).collect()),
+        v => v.clone(),
+    }
+}
+
+/// `schema` of OpenAPI 3.0 as JSON Schema: `nullable: true` admits `null`, and the boolean
+/// `exclusiveMinimum`/`exclusiveMaximum` take the value of `minimum`/`maximum` as in draft-07
+pub fn json_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(m) => {
+            let mut out: Map<String, Value> = m
+                .iter()
+                .filter(|(k, _)| k.as_str() != "nullable" && k.as_str() != "example")
+                .map(|(k, v)| {
+                    let v = match k.as_str() {
+                        // names of properties are not schemas, their values are
+                        "properties" | "patternProperties" | "definitions" | "$defs" => match v {
+                            Value::Object(props) => {
+                                Value::Object(props.iter().map(|(n, s)| (n.clone(), json_schema(s))).collect())
+                            }
+                            v => v.clone(),
+                        },
+                        "enum" | "const" | "default" | "examples" | "required" => v.clone(),
+                        _ => json_schema(v),
+                    };
+                    (k.clone(), v)
+                })
+                .collect();
+            if m.get("nullable") == Some(&Value::Bool(true)) {
+                match out.get("type").cloned() {
+                    Some(Value::String(t)) => {
+                        out.insert(String::from("type"), Value::from(vec![t, String::from("null")]));
+                    }
+                    Some(Value::Array(mut types)) => {
+                        types.push(Value::from("null"));
+                        out.insert(String::from("type"), Value::Array(types));
+                    }
+                    _ => {}
+                }
+                if let Some(Value::Array(values)) = out.get_mut("enum") {
+                    values.push(Value::Null);
+                }
+            }
+            for (exclusive, bound) in [("exclusiveMinimum", "minimum"), ("exclusiveMaximum", "maximum")] {
+                if let Some(Value::Bool(flag)) = out.get(exclusive).cloned() {
+                    match out.remove(bound) {
+                        Some(value) if flag => {
+                            out.insert(String::from(exclusive), value);
+                        }
+                        Some(value) => {
+                            out.insert(String::from(bound), value);
+                            out.remove(exclusive);
+                        }
+                        None => {
+                            out.remove(exclusive);
+                        }
+                    }
+                }
+            }
+            Value::Object(out)
+        }
+        Value::Array(a) => Value::Array(a.iter().map(json_schema).collect()),
+        v => v.clone(),
+    }
+}
+
+/// Example value of `schema`
+pub fn example_of(schema: &Value) -> Value {
+    example_at(schema, 0)
+}
+
+fn example_at(schema: &Value, depth: usize) -> Value {
+    for key in ["example", "const", "default"] {
+        if let Some(v) = schema.get(key) {
+            return v.clone();
+        }
+    }
+    if let Some(Value::Array(examples)) = schema.get("examples") {
+        if let Some(v) = examples.first() {
+            return v.clone();
+        }
+    }
+    if let Some(Value::Array(values)) = schema.get("enum") {
+        if let Some(v) = values.first() {
+            return v.clone();
+        }
+    }
+    if depth > 8 {
+        return Value::Null;
+    }
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        let mut merged = Map::new();
+        for s in all {
+            if let Value::Object(m) = example_at(s, depth + 1) {
+                merged.extend(m);
+            }
+        }
+        return Value::Object(merged);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(Value::Array(options)) = schema.get(key) {
+            if let Some(s) = options.first() {
+                return example_at(s, depth + 1);
+            }
+        }
+    }
+    let ty = match schema.get("type") {
+        Some(Value::String(t)) => t.as_str(),
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).find(|t| *t != "null").unwrap_or("null"),
+        _ if schema.get("properties").is_some() => "object",
+        _ if schema.get("items").is_some() => "array",
+        _ => "",
+    };
+    // an exclusive minimum, `exclusiveMinimum: true` of OpenAPI 3.0 or the number of 3.1, is not an example
+    let (minimum, exclusive) = match (schema.get("minimum").and_then(Value::as_f64), schema.get("exclusiveMinimum")) {
+        (_, Some(Value::Number(n))) => (n.as_f64(), true),
+        (minimum, Some(Value::Bool(true))) => (minimum, true),
+        (minimum, _) => (minimum, false),
+    };
+    let minimum = minimum.unwrap_or(0.0).max(0.0);
+    match ty {
+        "object" => {
+            let mut out = Map::new();
+            if let Some(Value::Object(props)) = schema.get("properties") {
+                for (name, s) in props {
+                    out.insert(name.clone(), example_at(s, depth + 1));
+                }
+            }
+            Value::Object(out)
+        }
+        "array" => {
+            let item = schema.get("items").map(|s| example_at(s, depth + 1)).unwrap_or(Value::Null);
+            if item.is_null() {
+                return Value::Array(vec![]);
+            }
+            let count = schema.get("minItems").and_then(Value::as_u64).unwrap_or(1).max(1);
+            Value::Array((0..count).map(|_| item.clone()).collect())
+        }
+        "string" => Value::from(match schema.get("format").and_then(Value::as_str) {
+            Some("date-time") => "1970-01-01T00:00:00Z",
+            Some("date") => "1970-01-01",
+            Some("time") => "00:00:00Z",
+            Some("uuid") => "00000000-0000-4000-8000-000000000000",
+            Some("email") => "user@example.com",
+            Some("uri") | Some("url") => "http://example.com",
+            Some("ipv4") => "127.0.0.1",
+            Some("ipv6") => "::1",
+            _ => "string",
+        }),
+        "integer" if exclusive => Value::from(minimum.floor() as i64 + 1),
+        "integer" => Value::from(minimum.ceil() as i64),
+        "number" if exclusive => Value::from(minimum + 1.0),
+        "number" => Value::from(minimum),
+        "boolean" => Value::Bool(true),
+        _ => Value::Null,
+    }
+}
+
+/// Example of a parameter or media type object, from `example`, `examples` or its schema
+fn example_of_object(object: &Value) -> Value {
+    if let Some(v) = object.get("example") {
+        return v.clone();
+    }
+    if let Some(Value::Object(examples)) = object.get("examples") {
+        if let Some(v) = examples.values().find_map(|e| e.get("value")) {
+            return v.clone();
+        }
+    }
+    object.get("schema").map(example_of).unwrap_or(Value::Null)
+}
+
+/// Media type of `content` to use, JSON first
+fn pick_media(content: &Value) -> Option<(String, &Value)> {
+    let content = content.as_object()?;
+    content
+        .iter()
+        .find(|(t, _)| t.contains("json"))
+        .or_else(|| content.iter().next())
+        .map(|(t, m)| (t.clone(), m))
+}
+
+fn operation(method: &str, path: &str, item: &Value, op: &Value) -> Operation {
+    let mut parameters: Vec<Parameter> = vec![];
+    let declared = item.get("parameters").into_iter().chain(op.get("parameters")).filter_map(Value::as_array);
+    for p in declared.flatten() {
+        let name = p.get("name").and_then(Value::as_str).unwrap_or("").to_string();
+        let location = p.get("in").and_then(Value::as_str).unwrap_or("query").to_string();
+        // operation parameters override the ones of the path item
+        parameters.retain(|q| !(q.name == name && q.location == location));
+        parameters.push(Parameter {
+            required: location == "path" || p.get("required") == Some(&Value::Bool(true)),
+            example: example_of_object(p),
+            name,
+            location,
+        });
+    }
+    let request_body = op
+        .pointer("/requestBody/content")
+        .and_then(pick_media)
+        .map(|(t, media)| (t, example_of_object(media)));
+    let responses = op.get("responses").and_then(Value::as_object);
+    let success = responses.and_then(|r| {
+        let mut codes: Vec<(&String, &Value)> = r.iter().filter(|(c, _)| c.starts_with('2')).collect();
+        codes.sort_by(|a, b| a.0.cmp(b.0));
+        codes.first().copied().or_else(|| r.get_key_value("default"))
+    });
+    let status = success
+        .and_then(|(code, _)| code.replace(['X', 'x'], "0").parse().ok())
+        .filter(|s: &u16| *s >= 100)
+        .unwrap_or(200);
+    let media = success.and_then(|(_, r)| r.get("content")).and_then(pick_media);
+    Operation {
+        method: method.to_ascii_uppercase(),
+        path: path.to_string(),
+        id: op
+            .get("operationId")
+            .and_then(Value::as_str)
+            .map(String::from)
+            .unwrap_or_else(|| format!("{} {}", method.to_ascii_uppercase(), path)),
+        parameters,
+        request_body,
+        status,
+        content_type: media.as_ref().map(|(t, _)| t.clone()),
+        example: media.as_ref().map(|(_, m)| example_of_object(m)),
+        schema: media.as_ref().and_then(|(_, m)| m.get("schema")).map(json_schema),
+    }
+}
+
+impl Spec {
+    /// Spec from JSON or YAML text
+    pub fn parse(text: &str) -> Result<Spec, Box<dyn Error>> {
+        let doc: Value = match serde_json::from_str(text) {
+            Ok(doc) => doc,
+            Err(_) => serde_norway::from_str(text)?,
+        };
+        Spec::from_value(&doc)
+    }
+    pub fn from_value(doc: &Value) -> Result<Spec, Box<dyn Error>> {
+        if doc.get("openapi").is_none() {
+            return Err("not an OpenAPI 3 document, `openapi` is missing".into());
+        }
+        let resolved = inline_refs(doc, doc, &mut vec![]);
+        let mut operations = vec![];
+        for (path, item) in resolved.get("paths").and_then(Value::as_object).into_iter().flatten() {
+            for method in METHODS {
+                if let Some(op) = item.get(method) {
+                    operations.push(operation(method, path, item, op));
+                }
+            }
+        }
+        Ok(Spec {
+            title: resolved.pointer("/info/title").and_then(Value::as_str).unwrap_or("").to_string(),
+            server: resolved.pointer("/servers/0/url").and_then(Value::as_str).map(String::from),
+            operations,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// Name of the generated suites
+    pub name: String,
+    /// Mock every operation under this fiddler target instead of one target per path
+    pub mock_name: Option<String>,
+    /// Host of the tests, the first server of the spec or `http://localhost` by default
+    pub host: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            name: String::from("openapi"),
+            mock_name: None,
+            host: None,
+        }
+    }
+}
+
+fn is_param(segment: &str) -> bool {
+    segment.starts_with('{') && segment.ends_with('}')
+}
+
+/// Anchored regular expression of a path template
+pub fn path_regex(path: &str) -> String {
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|s| {
+            if is_param(s) {
+                String::from("[^/]+")
+            } else {
+                s.chars()
+                    .map(|c| if "\\.+*?()|[]{}^$".contains(c) { format!("\\{}", c) } else { c.to_string() })
+                    .collect()
+            }
+        })
+        .collect();
+    format!("^{}$", segments.join("/"))
+}
+
+/// Fiddler target of a path template: the path itself, or `\/users\/.*` when it has parameters
+pub fn mock_target(path: &str) -> String {
+    if !path.split('/').any(is_param) {
+        return path.to_string();
+    }
+    path.split('/')
+        .map(|s| if is_param(s) { ".*" } else { s })
+        .collect::<Vec<_>>()
+        .join("\\/")
+}
+
+fn param_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::from("1"),
+        v => v.to_string(),
+    }
+}
+
+fn body_text(content_type: &str, example: &Value) -> String {
+    match example {
+        Value::String(s) if !content_type.contains("json") => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+fn header(file: &str) -> String {
+    format!(
+        "// Generated by wasm-mock-openapi, edit freely.
+extern crate wapc_guest as guest;
+use guest::prelude::*;
+use wasm_mock_macro::{};
+use wasm_mock_util::*;
+
+#[no_mangle]
+pub extern \"C\" fn _start() {{
+",
+        file
+    )
+}
+
+/// Rust source of a crate root whose `mock_suite!` answers every operation of `spec` with its example
+pub fn generate_mock(spec: &Spec, options: &Options) -> String {
+    let mut out = header("mock_suite");
+    writeln!(out, "    mock_suite!{{\n        name {};", options.name).unwrap();
+    for op in &spec.operations {
+        let target = options.mock_name.clone().unwrap_or_else(|| mock_target(&op.path));
+        writeln!(out, "        // {}", op.id).unwrap();
+        writeln!(out, "        stub {} {{", literal(&target)).unwrap();
+        writeln!(out, "            method({})", literal(&op.method)).unwrap();
+        if op.path.split('/').any(is_param) {
+            writeln!(out, "            path_regex({})", raw_literal(&path_regex(&op.path))).unwrap();
+        } else {
+            writeln!(out, "            path({})", literal(&op.path)).unwrap();
+        }
+        let body = match (&op.content_type, &op.example) {
+            (Some(t), Some(example)) => body_text(t, example),
+            _ => String::new(),
+        };
+        write!(out, "            respond(StubResponse::text({}, {})", op.status, raw_literal(&body)).unwrap();
+        if let Some(t) = &op.content_type {
+            write!(out, ".header(\"Content-Type\", {})", literal(t)).unwrap();
+        }
+        out.push_str(")\n        }\n");
+    }
+    out.push_str("    }\n}\nfn main() {}\n");
+    out
+}
+
+/// Rust source of a crate root whose `test_suite!` calls every operation of `spec` and checks the status
+/// and the schema of the response
+pub fn generate_tests(spec: &Spec, options: &Options) -> String {
+    let host = options
+        .host
+        .clone()
+        .or_else(|| spec.server.clone().filter(|s| s.contains("://")))
+        .unwrap_or_else(|| String::from("http://localhost"));
+    let mut out = header("test_suite");
+    writeln!(out, "    test_suite!{{\n        name {};\n        host {};", options.name, literal(&host)).unwrap();
+    for op in &spec.operations {
+        let path: Vec<String> = op
+            .path
+            .split('/')
+            .map(|s| {
+                let name = s.trim_start_matches('{').trim_end_matches('}');
+                match op.parameters.iter().find(|p| is_param(s) && p.location == "path" && p.name == name) {
+                    Some(p) => param_text(&p.example),
+                    None => s.to_string(),
+                }
+            })
+            .collect();
+        let query: Vec<String> = op
+            .parameters
+            .iter()
+            .filter(|p| p.location == "query" && p.required)
+            .map(|p| format!("({}, {})", literal(&p.name), literal(&param_text(&p.example))))
+            .collect();
+        let mut headers: Vec<(String, String)> = op
+            .parameters
+            .iter()
+            .filter(|p| p.location == "header" && p.required)
+            .map(|p| (p.name.clone(), param_text(&p.example)))
+            .collect();
+        let payload = match &op.request_body {
+            Some((t, example)) => {
+                headers.push((String::from("Content-Type"), t.clone()));
+                format!("{}.as_bytes().to_vec()", raw_literal(&body_text(t, example)))
+            }
+            None => String::from("vec![]"),
+        };
+        let headers: Vec<String> = headers
+            .iter()
+            .map(|(n, v)| format!("httparse::Header{{name: {}, value: {}.as_bytes()}}", literal(n), literal(v)))
+            .collect();
+        writeln!(out, "        // {}", op.id).unwrap();
+        write!(out, "        test http {} {}", literal(&op.method), literal(&path.join("/"))).unwrap();
+        if !query.is_empty() {
+            write!(out, " query (vec![{}])", query.join(", ")).unwrap();
+        }
+        writeln!(out, " ([{}])({})(res) {{", headers.join(", "), payload).unwrap();
+        writeln!(
+            out,
+            "            foo_assert_eq!(res.StatusCode, {}, {});",
+            literal(&op.status.to_string()),
+            literal(&format!("{} status", op.id))
+        )
+        .unwrap();
+        if let Some(schema) = &op.schema {
+            writeln!(
+                out,
+                "            let schema: serde_json::Value = serde_json::from_str({})?;",
+                raw_literal(&schema.to_string())
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "            foo_assert_json_schema!(res.body_json(), schema, {});",
+                literal(&format!("{} schema", op.id))
+            )
+            .unwrap();
+        }
+        out.push_str("        }\n");
+    }
+    out.push_str("    }\n}\nfn main() {}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SPEC: &str = r##"
+openapi: 3.0.3
+info: {title: users, version: "1"}
+servers: [{url: "http://localhost:3000"}]
+paths:
+  /users/{id}:
+    parameters:
+      - {name: id, in: path, required: true, schema: {type: integer}, example: 7}
+    get:
+      operationId: getUser
+      parameters:
+        - {name: lang, in: query, required: true, schema: {type: string, enum: [en, fr]}}
+      responses:
+        "200":
+          description: user
+          content:
+            application/json:
+              schema: {$ref: "#/components/schemas/User"}
+        "404": {description: missing}
+  /users:
+    post:
+      requestBody:
+        content:
+          application/json:
+            example: {name: ann}
+      responses:
+        "201":
+          description: created
+          content:
+            application/json:
+              example: {id: 1, name: ann}
+              schema: {$ref: "#/components/schemas/User"}
+components:
+  schemas:
+    User:
+      type: object
+      required: [id]
+      properties:
+        id: {type: integer, minimum: 1}
+        name: {type: string, nullable: true}
+        friends: {type: array, items: {$ref: "#/components/schemas/User"}}
+"##;
+
+    #[test]
+    fn reads_operations() {
+        let spec = Spec::parse(SPEC).unwrap();
+        assert_eq!(spec.operations.len(), 2);
+        let get = spec.operations.iter().find(|o| o.method == "GET").unwrap();
+        assert_eq!((get.method.as_str(), get.id.as_str(), get.status), ("GET", "getUser", 200));
+        assert_eq!(get.parameters[0].example, json!(7));
+        assert_eq!(get.parameters[1].example, json!("en"));
+        assert_eq!(get.example, Some(json!({"id": 1, "name": "string", "friends": []})));
+        let schema = get.schema.as_ref().unwrap();
+        assert_eq!(schema.pointer("/properties/name/type"), Some(&json!(["string", "null"])));
+        assert_eq!(schema.pointer("/properties/friends/items"), Some(&json!({})));
+        let post = spec.operations.iter().find(|o| o.method == "POST").unwrap();
+        assert_eq!(post.id, "POST /users");
+        assert_eq!(post.request_body, Some((String::from("application/json"), json!({"name": "ann"}))));
+        assert_eq!(post.example, Some(json!({"id": 1, "name": "ann"})));
+    }
+
+    #[test]
+    fn generates_suites() {
+        let spec = Spec::parse(SPEC).unwrap();
+        let mock = generate_mock(&spec, &Options::default());
+        assert!(mock.contains(r#"stub "\\/users\\/.*" {"#));
+        assert!(mock.contains(r#"path_regex(r"^/users/[^/]+$")"#));
+        assert!(mock.contains(r##"respond(StubResponse::text(201, r#"{"id":1,"name":"ann"}"#).header("Content-Type", "application/json"))"##));
+        let tests = generate_tests(&spec, &Options::default());
+        assert!(tests.contains(r#"host "http://localhost:3000";"#));
+        assert!(tests.contains(r#"test http "GET" "/users/7" query (vec![("lang", "en")]) ([])(vec![])(res) {"#));
+        assert!(tests.contains(r#"foo_assert_eq!(res.StatusCode, "201", "POST /users status");"#));
+        assert!(tests.contains("foo_assert_json_schema!(res.body_json(), schema, \"getUser schema\");"));
+    }
+
+    #[test]
+    fn exclusive_bounds_of_openapi_3_0() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "price": {"type": "number", "minimum": 0, "exclusiveMinimum": true, "maximum": 100, "exclusiveMaximum": false},
+                "rank": {"type": "integer", "maximum": 10, "exclusiveMaximum": true},
+                "exclusiveMinimum": {"type": "boolean"}
+            }
+        });
+        assert_eq!(
+            json_schema(&schema)["properties"],
+            json!({
+                "price": {"type": "number", "exclusiveMinimum": 0, "maximum": 100},
+                "rank": {"type": "integer", "exclusiveMaximum": 10},
+                "exclusiveMinimum": {"type": "boolean"}
+            })
+        );
+        assert_eq!(json_schema(&json!({"exclusiveMinimum": true})), json!({}));
+        assert_eq!(json_schema(&json!({"minimum": 2, "exclusiveMinimum": 1})), json!({"minimum": 2, "exclusiveMinimum": 1}));
+        assert_eq!(example_of(&json!({"type": "integer", "minimum": 3, "exclusiveMinimum": true})), json!(4));
+        assert_eq!(example_of(&json!({"type": "number", "exclusiveMinimum": 0.5})), json!(1.5));
+        assert_eq!(example_of(&json!({"type": "integer", "minimum": 3})), json!(3));
+    }
+
+    #[test]
+    fn regex_and_targets() {
+        assert_eq!(path_regex("/v1.0/{id}"), "^/v1\\.0/[^/]+$");
+        assert_eq!(mock_target("/users"), "/users");
+        assert_eq!(mock_target("/a/{b}/c"), "\\/a\\/.*\\/c");
+    }
+}
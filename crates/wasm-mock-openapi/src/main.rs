@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::fs;
+use wasm_mock_openapi::{generate_mock, generate_tests, Options, Spec};
+use wasm_mock_replay::check_identifier;
+use wasm_mock_replay::cli::{exit_on_error, Args};
+
+const USAGE: &str = "usage: wasm-mock-openapi <spec.yaml|spec.json> [options]
+
+options:
+  --mock <file>         write the mock_suite! crate root to <file>
+  --test <file>         write the test_suite! crate root to <file>
+  --name <name>         name of the generated suites, a Rust identifier (default: openapi)
+  --mock-name <target>  mock every operation under one fiddler target instead of one per path
+  --host <url>          host of the tests (default: first server of the spec)
+
+without --mock and --test the mock suite is written to stdout";
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = Args::new(USAGE);
+    let mut mock = None;
+    let mut test = None;
+    let mut options = Options::default();
+    while let Some(option) = args.next_option() {
+        match option.as_str() {
+            "--mock" => mock = Some(args.value(&option)?),
+            "--test" => test = Some(args.value(&option)?),
+            "--name" => options.name = args.value(&option)?,
+            "--mock-name" => options.mock_name = Some(args.value(&option)?),
+            "--host" => options.host = Some(args.value(&option)?),
+            _ => return Err(args.unexpected(&option)),
+        }
+    }
+    check_identifier(&options.name)?;
+    let spec = Spec::parse(&fs::read_to_string(args.input()?)?)?;
+    if mock.is_none() && test.is_none() {
+        print!("{}", generate_mock(&spec, &options));
+    }
+    if let Some(path) = mock {
+        fs::write(path, generate_mock(&spec, &options))?;
+    }
+    if let Some(path) = test {
+        fs::write(path, generate_tests(&spec, &options))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run());
+}
@@ -0,0 +1,89 @@
+//! Command line of the generator binaries, shared with `wasm-mock-openapi`:
+//! `<input> [options]`, where an option takes its value from the next argument.
+use std::error::Error;
+use std::iter::Skip;
+use std::{env, process};
+
+/// Arguments of a generator, the input file aside from the options
+pub struct Args<I: Iterator<Item = String>> {
+    args: I,
+    usage: &'static str,
+    input: Option<String>,
+}
+
+impl Args<Skip<env::Args>> {
+    /// Arguments of the process
+    pub fn new(usage: &'static str) -> Self {
+        Args::from_iter(usage, env::args().skip(1))
+    }
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    pub fn from_iter(usage: &'static str, args: I) -> Self {
+        Args { args, usage, input: None }
+    }
+    /// Next option, `None` after the last one. The first argument that is not an option is the input;
+    /// `-h` and `--help` print the usage and exit.
+    pub fn next_option(&mut self) -> Option<String> {
+        for arg in self.args.by_ref() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", self.usage);
+                    process::exit(0);
+                }
+                _ if self.input.is_none() && !arg.starts_with('-') => self.input = Some(arg),
+                _ => return Some(arg),
+            }
+        }
+        None
+    }
+    /// Value of `option`
+    pub fn value(&mut self, option: &str) -> Result<String, String> {
+        self.args.next().ok_or_else(|| format!("{} needs a value", option))
+    }
+    /// Error of an option the generator does not know
+    pub fn unexpected(&self, option: &str) -> Box<dyn Error> {
+        format!("unexpected argument {}\n\n{}", option, self.usage).into()
+    }
+    /// Input file, the usage is the error when there is none
+    pub fn input(&self) -> Result<&str, &'static str> {
+        self.input.as_deref().ok_or(self.usage)
+    }
+}
+
+/// Exits with the status 1 after printing the error of `result`
+pub fn exit_on_error(result: Result<(), Box<dyn Error>>) {
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args_of(list: &[&str]) -> Args<std::vec::IntoIter<String>> {
+        Args::from_iter("usage", list.iter().map(|a| a.to_string()).collect::<Vec<_>>().into_iter())
+    }
+
+    #[test]
+    fn input_and_options_in_any_order() {
+        let mut args = args_of(&["--name", "x", "rec.json", "--ignore-body"]);
+        assert_eq!(args.next_option().as_deref(), Some("--name"));
+        assert_eq!(args.value("--name").unwrap(), "x");
+        assert_eq!(args.next_option().as_deref(), Some("--ignore-body"));
+        assert_eq!(args.next_option(), None);
+        assert_eq!(args.input(), Ok("rec.json"));
+    }
+
+    #[test]
+    fn missing_value_input_and_second_input() {
+        let mut args = args_of(&["a.json", "b.json", "--name"]);
+        assert_eq!(args.next_option().as_deref(), Some("b.json"));
+        assert!(args.unexpected("b.json").to_string().starts_with("unexpected argument b.json"));
+        assert_eq!(args.next_option().as_deref(), Some("--name"));
+        assert_eq!(args.value("--name"), Err(String::from("--name needs a value")));
+        assert_eq!(args_of(&[]).input(), Err("usage"));
+    }
+}
@@ -0,0 +1,375 @@
+//! Turns a fiddler recording back into a mock.
+//!
+//! A recording is the JSON the mock server exports for a fiddler session:
+//!
+//! * `{"http": [..], "tcp": [..]}`, or only the `http` array
+//! * an HTTP exchange is `{"request": RequestReceivedInMock, "response": HttpResponse}`, or an `HttpResponse`
+//!   whose `http_req` holds the request
+//! * a TCP stream is `{"name": "3335-:3334", "items": [TcpItem, ..]}` with the items sent by the remote
+//!   connection
+//!
+//! [`generate`] writes a Rust source file whose `mock_suite!` replays it: one `stub` per HTTP exchange,
+//! matching method, path, query and JSON body, and one `modify tcp_res` per TCP stream. Identical requests
+//! answered differently are chained with a scenario so they are replayed in the recorded order. Volatile
+//! values are left out of the stubs ([`Options::drop_headers`], [`Options::ignore_queries`],
+//! [`Options::ignore_body`]) or turned into response templates ([`Options::templates`]).
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::Write;
+
+pub mod cli;
+pub mod source;
+pub use source::check_identifier;
+use source::{literal, raw_literal};
+
+/// Request of a recorded HTTP exchange
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct RecordedRequest {
+    pub http_method: String,
+    pub http_path: String,
+    pub http_param: Option<BTreeMap<String, Vec<String>>>,
+    pub http_header: Option<HashMap<String, Value>>,
+    pub http_body: Value,
+    pub http_body_raw: String,
+}
+
+/// Response of a recorded HTTP exchange
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct RecordedResponse {
+    pub status_code: String,
+    pub http_header: Option<BTreeMap<String, Value>>,
+    pub http_body: Value,
+    pub http_body_raw: String,
+    pub http_req: RecordedRequest,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Exchange {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Framed message sent by the remote connection
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct TcpItem {
+    pub Payload: String,
+    pub String: String,
+}
+
+/// Items recorded for one TCP fiddler mock
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TcpStream {
+    pub name: String,
+    pub items: Vec<TcpItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Recording {
+    pub http: Vec<Exchange>,
+    pub tcp: Vec<TcpStream>,
+}
+
+fn exchange_of(value: Value) -> Result<Exchange, serde_json::Error> {
+    if value.get("request").is_some() || value.get("response").is_some() {
+        return serde_json::from_value(value);
+    }
+    let response: RecordedResponse = serde_json::from_value(value)?;
+    Ok(Exchange {
+        request: response.http_req.clone(),
+        response,
+    })
+}
+
+impl Recording {
+    pub fn from_json(text: &str) -> Result<Recording, Box<dyn Error>> {
+        let (http, tcp) = match serde_json::from_str(text)? {
+            Value::Array(http) => (http, Value::Null),
+            Value::Object(mut m) => (
+                match m.remove("http") {
+                    Some(Value::Array(http)) => http,
+                    _ => vec![],
+                },
+                m.remove("tcp").unwrap_or(Value::Null),
+            ),
+            _ => return Err("a recording must be a json object or array".into()),
+        };
+        Ok(Recording {
+            http: http.into_iter().map(exchange_of).collect::<Result<_, _>>()?,
+            tcp: if tcp.is_null() { vec![] } else { serde_json::from_value(tcp)? },
+        })
+    }
+}
+
+/// Headers left out of the replayed responses by default
+pub const VOLATILE_HEADERS: [&str; 3] = ["Content-Length", "Date", "Transfer-Encoding"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// Name of the generated suite
+    pub name: String,
+    /// Response headers left out, compared without case
+    pub drop_headers: Vec<String>,
+    /// Query parameters the stubs do not match on
+    pub ignore_queries: Vec<String>,
+    /// Stubs do not match on the request body
+    pub ignore_body: bool,
+    /// `(target, template)` pairs: a JSON pointer of the response body (`/id`) or a response header name
+    /// whose recorded value is replaced by a template such as `{{uuid}}` or `{{request.path[1]}}`
+    pub templates: Vec<(String, String)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            name: String::from("replay"),
+            drop_headers: VOLATILE_HEADERS.iter().map(|h| h.to_string()).collect(),
+            ignore_queries: vec![],
+            ignore_body: false,
+            templates: vec![],
+        }
+    }
+}
+
+fn split_target(path: &str) -> (&str, Option<&str>) {
+    match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    }
+}
+
+fn query_of(req: &RecordedRequest, options: &Options) -> Vec<(String, String)> {
+    let mut query: Vec<(String, String)> = match (&req.http_param, split_target(&req.http_path).1) {
+        (Some(params), _) => params
+            .iter()
+            .filter_map(|(k, v)| v.first().map(|v| (k.clone(), v.clone())))
+            .collect(),
+        (None, Some(q)) => q
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.split_once('=') {
+                Some((k, v)) => (k.to_string(), v.to_string()),
+                None => (p.to_string(), String::new()),
+            })
+            .collect(),
+        (None, None) => vec![],
+    };
+    query.retain(|(k, _)| !options.ignore_queries.contains(k));
+    query.sort();
+    query
+}
+
+fn json_body(body: &Value, raw: &str) -> Option<Value> {
+    let json = if body.is_null() {
+        serde_json::from_str(raw).unwrap_or(Value::Null)
+    } else {
+        body.clone()
+    };
+    if json.is_null() {
+        None
+    } else {
+        Some(json)
+    }
+}
+
+/// Matchers of the stub of `req`, also the key grouping identical requests
+fn matchers(req: &RecordedRequest, options: &Options) -> Vec<String> {
+    let path = split_target(&req.http_path).0;
+    let mut lines = vec![];
+    if !req.http_method.is_empty() {
+        lines.push(format!("method({})", literal(&req.http_method)));
+    }
+    lines.push(format!("path({})", literal(path)));
+    for (k, v) in query_of(req, options) {
+        lines.push(format!("query({}, {})", literal(&k), literal(&v)));
+    }
+    if !options.ignore_body {
+        if let Some(body) = json_body(&req.http_body, &req.http_body_raw) {
+            lines.push(format!(
+                "body_json(serde_json::from_str({}).unwrap())",
+                raw_literal(&body.to_string())
+            ));
+        }
+    }
+    lines
+}
+
+fn header_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| v.as_str().map(String::from).unwrap_or_else(|| v.to_string()))
+            .collect::<Vec<_>>()
+            .join(", "),
+        v => v.to_string(),
+    }
+}
+
+/// `StubResponse` expression of `res`
+fn response(res: &RecordedResponse, options: &Options) -> String {
+    let status: u16 = res.status_code.trim().parse().unwrap_or(200);
+    let body = match json_body(&res.http_body, &res.http_body_raw) {
+        Some(mut json) => {
+            for (target, template) in &options.templates {
+                if let Some(v) = json.pointer_mut(target) {
+                    *v = Value::String(template.clone());
+                }
+            }
+            json.to_string()
+        }
+        None => res.http_body_raw.clone(),
+    };
+    let mut out = format!("StubResponse::text({}, {})", status, raw_literal(&body));
+    for (name, value) in res.http_header.iter().flatten() {
+        if options.drop_headers.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        let value = options
+            .templates
+            .iter()
+            .find(|(target, _)| target.eq_ignore_ascii_case(name))
+            .map(|(_, template)| template.clone())
+            .unwrap_or_else(|| header_value(value));
+        write!(out, ".header({}, {})", literal(name), literal(&value)).unwrap();
+    }
+    out
+}
+
+/// Rust source of a crate root whose `mock_suite!` replays `recording`. Fails when [`Options::name`] is
+/// not a Rust identifier
+pub fn generate(recording: &Recording, options: &Options) -> Result<String, Box<dyn Error>> {
+    check_identifier(&options.name)?;
+    let mut groups: Vec<(Vec<String>, Vec<&Exchange>)> = vec![];
+    for exchange in &recording.http {
+        let key = matchers(&exchange.request, options);
+        match groups.iter_mut().find(|(k, _)| k == &key) {
+            Some((_, exchanges)) => exchanges.push(exchange),
+            None => groups.push((key, vec![exchange])),
+        }
+    }
+    let mut suite = String::new();
+    for (index, (key, exchanges)) in groups.iter().enumerate() {
+        let name = split_target(&exchanges[0].request.http_path).0;
+        let scenario = format!("{}_{}", options.name, index);
+        for (n, exchange) in exchanges.iter().enumerate() {
+            writeln!(suite, "        stub {} {{", literal(name)).unwrap();
+            for line in key {
+                writeln!(suite, "            {}", line).unwrap();
+            }
+            if exchanges.len() > 1 {
+                // Started, 2, 3, .. the last answer repeats
+                let state = if n == 0 { String::from("Started") } else { (n + 1).to_string() };
+                write!(suite, "            scenario({}) when_state({})", literal(&scenario), literal(&state)).unwrap();
+                if n + 1 < exchanges.len() {
+                    write!(suite, " set_state({})", literal(&(n + 2).to_string())).unwrap();
+                }
+                suite.push('\n');
+            }
+            writeln!(suite, "            respond({})", response(&exchange.response, options)).unwrap();
+            suite.push_str("        }\n");
+        }
+    }
+    for stream in &recording.tcp {
+        writeln!(suite, "        modify tcp_res {} (payload) {{", literal(&stream.name)).unwrap();
+        writeln!(suite, "            replay_tcp({}, &payload, &[", literal(&stream.name)).unwrap();
+        for item in &stream.items {
+            writeln!(suite, "                ({}, {}),", literal(&item.Payload), literal(&item.String)).unwrap();
+        }
+        suite.push_str("            ])\n        }\n");
+    }
+    Ok(format!(
+        "// Generated by wasm-mock-replay, edit freely.
+extern crate wapc_guest as guest;
+use guest::prelude::*;
+use wasm_mock_macro::mock_suite;
+use wasm_mock_util::*;
+
+#[no_mangle]
+pub extern \"C\" fn _start() {{
+    mock_suite!{{
+        name {};
+{}    }}
+}}
+fn main() {{}}
+",
+        options.name, suite
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = r#"{
+        "http": [
+            {"request": {"http_method": "GET", "http_path": "/users/7?page=2&t=1"},
+             "response": {"status_code": "200", "http_body": {"at": 1, "id": 7}, "http_header": {"Date": ["x"], "X-Id": ["a"]}}},
+            {"status_code": "201", "http_body_raw": "created", "http_req": {"http_method": "POST", "http_path": "/users", "http_body_raw": "{\"name\":\"ann\"}"}},
+            {"status_code": "202", "http_body_raw": "again", "http_req": {"http_method": "POST", "http_path": "/users", "http_body_raw": "{\"name\":\"ann\"}"}}
+        ],
+        "tcp": [{"name": "3335-:3334", "items": [{"Payload": "aGk=", "String": "hi"}]}]
+    }"#;
+
+    #[test]
+    fn reads_both_exchange_shapes() {
+        let recording = Recording::from_json(RECORDING).unwrap();
+        assert_eq!(recording.http.len(), 3);
+        assert_eq!(recording.http[1].request.http_method, "POST");
+        assert_eq!(recording.tcp[0].items[0].String, "hi");
+        assert_eq!(Recording::from_json("[]").unwrap(), Recording::default());
+    }
+
+    #[test]
+    fn generates_stubs_and_scenarios() {
+        let recording = Recording::from_json(RECORDING).unwrap();
+        let options = Options {
+            ignore_queries: vec![String::from("t")],
+            templates: vec![(String::from("/at"), String::from("{{now}}"))],
+            ..Options::default()
+        };
+        let source = generate(&recording, &options).unwrap();
+        assert!(source.contains(r#"stub "/users/7" {"#));
+        assert!(source.contains(r#"query("page", "2")"#));
+        assert!(!source.contains(r#"query("t""#));
+        assert!(source.contains(r##"StubResponse::text(200, r#"{"at":"{{now}}","id":7}"#).header("X-Id", "a")"##));
+        assert!(!source.contains("Date"));
+        assert!(source.contains(r#"scenario("replay_1") when_state("Started") set_state("2")"#));
+        assert!(source.contains(r#"scenario("replay_1") when_state("2")"#));
+        assert!(source.contains(r#"("aGk=", "hi"),"#));
+    }
+
+    #[test]
+    fn generated_source_parses() {
+        let recording = Recording::from_json(RECORDING).unwrap();
+        let options = Options {
+            name: String::from("my_suite2"),
+            templates: vec![(String::from("X-Id"), String::from("\"#{{uuid}}"))],
+            ..Options::default()
+        };
+        let file = syn::parse_file(&generate(&recording, &options).unwrap()).unwrap();
+        let start = file.items.iter().find_map(|item| match item {
+            syn::Item::Fn(f) if f.sig.ident == "_start" => Some(f),
+            _ => None,
+        });
+        let suite = match start.unwrap().block.stmts.as_slice() {
+            [syn::Stmt::Macro(m)] => m.mac.tokens.to_string(),
+            stmts => panic!("unexpected _start body: {} statements", stmts.len()),
+        };
+        assert!(suite.starts_with("name my_suite2 ;"), "{}", suite);
+    }
+
+    #[test]
+    fn suite_name_must_be_an_identifier() {
+        let options = Options {
+            name: String::from("x; } fn evil() {"),
+            ..Options::default()
+        };
+        assert!(generate(&Recording::default(), &options).is_err());
+    }
+}
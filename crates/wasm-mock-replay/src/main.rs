@@ -0,0 +1,48 @@
+use std::error::Error;
+use std::fs;
+use wasm_mock_replay::cli::{exit_on_error, Args};
+use wasm_mock_replay::{generate, Options, Recording};
+
+const USAGE: &str = "usage: wasm-mock-replay <recording.json> [options]
+
+options:
+  -o, --output <file>          write the generated source to <file> instead of stdout
+  --name <name>                name of the generated suite, a Rust identifier (default: replay)
+  --drop-header <name>         leave a response header out, repeatable
+                               (Content-Length, Date and Transfer-Encoding always are)
+  --ignore-query <name>        do not match a query parameter, repeatable
+  --ignore-body                do not match request bodies
+  --template <target=tmpl>     replace a response body value (JSON pointer, e.g. /id) or a response
+                               header by a template such as {{uuid}}, repeatable";
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = Args::new(USAGE);
+    let mut output = None;
+    let mut options = Options::default();
+    while let Some(option) = args.next_option() {
+        match option.as_str() {
+            "-o" | "--output" => output = Some(args.value(&option)?),
+            "--name" => options.name = args.value(&option)?,
+            "--drop-header" => options.drop_headers.push(args.value(&option)?),
+            "--ignore-query" => options.ignore_queries.push(args.value(&option)?),
+            "--ignore-body" => options.ignore_body = true,
+            "--template" => {
+                let v = args.value(&option)?;
+                let (target, template) = v.split_once('=').ok_or("--template expects <target>=<template>")?;
+                options.templates.push((target.to_string(), template.to_string()));
+            }
+            _ => return Err(args.unexpected(&option)),
+        }
+    }
+    let recording = Recording::from_json(&fs::read_to_string(args.input()?)?)?;
+    let source = generate(&recording, &options)?;
+    match output {
+        Some(path) => fs::write(path, source)?,
+        None => print!("{}", source),
+    }
+    Ok(())
+}
+
+fn main() {
+    exit_on_error(run());
+}
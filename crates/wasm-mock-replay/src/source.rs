@@ -0,0 +1,59 @@
+//! Rust source fragments written by the generators, shared with `wasm-mock-openapi`.
+
+/// `s` as a raw string literal
+pub fn raw_literal(s: &str) -> String {
+    let mut hashes = String::new();
+    while s.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+    format!("r{0}\"{1}\"{0}", hashes, s)
+}
+
+/// `s` as a string literal
+pub fn literal(s: &str) -> String {
+    format!("{:?}", s)
+}
+
+const KEYWORDS: [&str; 51] = [
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do",
+    "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static",
+    "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where",
+    "while",
+];
+
+/// Fails unless `name` can be written as a Rust identifier: ASCII letters, digits and `_`, not starting
+/// with a digit, and not a keyword
+pub fn check_identifier(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    };
+    if !valid || name == "_" || KEYWORDS.contains(&name) {
+        return Err(format!("{:?} is not a valid suite name, use letters, digits and _", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        for name in ["replay", "_x", "Users_v2"] {
+            assert_eq!(check_identifier(name), Ok(()));
+        }
+        for name in ["", "_", "2fa", "my-suite", "a b", "fn", "self", "r#x", "é"] {
+            assert!(check_identifier(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn raw_literals_outlive_their_content() {
+        assert_eq!(raw_literal("a"), "r\"a\"");
+        assert_eq!(raw_literal("say \"hi\""), "r#\"say \"hi\"\"#");
+        assert_eq!(raw_literal("\"#"), "r##\"\"#\"##");
+    }
+}
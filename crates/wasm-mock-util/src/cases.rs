@@ -0,0 +1,105 @@
+//! Rows of data-driven `test` items.
+//!
+//! `test_suite!` registers one automation index per row of `cases [..]`, `from_csv (..)` or `from_json (..)`.
+//! The row is kept in [`CASE_MAP`] under that index: `{{field}}` placeholders in the path, query, headers and
+//! payload are filled from it, and the assertion block reads it back with [`current_case`].
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::host::{self, HostClient, WapcHost};
+use crate::http1x::percent_encode;
+use crate::record::{report_step, SourceLocation, StepRecord};
+use crate::template::fill_with;
+
+lazy_static! {
+    /// Row of each data-driven automation index
+    pub static ref CASE_MAP: Arc<Mutex<HashMap<i32, Value>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Rows of a CSV table with a header line, every value kept as a string
+pub fn cases_from_csv(text: &str) -> Result<Vec<Value>, Box<dyn Error + Sync + Send>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(text.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let mut row = Map::new();
+        for (name, value) in headers.iter().zip(record.iter()) {
+            row.insert(name.to_string(), Value::String(value.to_string()));
+        }
+        rows.push(Value::Object(row));
+    }
+    Ok(rows)
+}
+
+/// Rows of a JSON array
+pub fn cases_from_json(text: &str) -> Result<Vec<Value>, Box<dyn Error + Sync + Send>> {
+    match serde_json::from_str(text)? {
+        Value::Array(rows) => Ok(rows),
+        _ => Err("test cases must be a json array".into()),
+    }
+}
+
+/// Rows of `cases`. When they could not be read a failed step is reported and the item gets no rows
+pub fn cases_or_report(cases: Result<Vec<Value>, Box<dyn Error + Sync + Send>>, source: &str) -> Vec<Value> {
+    cases.unwrap_or_else(|e| {
+        let _ = report_step(StepRecord::new(
+            false,
+            format!("invalid {} test cases: {}", source, e),
+            SourceLocation::new(file!(), line!(), column!()),
+        ));
+        vec![]
+    })
+}
+
+/// Field of `row` at the dotted `path`, e.g. `user.id` or `items.0`
+pub fn case_field<'a>(row: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(row, |v, key| match v {
+        Value::Object(m) => m.get(key),
+        Value::Array(a) => key.parse::<usize>().ok().and_then(|i| a.get(i)),
+        _ => None,
+    })
+}
+
+/// Replaces every `{{field}}` of `template` with the field of `row`. Strings are inserted without quotes,
+/// other values as JSON; unknown fields are left as they are
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wasm_mock_util::fill_case;
+/// let row = json!({"id": 7, "user": {"name": "ann"}});
+/// assert_eq!(fill_case("/users/{{id}}?name={{user.name}}", &row), "/users/7?name=ann");
+/// ```
+pub fn fill_case(template: &str, row: &Value) -> String {
+    fill_with(template, |key| case_field(row, key).cloned())
+}
+
+/// Like [`fill_case`] for a request target: the inserted values are percent-encoded
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wasm_mock_util::fill_case_path;
+/// let row = json!({"name": "a b/c", "page": 2});
+/// assert_eq!(fill_case_path("/users/{{name}}?page={{page}}", &row), "/users/a%20b%2Fc?page=2");
+/// ```
+pub fn fill_case_path(template: &str, row: &Value) -> String {
+    fill_with(template, |key| {
+        case_field(row, key).map(|v| match v {
+            Value::String(s) => Value::String(percent_encode(s)),
+            v => Value::String(percent_encode(&v.to_string())),
+        })
+    })
+}
+
+/// Row of the current automation index, `Value::Null` for items without cases
+pub fn current_case() -> Value {
+    let index = WapcHost.get_index(&host::uid()).unwrap_or(-1) as i32;
+    CASE_MAP.lock().unwrap().get(&index).cloned().unwrap_or(Value::Null)
+}
@@ -0,0 +1,557 @@
+//! Comparison of the A and B responses of the fiddler replayers.
+//!
+//! A [`Comparison`] diffs the status, headers and JSON body of a [`FiddlerAB`], or the payloads of a
+//! [`TcpFiddlerAB`]. Body paths are JSON pointers where `*` stands for any single segment, e.g.
+//! `/items/*/updatedAt`. Ignored paths are skipped, numbers may differ within a tolerance and arrays at
+//! unordered paths are matched item by item regardless of their order, an item without a match being
+//! reported at its index on its own side ([`DiffPath::OnlyA`], [`DiffPath::OnlyB`]). [`Comparison::report_http`] reports
+//! every difference as its own assertion and a step with the share of equal values, which also adds up per
+//! `UrlPath` in [`AB_SCORE_MAP`].
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+
+use crate::host::HostError;
+use crate::record::{report_assertion, report_step, AssertionRecord, Operand, SourceLocation, StepRecord};
+use crate::{FiddlerAB, HttpResponse, TcpFiddlerAB};
+
+lazy_static! {
+    /// Scores of every compared `UrlPath` (or TCP mock name)
+    pub static ref AB_SCORE_MAP: Arc<Mutex<BTreeMap<String, AbScore>>> = Arc::new(Mutex::new(BTreeMap::new()));
+}
+
+/// Part of a response that differs
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffPath {
+    Status,
+    Header(String),
+    /// JSON pointer into the body, empty for the whole body
+    Body(String),
+    /// JSON pointer into the A body of an unordered array item that B has no match for
+    OnlyA(String),
+    /// JSON pointer into the B body of an unordered array item that A has no match for
+    OnlyB(String),
+}
+
+impl fmt::Display for DiffPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffPath::Status => write!(f, "status"),
+            DiffPath::Header(name) => write!(f, "header {}", name),
+            DiffPath::Body(p) if p.is_empty() => write!(f, "body"),
+            DiffPath::Body(p) => write!(f, "body {}", p),
+            DiffPath::OnlyA(p) => write!(f, "body {} (A only)", p),
+            DiffPath::OnlyB(p) => write!(f, "body {} (B only)", p),
+        }
+    }
+}
+
+/// A value that differs between A and B, `None` on the side missing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub path: DiffPath,
+    pub a: Option<Value>,
+    pub b: Option<Value>,
+}
+
+/// Differences of one A/B pair and how many values were compared
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AbDiff {
+    pub compared: usize,
+    pub differences: Vec<Difference>,
+}
+
+impl AbDiff {
+    /// Share of equal values, 1 when nothing was compared
+    pub fn score(&self) -> f64 {
+        if self.compared == 0 {
+            return 1.0;
+        }
+        self.compared.saturating_sub(self.differences.len()) as f64 / self.compared as f64
+    }
+}
+
+/// Values compared and found equal over every A/B pair of a path
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AbScore {
+    pub pairs: usize,
+    pub compared: usize,
+    pub equal: usize,
+}
+
+impl AbScore {
+    pub fn score(&self) -> f64 {
+        if self.compared == 0 {
+            return 1.0;
+        }
+        self.equal as f64 / self.compared as f64
+    }
+}
+
+/// Score of every compared path
+pub fn ab_scores() -> BTreeMap<String, AbScore> {
+    AB_SCORE_MAP.lock().unwrap().clone()
+}
+
+fn pattern_matches(pattern: &str, pointer: &str) -> bool {
+    let p: Vec<&str> = pattern.split('/').collect();
+    let v: Vec<&str> = pointer.split('/').collect();
+    p.len() == v.len() && p.iter().zip(&v).all(|(p, v)| *p == "*" || p == v)
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn header_values(res: &HttpResponse) -> HashMap<String, (String, Value)> {
+    res.HttpHeader
+        .iter()
+        .flatten()
+        .map(|(k, v)| (k.to_ascii_lowercase(), (k.clone(), v.clone())))
+        .collect()
+}
+
+/// Rules of an A/B comparison
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wasm_mock_util::compare::{Comparison, DiffPath};
+/// let c = Comparison::new().ignore("/items/*/at").tolerance(0.01).unordered("/items");
+/// let a = json!({"total": 1.001, "items": [{"id": 1, "at": 5}, {"id": 2, "at": 6}]});
+/// let b = json!({"total": 1.0, "items": [{"id": 2, "at": 7}, {"id": 3, "at": 8}]});
+/// let diff = c.diff_json(&a, &b);
+/// assert_eq!(diff.differences.len(), 2);
+/// assert_eq!(diff.differences[0].path, DiffPath::OnlyA("/items/0".into()));
+/// assert_eq!(diff.differences[1].path, DiffPath::OnlyB("/items/1".into()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub ignore: Vec<String>,
+    pub ignore_headers: Vec<String>,
+    pub compare_status: bool,
+    pub compare_headers: bool,
+    /// Absolute tolerance of every number
+    pub tolerance: f64,
+    /// Absolute tolerance of the numbers at a path
+    pub tolerances: Vec<(String, f64)>,
+    pub unordered: Vec<String>,
+    pub unordered_all: bool,
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Comparison {
+            ignore: vec![],
+            ignore_headers: vec![String::from("Date")],
+            compare_status: true,
+            compare_headers: true,
+            tolerance: 0.0,
+            tolerances: vec![],
+            unordered: vec![],
+            unordered_all: false,
+        }
+    }
+}
+
+impl Comparison {
+    pub fn new() -> Self {
+        Comparison::default()
+    }
+    /// Skips the body values at `path`
+    pub fn ignore(mut self, path: &str) -> Self {
+        self.ignore.push(path.to_string());
+        self
+    }
+    /// Skips the header `name`, `Date` is skipped unless [`Comparison::headers_all`] is used
+    pub fn ignore_header(mut self, name: &str) -> Self {
+        self.ignore_headers.push(name.to_string());
+        self
+    }
+    /// Compares every header, `Date` included
+    pub fn headers_all(mut self) -> Self {
+        self.ignore_headers.clear();
+        self
+    }
+    pub fn without_status(mut self) -> Self {
+        self.compare_status = false;
+        self
+    }
+    pub fn without_headers(mut self) -> Self {
+        self.compare_headers = false;
+        self
+    }
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    pub fn tolerance_at(mut self, path: &str, tolerance: f64) -> Self {
+        self.tolerances.push((path.to_string(), tolerance));
+        self
+    }
+    /// Matches the items of the arrays at `path` regardless of their order
+    pub fn unordered(mut self, path: &str) -> Self {
+        self.unordered.push(path.to_string());
+        self
+    }
+    /// Matches the items of every array regardless of their order
+    pub fn unordered_arrays(mut self) -> Self {
+        self.unordered_all = true;
+        self
+    }
+
+    fn ignored(&self, pointer: &str) -> bool {
+        self.ignore.iter().any(|p| pattern_matches(p, pointer))
+    }
+    fn tolerance_of(&self, pointer: &str) -> f64 {
+        self.tolerances
+            .iter()
+            .find(|(p, _)| pattern_matches(p, pointer))
+            .map(|(_, t)| *t)
+            .unwrap_or(self.tolerance)
+    }
+    fn is_unordered(&self, pointer: &str) -> bool {
+        self.unordered_all || self.unordered.iter().any(|p| pattern_matches(p, pointer))
+    }
+
+    fn walk(&self, pointer: &str, a: Option<&Value>, b: Option<&Value>, out: &mut AbDiff) {
+        if self.ignored(pointer) {
+            return;
+        }
+        match (a, b) {
+            (Some(Value::Object(ma)), Some(Value::Object(mb))) => {
+                let mut keys: Vec<&String> = ma.keys().chain(mb.keys().filter(|k| !ma.contains_key(*k))).collect();
+                keys.sort();
+                for k in keys {
+                    self.walk(&format!("{}/{}", pointer, escape(k)), ma.get(k), mb.get(k), out);
+                }
+            }
+            (Some(Value::Array(va)), Some(Value::Array(vb))) if self.is_unordered(pointer) => {
+                let mut used = vec![false; vb.len()];
+                let mut unmatched = vec![];
+                for (i, item) in va.iter().enumerate() {
+                    let path = format!("{}/{}", pointer, i);
+                    let found = vb.iter().enumerate().position(|(j, other)| {
+                        let mut d = AbDiff::default();
+                        !used[j] && {
+                            self.walk(&path, Some(item), Some(other), &mut d);
+                            d.differences.is_empty()
+                        }
+                    });
+                    match found {
+                        Some(j) => {
+                            used[j] = true;
+                            self.walk(&path, Some(item), Some(&vb[j]), out);
+                        }
+                        None => unmatched.push(i),
+                    }
+                }
+                for i in unmatched {
+                    out.compared += 1;
+                    out.differences.push(Difference {
+                        path: DiffPath::OnlyA(format!("{}/{}", pointer, i)),
+                        a: Some(va[i].clone()),
+                        b: None,
+                    });
+                }
+                for (j, item) in vb.iter().enumerate().filter(|(j, _)| !used[*j]) {
+                    out.compared += 1;
+                    out.differences.push(Difference {
+                        path: DiffPath::OnlyB(format!("{}/{}", pointer, j)),
+                        a: None,
+                        b: Some(item.clone()),
+                    });
+                }
+            }
+            (Some(Value::Array(va)), Some(Value::Array(vb))) => {
+                for i in 0..va.len().max(vb.len()) {
+                    self.walk(&format!("{}/{}", pointer, i), va.get(i), vb.get(i), out);
+                }
+            }
+            (a, b) => {
+                out.compared += 1;
+                let equal = match (a, b) {
+                    (Some(Value::Number(x)), Some(Value::Number(y))) => match (x.as_f64(), y.as_f64()) {
+                        (Some(x), Some(y)) => (x - y).abs() <= self.tolerance_of(pointer),
+                        _ => x == y,
+                    },
+                    (a, b) => a == b,
+                };
+                if !equal {
+                    out.differences.push(Difference {
+                        path: DiffPath::Body(pointer.to_string()),
+                        a: a.cloned(),
+                        b: b.cloned(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Differences between two JSON bodies
+    pub fn diff_json(&self, a: &Value, b: &Value) -> AbDiff {
+        let mut out = AbDiff::default();
+        self.walk("", Some(a), Some(b), &mut out);
+        out
+    }
+
+    /// Differences between two responses: status, headers, then the JSON body or the raw body
+    pub fn diff_http(&self, a: &HttpResponse, b: &HttpResponse) -> AbDiff {
+        let mut out = AbDiff::default();
+        if self.compare_status {
+            out.compared += 1;
+            if a.StatusCode != b.StatusCode {
+                out.differences.push(Difference {
+                    path: DiffPath::Status,
+                    a: Some(Value::String(a.StatusCode.clone())),
+                    b: Some(Value::String(b.StatusCode.clone())),
+                });
+            }
+        }
+        if self.compare_headers {
+            let (ha, hb) = (header_values(a), header_values(b));
+            let mut names: Vec<&String> = ha.keys().chain(hb.keys().filter(|k| !ha.contains_key(*k))).collect();
+            names.sort();
+            for name in names {
+                if self.ignore_headers.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+                    continue;
+                }
+                let (va, vb) = (ha.get(name), hb.get(name));
+                out.compared += 1;
+                if va.map(|v| &v.1) != vb.map(|v| &v.1) {
+                    out.differences.push(Difference {
+                        path: DiffPath::Header(va.or(vb).map(|v| v.0.clone()).unwrap_or_default()),
+                        a: va.map(|v| v.1.clone()),
+                        b: vb.map(|v| v.1.clone()),
+                    });
+                }
+            }
+        }
+        let (ja, jb) = (a.body_json(), b.body_json());
+        if ja.is_null() && jb.is_null() {
+            out.compared += 1;
+            if a.HttpBodyRaw != b.HttpBodyRaw {
+                out.differences.push(Difference {
+                    path: DiffPath::Body(String::new()),
+                    a: Some(Value::String(a.HttpBodyRaw.clone())),
+                    b: Some(Value::String(b.HttpBodyRaw.clone())),
+                });
+            }
+        } else {
+            self.walk("", Some(&ja), Some(&jb), &mut out);
+        }
+        out
+    }
+
+    /// Differences between two TCP payloads, compared as JSON when both are, as bytes otherwise
+    pub fn diff_tcp(&self, ab: &TcpFiddlerAB) -> AbDiff {
+        let json = |bytes: &[u8]| serde_json::from_slice::<Value>(bytes).ok();
+        if let (Some(a), Some(b)) = (json(&ab.ResA), json(&ab.ResB)) {
+            return self.diff_json(&a, &b);
+        }
+        let mut out = AbDiff {
+            compared: 1,
+            differences: vec![],
+        };
+        if ab.ResA != ab.ResB {
+            let text = |bytes: &[u8]| Value::String(String::from_utf8_lossy(bytes).into_owned());
+            out.differences.push(Difference {
+                path: DiffPath::Body(String::new()),
+                a: Some(text(&ab.ResA)),
+                b: Some(text(&ab.ResB)),
+            });
+        }
+        out
+    }
+
+    /// Compares the responses of `ab`, then reports each difference as a failed assertion (a passed one when
+    /// there is none) and the score of the pair as a step
+    #[track_caller]
+    pub fn report_http(&self, ab: &FiddlerAB) -> Result<AbDiff, HostError> {
+        let diff = self.diff_http(&ab.ResA, &ab.ResB);
+        report(&ab.UrlPath, &diff, Location::caller())?;
+        Ok(diff)
+    }
+
+    /// [`Comparison::report_http`] of the payloads of the TCP mock `name`
+    #[track_caller]
+    pub fn report_tcp(&self, name: &str, ab: &TcpFiddlerAB) -> Result<AbDiff, HostError> {
+        let diff = self.diff_tcp(ab);
+        report(name, &diff, Location::caller())?;
+        Ok(diff)
+    }
+}
+
+fn operand(v: &Option<Value>) -> Operand {
+    Operand {
+        debug: v.as_ref().map(|v| v.to_string()).unwrap_or_else(|| String::from("<missing>")),
+        json: v.clone(),
+    }
+}
+
+fn report(name: &str, diff: &AbDiff, caller: &Location) -> Result<(), HostError> {
+    let location = SourceLocation::new(caller.file(), caller.line(), caller.column());
+    for d in &diff.differences {
+        let description = format!("{} A/B {}", name, d.path);
+        let message = format!(
+            "`{}` assertion failed: A: `{}`, B: `{}`",
+            description,
+            operand(&d.a).debug,
+            operand(&d.b).debug
+        );
+        report_assertion(AssertionRecord::compare(
+            false,
+            description,
+            message,
+            operand(&d.a),
+            operand(&d.b),
+            location.clone(),
+        ))?;
+    }
+    if diff.differences.is_empty() {
+        report_assertion(AssertionRecord::new(true, format!("{} A/B responses match", name), location.clone()))?;
+    }
+    let equal = diff.compared.saturating_sub(diff.differences.len());
+    {
+        let mut scores = AB_SCORE_MAP.lock().unwrap();
+        let score = scores.entry(name.to_string()).or_default();
+        score.pairs += 1;
+        score.compared += diff.compared;
+        score.equal += equal;
+    }
+    report_step(StepRecord::new(
+        diff.differences.is_empty(),
+        format!("{} A/B score {:.3} ({}/{} values equal)", name, diff.score(), equal, diff.compared),
+        location,
+    ))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{ReportEvent, REPORT};
+    use serde_json::json;
+
+    fn response(status: &str, headers: Value, raw: &str) -> HttpResponse {
+        HttpResponse {
+            StatusCode: status.to_string(),
+            HttpHeader: serde_json::from_value(headers).unwrap(),
+            HttpBodyRaw: raw.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn paths(diff: &AbDiff) -> Vec<String> {
+        diff.differences.iter().map(|d| d.path.to_string()).collect()
+    }
+
+    #[test]
+    fn status_headers_and_json_body() {
+        let a = response("200", json!({"Date": ["x"], "X-Id": ["1"], "ETag": ["e"]}), r#"{"id":1,"name":"ann"}"#);
+        let b = response("201", json!({"date": ["y"], "x-id": ["2"], "Vary": ["*"]}), r#"{"id":1,"name":"bob"}"#);
+        let diff = Comparison::new().diff_http(&a, &b);
+        assert_eq!(paths(&diff), ["status", "header ETag", "header Vary", "header X-Id", "body /name"]);
+        assert_eq!(diff.differences[3].a, Some(json!(["1"])));
+        assert_eq!(diff.differences[3].b, Some(json!(["2"])));
+        // status, 3 headers (Date aside) and 2 body values
+        assert_eq!(diff.compared, 6);
+
+        let diff = Comparison::new().without_status().headers_all().ignore_header("etag").diff_http(&a, &b);
+        assert_eq!(paths(&diff), ["header Date", "header Vary", "header X-Id", "body /name"]);
+        let diff = Comparison::new().without_status().without_headers().ignore("/name").diff_http(&a, &b);
+        assert_eq!(diff, AbDiff { compared: 1, differences: vec![] });
+    }
+
+    #[test]
+    fn raw_bodies_when_neither_is_json() {
+        let c = Comparison::new().without_status().without_headers();
+        let diff = c.diff_http(&response("200", json!(null), "hello"), &response("200", json!(null), "hullo"));
+        assert_eq!(diff.compared, 1);
+        assert_eq!(paths(&diff), ["body"]);
+        assert_eq!(diff.differences[0].b, Some(json!("hullo")));
+        assert!(c.diff_http(&response("200", json!(null), "same"), &response("200", json!(null), "same")).differences.is_empty());
+        // one JSON side is walked as JSON against null
+        let diff = c.diff_http(&response("200", json!(null), "[1]"), &response("200", json!(null), "x"));
+        assert_eq!(paths(&diff), ["body"]);
+        assert_eq!(diff.differences[0].b, Some(Value::Null));
+    }
+
+    #[test]
+    fn tcp_payloads() {
+        let c = Comparison::new().tolerance(0.5);
+        let json = TcpFiddlerAB { ResA: br#"{"n":1.2,"s":"a"}"#.to_vec(), ResB: br#"{"n":1.5,"s":"b"}"#.to_vec() };
+        assert_eq!(paths(&c.diff_tcp(&json)), ["body /s"]);
+        let bytes = TcpFiddlerAB { ResA: vec![1, 2, 0xff], ResB: vec![1, 2, 3] };
+        let diff = c.diff_tcp(&bytes);
+        assert_eq!(diff.compared, 1);
+        assert_eq!(diff.differences[0].a, Some(json!("\u{1}\u{2}\u{fffd}")));
+        assert!(c.diff_tcp(&TcpFiddlerAB { ResA: vec![9], ResB: vec![9] }).differences.is_empty());
+    }
+
+    #[test]
+    fn tolerance_at_a_path_wins() {
+        let c = Comparison::new().tolerance(0.1).tolerance_at("/items/*/price", 1.0);
+        let a = json!({"total": 10.0, "items": [{"price": 5.0, "qty": 1.0}]});
+        let b = json!({"total": 10.05, "items": [{"price": 5.9, "qty": 1.2}]});
+        assert_eq!(paths(&c.diff_json(&a, &b)), ["body /items/0/qty"]);
+        let b = json!({"total": 10.5, "items": [{"price": 6.5, "qty": 1.0}]});
+        assert_eq!(paths(&c.diff_json(&a, &b)), ["body /items/0/price", "body /total"]);
+    }
+
+    #[test]
+    fn unmatched_items_are_labeled_with_their_side() {
+        let c = Comparison::new().unordered_arrays();
+        let a = json!({"tags": ["x", "y", "z"]});
+        let b = json!({"tags": ["w", "z", "x", "v"]});
+        let diff = c.diff_json(&a, &b);
+        assert_eq!(paths(&diff), ["body /tags/1 (A only)", "body /tags/0 (B only)", "body /tags/3 (B only)"]);
+        assert_eq!(diff.differences[0].a, Some(json!("y")));
+        assert_eq!(diff.differences[1].b, Some(json!("w")));
+        // x and z are matched whatever their order
+        assert_eq!(diff.compared, 5);
+        assert!(c.diff_json(&json!([[1, 2], [3]]), &json!([[3], [2, 1]])).differences.is_empty());
+    }
+
+    #[test]
+    fn report_adds_up_scores() {
+        let name = "/compare-report";
+        let c = Comparison::new().without_headers();
+        let ab = |b: &str| FiddlerAB {
+            ResA: response("200", json!(null), r#"{"a":1,"b":2,"c":3}"#),
+            ResB: response("200", json!(null), b),
+            UrlPath: name.to_string(),
+        };
+        c.report_http(&ab(r#"{"a":1,"b":2,"c":3}"#)).unwrap();
+        let diff = c.report_http(&ab(r#"{"a":1,"b":0,"c":0}"#)).unwrap();
+        assert_eq!(diff.score(), 0.5);
+        assert_eq!(ab_scores()[name], AbScore { pairs: 2, compared: 8, equal: 6 });
+        assert_eq!(ab_scores()[name].score(), 0.75);
+
+        let events: Vec<(bool, String)> = REPORT
+            .lock()
+            .unwrap()
+            .events()
+            .iter()
+            .filter_map(|e| match e {
+                ReportEvent::Assertion(a) if a.message.contains(name) => Some((a.passed, a.message.clone())),
+                ReportEvent::Step(s) if s.description.contains(name) => Some((s.passed, s.description.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (true, format!("{} A/B responses match", name)),
+                (true, format!("{} A/B score 1.000 (4/4 values equal)", name)),
+                (false, format!("`{} A/B body /b` assertion failed: A: `2`, B: `0`", name)),
+                (false, format!("`{} A/B body /c` assertion failed: A: `3`, B: `0`", name)),
+                (false, format!("{} A/B score 0.500 (2/4 values equal)", name)),
+            ]
+        );
+    }
+}
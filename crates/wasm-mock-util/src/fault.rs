@@ -0,0 +1,240 @@
+//! Fault injection for the HTTP fiddler mocks, declared with `fault` in `mock_suite!`.
+//!
+//! A [`Fault`] attached to a mock runs after its `modify http_res` rule or stub: it sleeps with the host
+//! `sleep`, then may replace the status code, truncate the body or fail the guest call so the mock server
+//! drops the response. Every random draw comes from the fault's own [`SplitMix64`] when it is seeded with
+//! `seed(..)`, from the guest generator of [`rng`](crate::rng) otherwise, so seeded runs are reproducible.
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::host::{self, HostClient, WapcHost};
+use crate::rng::{with_rng, SplitMix64};
+use crate::HttpResponse;
+
+lazy_static! {
+    /// Fault of each mock
+    pub static ref FAULT_MAP: Arc<Mutex<HashMap<String, Fault>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Faults injected into the responses of a mock
+///
+/// # Examples
+///
+/// ```
+/// # extern crate wasm_mock_host_abi;
+/// use wasm_mock_util::{Fault, HttpResponse};
+/// let mut fault = Fault::new().seed(7).status(1.0, 503).truncate(1.0, 2);
+/// let mut res = HttpResponse { StatusCode: "200".into(), HttpBodyRaw: "hello".into(), ..Default::default() };
+/// fault.inject(&mut res).unwrap();
+/// assert_eq!(res.StatusCode, "503");
+/// assert_eq!(res.HttpBodyRaw, "he");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Fault {
+    /// Milliseconds slept before answering, drawn in `[min, max]`
+    pub latency: Option<(u64, u64)>,
+    /// Status codes with the probability of each, tried in order with a single draw
+    pub statuses: Vec<(f64, u16)>,
+    /// Probability and length in bytes of a truncated body
+    pub truncate: Option<(f64, usize)>,
+    /// Probability and message of a failed guest call
+    pub error: Option<(f64, String)>,
+    rng: Option<SplitMix64>,
+}
+
+impl Fault {
+    pub fn new() -> Self {
+        Fault::default()
+    }
+    /// Sleeps `millis` before every response
+    pub fn latency(mut self, millis: u64) -> Self {
+        self.latency = Some((millis, millis));
+        self
+    }
+    /// Sleeps a random duration between `min` and `max` milliseconds before every response
+    pub fn latency_between(mut self, min: u64, max: u64) -> Self {
+        self.latency = Some((min.min(max), min.max(max)));
+        self
+    }
+    /// Answers `status` for a share `probability` (0 to 1) of the responses
+    pub fn status(mut self, probability: f64, status: u16) -> Self {
+        self.statuses.push((probability, status));
+        self
+    }
+    /// Cuts the body to its first `len` bytes for a share `probability` of the responses
+    pub fn truncate(mut self, probability: f64, len: usize) -> Self {
+        self.truncate = Some((probability, len));
+        self
+    }
+    /// Fails the guest call with `message` for a share `probability` of the responses
+    pub fn error(mut self, probability: f64, message: &str) -> Self {
+        self.error = Some((probability, message.to_string()));
+        self
+    }
+    /// Draws from a generator of its own seeded with `seed`
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Some(SplitMix64::new(seed));
+        self
+    }
+
+    fn draw<T>(&mut self, f: impl FnOnce(&mut SplitMix64) -> T) -> T {
+        match self.rng.as_mut() {
+            Some(rng) => f(rng),
+            None => with_rng(f),
+        }
+    }
+    fn happens(&mut self, probability: f64) -> bool {
+        probability >= 1.0 || (probability > 0.0 && self.draw(|r| r.next_f64()) < probability)
+    }
+
+    /// Injects the faults into `res`, `Err` when the guest call has to fail
+    pub fn inject(&mut self, res: &mut HttpResponse) -> Result<(), Box<dyn Error + Sync + Send>> {
+        if let Some((min, max)) = self.latency {
+            let millis = self.draw(|r| r.range(min, max));
+            if millis > 0 {
+                WapcHost.sleep(&host::uid(), millis)?;
+            }
+        }
+        if let Some((probability, message)) = self.error.clone() {
+            if self.happens(probability) {
+                return Err(message.into());
+            }
+        }
+        if !self.statuses.is_empty() {
+            let roll = self.draw(|r| r.next_f64());
+            let mut bound = 0.0;
+            for (probability, status) in &self.statuses {
+                bound += probability;
+                if roll < bound {
+                    res.StatusCode = status.to_string();
+                    break;
+                }
+            }
+        }
+        if let Some((probability, len)) = self.truncate {
+            if self.happens(probability) {
+                let raw = if res.HttpBodyRaw.is_empty() && !res.HttpBody.is_null() {
+                    res.HttpBody.to_string()
+                } else {
+                    std::mem::take(&mut res.HttpBodyRaw)
+                };
+                let mut end = len.min(raw.len());
+                while !raw.is_char_boundary(end) {
+                    end -= 1;
+                }
+                res.HttpBodyRaw = raw[..end].to_string();
+                res.HttpBody = serde_json::Value::Null;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Attaches `fault` to the mock `name`
+pub fn add_fault(name: &str, fault: Fault) {
+    FAULT_MAP.lock().unwrap().insert(name.to_string(), fault);
+}
+
+/// Injects the fault of the mock `name` into `res`, if it has one
+pub fn inject_fault(name: &str, res: &mut HttpResponse) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let mut fault = match FAULT_MAP.lock().unwrap().get(name) {
+        Some(fault) => fault.clone(),
+        None => return Ok(()),
+    };
+    let result = fault.inject(res);
+    // keep the generator where the draws left it
+    if let Some(stored) = FAULT_MAP.lock().unwrap().get_mut(name) {
+        stored.rng = fault.rng;
+    }
+    result
+}
+
+/// `{name}_http_modify_res` of a mock with a fault and no other response rule
+pub fn fault_http_res(name: &str, msg: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Sync + Send>> {
+    let mut res: HttpResponse = serde_json::from_slice(msg)?;
+    inject_fault(name, &mut res)?;
+    Ok(serde_json::to_vec(&res)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+    use wasm_mock_host_abi::MockHost;
+
+    fn ok() -> HttpResponse {
+        HttpResponse {
+            StatusCode: String::from("200"),
+            HttpBodyRaw: String::from("hello"),
+            ..Default::default()
+        }
+    }
+
+    fn statuses(name: &str, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| {
+                let mut res = ok();
+                inject_fault(name, &mut res).unwrap();
+                res.StatusCode
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seeded_statuses_repeat_across_calls() {
+        let fault = Fault::new().seed(42).status(0.3, 500).status(0.3, 503);
+        add_fault("fault-seeded", fault.clone());
+        let first = statuses("fault-seeded", 20);
+
+        // a single draw per response, against the cumulative probabilities
+        let mut rng = SplitMix64::new(42);
+        let expected: Vec<String> = (0..20)
+            .map(|_| match rng.next_f64() {
+                roll if roll < 0.3 => "500",
+                roll if roll < 0.6 => "503",
+                _ => "200",
+            })
+            .map(String::from)
+            .collect();
+        assert_eq!(first, expected);
+        for status in ["200", "500", "503"] {
+            assert!(first.iter().any(|s| s == status), "{} missing from {:?}", status, first);
+        }
+
+        add_fault("fault-seeded", fault);
+        assert_eq!(statuses("fault-seeded", 20), first);
+    }
+
+    #[test]
+    fn latency_sleeps_with_the_host() {
+        let host = MockHost::new();
+        add_fault("fault-latency", Fault::new().latency(250));
+        inject_fault("fault-latency", &mut ok()).unwrap();
+        add_fault("fault-latency-between", Fault::new().seed(9).latency_between(100, 400));
+        inject_fault("fault-latency-between", &mut ok()).unwrap();
+
+        let slept: Vec<u64> = host.calls_to("sleep").iter().map(|c| LittleEndian::read_u64(&c.payload)).collect();
+        assert_eq!(slept, [250, SplitMix64::new(9).range(100, 400)]);
+        assert_eq!(host.now(), 250 + slept[1]);
+    }
+
+    #[test]
+    fn error_fails_the_call_before_the_status() {
+        add_fault("fault-error", Fault::new().error(1.0, "connection reset").status(1.0, 503));
+        let mut res = ok();
+        let e = inject_fault("fault-error", &mut res).unwrap_err();
+        assert_eq!(e.to_string(), "connection reset");
+        assert_eq!(res.StatusCode, "200");
+
+        add_fault("fault-no-error", Fault::new().error(0.0, "connection reset").status(1.0, 503));
+        let mut res = ok();
+        inject_fault("fault-no-error", &mut res).unwrap();
+        assert_eq!(res.StatusCode, "503");
+
+        let mut res = ok();
+        inject_fault("fault-missing", &mut res).unwrap();
+        assert_eq!((res.StatusCode.as_str(), res.HttpBodyRaw.as_str()), ("200", "hello"));
+    }
+}
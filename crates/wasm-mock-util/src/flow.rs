@@ -0,0 +1,223 @@
+//! Values captured from one response and fed into the requests of later automation indexes.
+//!
+//! `capture { token = body "/token" }` in `test_suite!` (or [`capture_var`] inside a test body) stores a value
+//! of the response in [`VARIABLES`]. The requests registered by `test_suite!` are kept as templates: the
+//! guest calls `command`, `request` and `request_marshalling` fill `{{token}}` in the url, headers and body
+//! from the variables and fixtures at the time the host asks for them. Values are percent-encoded in the url
+//! and request target, and a value holding a line break is refused in a header.
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::cases::{case_field, fill_case, fill_case_path};
+use crate::lifecycle::FIXTURE_MAP;
+use crate::record::{report_step, SourceLocation, StepRecord};
+use crate::template::fill_with;
+use crate::{HttpRequest, HttpResponse};
+
+lazy_static! {
+    /// Variables captured by the suite so far
+    pub static ref VARIABLES: Arc<Mutex<HashMap<String, Value>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Part of an [`HttpResponse`] a variable is captured from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Extract {
+    /// JSON pointer into the body, e.g. `/data/token`
+    Body(String),
+    /// First value of a header, case insensitive
+    Header(String),
+    Cookie(String),
+    Status,
+}
+
+impl Extract {
+    /// `body`, `header`, `cookie` or `status` with its argument, as written in `capture { .. }`
+    pub fn parse(kind: &str, arg: &str) -> Option<Self> {
+        match kind {
+            "body" => Some(Extract::Body(arg.to_string())),
+            "header" => Some(Extract::Header(arg.to_string())),
+            "cookie" => Some(Extract::Cookie(arg.to_string())),
+            "status" => Some(Extract::Status),
+            _ => None,
+        }
+    }
+    pub fn extract(&self, res: &HttpResponse) -> Option<Value> {
+        match self {
+            Extract::Body(pointer) => res.body_json().pointer(pointer).cloned(),
+            Extract::Header(name) => {
+                let headers = res.HttpHeader.as_ref()?;
+                let (_, v) = headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name))?;
+                match v {
+                    Value::Array(values) => values.first().cloned(),
+                    v => Some(v.clone()),
+                }
+            }
+            Extract::Cookie(name) => res.HttpCookie.as_ref()?.get(name).map(|c| Value::String(c.clone())),
+            Extract::Status => Some(Value::String(res.StatusCode.clone())),
+        }
+    }
+}
+
+pub fn set_variable(name: &str, value: Value) {
+    VARIABLES.lock().unwrap().insert(name.to_string(), value);
+}
+
+pub fn variable(name: &str) -> Option<Value> {
+    VARIABLES.lock().unwrap().get(name).cloned()
+}
+
+/// Captures `kind`/`arg` of `res` into the variable `name` and reports it as a step
+///
+/// # Examples
+///
+/// ```ignore
+/// test http_post "/login" ([])(b"{\"user\":\"ann\"}".to_vec())(res) {
+///     capture_var("token", &res, "body", "/token")?;
+/// }
+/// ```
+pub fn capture_var(name: &str, res: &HttpResponse, kind: &str, arg: &str) -> Result<Value, Box<dyn Error + Sync + Send>> {
+    let extract = Extract::parse(kind, arg).ok_or_else(|| format!("unknown capture {} {}", kind, arg))?;
+    let location = SourceLocation::new(file!(), line!(), column!());
+    match extract.extract(res) {
+        Some(value) => {
+            report_step(StepRecord::new(true, format!("capture {} = {}", name, value), location))?;
+            set_variable(name, value.clone());
+            Ok(value)
+        }
+        None => {
+            report_step(StepRecord::new(false, format!("capture {}: {} {} not found", name, kind, arg), location))?;
+            Err(format!("capture {}: {} {} not found", name, kind, arg).into())
+        }
+    }
+}
+
+/// Fixtures and captured variables, variables taking precedence
+fn scope() -> Value {
+    let mut scope = Map::new();
+    for (k, v) in FIXTURE_MAP.lock().unwrap().iter() {
+        scope.insert(k.clone(), v.clone());
+    }
+    for (k, v) in VARIABLES.lock().unwrap().iter() {
+        scope.insert(k.clone(), v.clone());
+    }
+    Value::Object(scope)
+}
+
+/// Fills `{{name}}` in `template` from the fixtures and captured variables, variables taking precedence
+pub fn interpolate(template: &str) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+    fill_case(template, &scope())
+}
+
+/// [`interpolate`] for a url or request target: the values are percent-encoded, so a captured id holding a
+/// space, `/`, `?`, `&`, `#` or a line break stays a single path segment or query value
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wasm_mock_util::{interpolate_target, set_variable};
+/// set_variable("doc_target_id", json!("a b?c"));
+/// assert_eq!(interpolate_target("/items/{{doc_target_id}}"), "/items/a%20b%3Fc");
+/// ```
+pub fn interpolate_target(template: &str) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+    fill_case_path(template, &scope())
+}
+
+/// [`interpolate`] for an HTTP/1.x request line and headers: the request target as [`interpolate_target`],
+/// the header values as they are. Fails when a value going into a header holds a CR or LF, which would end
+/// the header and start another one
+pub fn interpolate_head(head: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+    if !head.contains("{{") {
+        return Ok(head.to_string());
+    }
+    let scope = scope();
+    let (line, headers) = head.split_once("\r\n").unwrap_or((head, ""));
+    // `{method} {target} {version}`
+    let line = match line.split_once(' ') {
+        Some((method, rest)) => match rest.rsplit_once(' ') {
+            Some((target, version)) => format!("{} {} {}", method, fill_case_path(target, &scope), version),
+            None => format!("{} {}", method, fill_case_path(rest, &scope)),
+        },
+        None => line.to_string(),
+    };
+    let broken = RefCell::new(None);
+    let headers = fill_with(headers, |key| {
+        let value = case_field(&scope, key)?;
+        if value.as_str().is_some_and(|s| s.contains(['\r', '\n'])) {
+            broken.borrow_mut().get_or_insert_with(|| key.to_string());
+        }
+        Some(value.clone())
+    });
+    if let Some(key) = broken.into_inner() {
+        return Err(format!("{} holds a line break and cannot go into a header", key).into());
+    }
+    if headers.is_empty() {
+        return Ok(line);
+    }
+    Ok(format!("{}\r\n{}", line, headers))
+}
+
+/// `r` with its request head and utf-8 body interpolated, the head as [`interpolate_head`]
+pub fn interpolate_request(r: &HttpRequest) -> Result<HttpRequest, Box<dyn Error + Sync + Send>> {
+    let mut r = r.clone();
+    r.Http1x = interpolate_head(&r.Http1x)?;
+    if let Ok(body) = std::str::from_utf8(&r.HttpBody) {
+        if body.contains("{{") {
+            r.HttpBody = interpolate(body).into_bytes();
+        }
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn captured_values_are_encoded_in_the_request_target() {
+        set_variable("target_id", json!("7 & 8?x#y\r\nX-Admin: 1"));
+        assert_eq!(
+            interpolate_target("http://localhost/items/{{target_id}}?q={{target_id}}"),
+            "http://localhost/items/7%20%26%208%3Fx%23y%0D%0AX-Admin%3A%201?q=7%20%26%208%3Fx%23y%0D%0AX-Admin%3A%201"
+        );
+        // the body keeps the value as it is
+        assert_eq!(interpolate("{\"id\":\"{{target_id}}\"}"), "{\"id\":\"7 & 8?x#y\r\nX-Admin: 1\"}");
+    }
+
+    #[test]
+    fn head_encodes_the_target_and_fills_the_headers() {
+        set_variable("head_id", json!("a/b"));
+        set_variable("head_token", json!("t 1"));
+        let head = "GET /items/{{head_id}} HTTP/1.1\r\nAuthorization: Bearer {{head_token}}\r\n\r\n";
+        assert_eq!(
+            interpolate_head(head).unwrap(),
+            "GET /items/a%2Fb HTTP/1.1\r\nAuthorization: Bearer t 1\r\n\r\n"
+        );
+        assert_eq!(interpolate_head("GET /{{head_id}} HTTP/1.1").unwrap(), "GET /a%2Fb HTTP/1.1");
+    }
+
+    #[test]
+    fn line_breaks_are_refused_in_headers() {
+        set_variable("crlf_token", json!("t\r\nX-Admin: 1"));
+        let head = "GET / HTTP/1.1\r\nAuthorization: Bearer {{crlf_token}}\r\n\r\n";
+        let e = interpolate_head(head).unwrap_err();
+        assert_eq!(e.to_string(), "crlf_token holds a line break and cannot go into a header");
+        let req = HttpRequest {
+            Http1x: head.to_string(),
+            HttpBody: vec![],
+            ProxyUrl: String::new(),
+        };
+        assert!(interpolate_request(&req).is_err());
+    }
+}
@@ -0,0 +1,890 @@
+//! HAR 1.2 import and export of the HTTP traffic types.
+//!
+//! [`HarRequest`] and [`HarResponse`] convert from and into [`RequestReceivedInMock`] and [`HttpResponse`]
+//! with their headers, cookies, query parameters and bodies, so traffic moves between browser DevTools,
+//! Fiddler/Charles and the mocks. Bodies that are UTF-8 text go to `HttpBodyRaw`, whether the HAR has them
+//! base64 encoded or not; other bodies go to `HttpBodyBase64` and are exported with `"encoding": "base64"` again.
+//! [`add_har_stubs`] loads the entries of a HAR file as [`Stub`]s, which is what `har` does in `mock_suite!`.
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::http1x::{percent_decode, query_string, with_query};
+use crate::stub::{add_stub, Stub, StubResponse};
+use crate::{HttpResponse, RequestReceivedInMock};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub entries: Vec<HarEntry>,
+}
+
+impl Default for HarLog {
+    fn default() -> Self {
+        HarLog {
+            version: String::from("1.2"),
+            creator: HarCreator {
+                name: String::from("wasm-mock-util"),
+                version: String::from(env!("CARGO_PKG_VERSION")),
+            },
+            entries: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    /// Milliseconds
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub cache: HarCache,
+    pub timings: HarTimings,
+}
+
+impl Default for HarEntry {
+    fn default() -> Self {
+        HarEntry {
+            started_date_time: String::from("1970-01-01T00:00:00.000Z"),
+            time: 0.0,
+            request: HarRequest::default(),
+            response: HarResponse::default(),
+            cache: HarCache::default(),
+            timings: HarTimings::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct HarCache {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct HarTimings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarCookie {
+    pub name: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarPostData {
+    pub mime_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub cookies: Vec<HarCookie>,
+    pub headers: Vec<HarNameValue>,
+    pub query_string: Vec<HarNameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+impl Default for HarRequest {
+    fn default() -> Self {
+        HarRequest {
+            method: String::from("GET"),
+            url: String::new(),
+            http_version: String::from("HTTP/1.1"),
+            cookies: vec![],
+            headers: vec![],
+            query_string: vec![],
+            post_data: None,
+            headers_size: -1,
+            body_size: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub cookies: Vec<HarCookie>,
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+impl Default for HarResponse {
+    fn default() -> Self {
+        HarResponse {
+            status: 200,
+            status_text: String::from("OK"),
+            http_version: String::from("HTTP/1.1"),
+            cookies: vec![],
+            headers: vec![],
+            content: HarContent::default(),
+            redirect_url: String::new(),
+            headers_size: -1,
+            body_size: 0,
+        }
+    }
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+fn header_pairs(headers: &Option<HashMap<String, Value>>) -> Vec<HarNameValue> {
+    let mut pairs = vec![];
+    for (name, value) in headers.iter().flatten() {
+        let values = match value {
+            Value::Array(values) => values.clone(),
+            v => vec![v.clone()],
+        };
+        for v in values {
+            pairs.push(HarNameValue {
+                name: name.clone(),
+                value: v.as_str().map(String::from).unwrap_or_else(|| v.to_string()),
+            });
+        }
+    }
+    pairs.sort_by(|a, b| a.name.cmp(&b.name));
+    pairs
+}
+
+/// Headers of a HAR entry without the HTTP/2 pseudo headers (`:authority`, `:path`...) browsers record
+fn header_map(pairs: &[HarNameValue]) -> Option<HashMap<String, Value>> {
+    let mut map: HashMap<String, Value> = HashMap::new();
+    for pair in pairs.iter().filter(|p| !p.name.starts_with(':')) {
+        if let Value::Array(values) = map.entry(pair.name.clone()).or_insert_with(|| Value::Array(vec![])) {
+            values.push(Value::String(pair.value.clone()));
+        }
+    }
+    if map.is_empty() {
+        None
+    } else {
+        Some(map)
+    }
+}
+
+/// Headers describing how the recorded body was sent. HAR files keep the decoded body, so a stub
+/// replaying them would label a plain body as compressed or give it the wrong length.
+const TRANSFER_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+fn header_of<'a>(pairs: &'a [HarNameValue], name: &str) -> Option<&'a str> {
+    pairs.iter().find(|p| p.name.eq_ignore_ascii_case(name)).map(|p| p.value.as_str())
+}
+
+fn cookie_pairs(cookies: &Option<HashMap<String, String>>) -> Vec<HarCookie> {
+    let mut pairs: Vec<HarCookie> = cookies
+        .iter()
+        .flatten()
+        .map(|(name, value)| HarCookie {
+            name: name.clone(),
+            value: value.clone(),
+            ..Default::default()
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.name.cmp(&b.name));
+    pairs
+}
+
+fn cookie_map(cookies: &[HarCookie]) -> Option<HashMap<String, String>> {
+    if cookies.is_empty() {
+        return None;
+    }
+    Some(cookies.iter().map(|c| (c.name.clone(), c.value.clone())).collect())
+}
+
+/// `text`, `encoding` and size in bytes of the HAR body of `HttpBody`, `HttpBodyRaw` and `HttpBodyBase64`:
+/// text as it is, other bodies base64 encoded
+fn encode_body(body: &Value, raw: &str, base64: &str) -> (String, Option<String>, i64) {
+    if !base64.is_empty() {
+        let size = general_purpose::STANDARD.decode(base64).map(|b| b.len()).unwrap_or(0);
+        (base64.to_string(), Some(String::from("base64")), size as i64)
+    } else if raw.is_empty() && !body.is_null() {
+        let text = body.to_string();
+        let size = text.len() as i64;
+        (text, None, size)
+    } else {
+        (raw.to_string(), None, raw.len() as i64)
+    }
+}
+
+/// `HttpBodyRaw`, `HttpBody` and `HttpBodyBase64` of a HAR body. A base64 body that decodes to UTF-8 text
+/// is text, one that does not decode is kept as the text of the HAR.
+fn decode_body(text: &str, encoding: Option<&str>) -> (String, Value, String) {
+    let bytes = match encoding {
+        Some(e) if e.eq_ignore_ascii_case("base64") => general_purpose::STANDARD.decode(text).ok(),
+        _ => None,
+    };
+    let (raw, base64) = match bytes.map(String::from_utf8) {
+        Some(Ok(raw)) => (raw, String::new()),
+        Some(Err(e)) => (String::new(), general_purpose::STANDARD.encode(e.into_bytes())),
+        None => (text.to_string(), String::new()),
+    };
+    let json = serde_json::from_str(&raw).unwrap_or(Value::Null);
+    (raw, json, base64)
+}
+
+impl From<&RequestReceivedInMock> for HarRequest {
+    fn from(req: &RequestReceivedInMock) -> Self {
+        let headers = header_pairs(&req.HttpHeader);
+        let mut params: Vec<(&String, &Vec<String>)> = req.HttpParam.iter().flatten().collect();
+        params.sort();
+        let query: Vec<HarNameValue> = params
+            .into_iter()
+            .flat_map(|(name, values)| {
+                values.iter().map(move |value| HarNameValue {
+                    name: name.clone(),
+                    value: value.clone(),
+                })
+            })
+            .collect();
+        let scheme = if req.HttpScheme.is_empty() { "http" } else { &req.HttpScheme };
+        let host = header_of(&headers, "Host")
+            .map(String::from)
+            .or_else(|| Some(req.HttpProxyUrl.clone()).filter(|h| !h.is_empty()))
+            .unwrap_or_else(|| String::from("localhost"));
+        let path = if req.HttpPath.is_empty() { "/" } else { &req.HttpPath };
+        let target = with_query(path, &query_string(query.iter().map(|q| (&q.name, &q.value))));
+        let (text, encoding, size) = encode_body(&req.HttpBody, &req.HttpBodyRaw, &req.HttpBodyBase64);
+        let post_data = if text.is_empty() {
+            None
+        } else {
+            Some(HarPostData {
+                mime_type: header_of(&headers, "Content-Type").unwrap_or("").to_string(),
+                text,
+                encoding,
+            })
+        };
+        HarRequest {
+            method: if req.HttpMethod.is_empty() { String::from("GET") } else { req.HttpMethod.clone() },
+            url: format!("{}://{}{}", scheme, host, target),
+            cookies: cookie_pairs(&req.HttpCookie),
+            body_size: size,
+            headers,
+            query_string: query,
+            post_data,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&HarRequest> for RequestReceivedInMock {
+    fn from(har: &HarRequest) -> Self {
+        let (scheme, rest) = har.url.split_once("://").unwrap_or(("", &har.url));
+        let (host, target) = match rest.find('/') {
+            Some(i) if !scheme.is_empty() => (&rest[..i], &rest[i..]),
+            _ if !scheme.is_empty() => (rest, "/"),
+            _ => ("", rest),
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut params: HashMap<String, Vec<String>> = HashMap::new();
+        if har.query_string.is_empty() {
+            for pair in query.split('&').filter(|p| !p.is_empty()) {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+                params.entry(percent_decode(k)).or_default().push(percent_decode(v));
+            }
+        } else {
+            for q in &har.query_string {
+                params.entry(q.name.clone()).or_default().push(q.value.clone());
+            }
+        }
+        let (raw, json, base64) = match &har.post_data {
+            Some(p) => decode_body(&p.text, p.encoding.as_deref()),
+            None => (String::new(), Value::Null, String::new()),
+        };
+        RequestReceivedInMock {
+            HttpParam: if params.is_empty() { None } else { Some(params) },
+            HttpHeader: header_map(&har.headers),
+            HttpCookie: cookie_map(&har.cookies),
+            HttpBody: json,
+            HttpBodyRaw: raw,
+            HttpBodyBase64: base64,
+            HttpProxyUrl: host.to_string(),
+            HttpPath: path.to_string(),
+            HttpScheme: scheme.to_string(),
+            HttpMethod: har.method.clone(),
+        }
+    }
+}
+
+impl From<&HttpResponse> for HarResponse {
+    fn from(res: &HttpResponse) -> Self {
+        let status: u16 = res.StatusCode.trim().parse().unwrap_or(0);
+        let headers = header_pairs(&res.HttpHeader);
+        let mime = header_of(&headers, "Content-Type").unwrap_or("").to_string();
+        let (text, encoding, size) = encode_body(&res.HttpBody, &res.HttpBodyRaw, &res.HttpBodyBase64);
+        HarResponse {
+            status,
+            status_text: status_text(status).to_string(),
+            cookies: cookie_pairs(&res.HttpCookie),
+            redirect_url: header_of(&headers, "Location").unwrap_or("").to_string(),
+            headers,
+            content: HarContent {
+                size,
+                mime_type: mime,
+                text: Some(text),
+                encoding,
+            },
+            body_size: size,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&HarResponse> for HttpResponse {
+    fn from(har: &HarResponse) -> Self {
+        let text = har.content.text.as_deref().unwrap_or("");
+        let (raw, json, base64) = decode_body(text, har.content.encoding.as_deref());
+        HttpResponse {
+            HttpHeader: header_map(&har.headers),
+            HttpCookie: cookie_map(&har.cookies),
+            HttpBody: json,
+            HttpBodyRaw: raw,
+            HttpBodyBase64: base64,
+            StatusCode: har.status.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+impl HarEntry {
+    pub fn new(req: &RequestReceivedInMock, res: &HttpResponse) -> Self {
+        HarEntry {
+            request: req.into(),
+            response: res.into(),
+            ..Default::default()
+        }
+    }
+    /// Request of the entry and its response, whose `HttpReq` is the request
+    pub fn to_exchange(&self) -> (RequestReceivedInMock, HttpResponse) {
+        let req = RequestReceivedInMock::from(&self.request);
+        let mut res = HttpResponse::from(&self.response);
+        res.HttpReq = req.clone();
+        (req, res)
+    }
+}
+
+impl Har {
+    pub fn new(entries: Vec<HarEntry>) -> Self {
+        Har {
+            log: HarLog {
+                entries,
+                ..Default::default()
+            },
+        }
+    }
+    pub fn from_json(text: &str) -> Result<Har, Box<dyn Error + Sync + Send>> {
+        Ok(serde_json::from_str(text)?)
+    }
+    pub fn to_json(&self) -> Result<String, Box<dyn Error + Sync + Send>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// HAR file of `exchanges`
+pub fn export_har(exchanges: &[(RequestReceivedInMock, HttpResponse)]) -> Result<String, Box<dyn Error + Sync + Send>> {
+    Har::new(exchanges.iter().map(|(req, res)| HarEntry::new(req, res)).collect()).to_json()
+}
+
+/// Request and response of every entry of a HAR file
+pub fn import_har(text: &str) -> Result<Vec<(RequestReceivedInMock, HttpResponse)>, Box<dyn Error + Sync + Send>> {
+    Ok(Har::from_json(text)?.log.entries.iter().map(HarEntry::to_exchange).collect())
+}
+
+/// One stub per entry of a HAR file, matching the method, path, query and JSON body of its request and
+/// answering with its response, without its `Content-Encoding`, `Content-Length` and `Transfer-Encoding`
+pub fn stubs_from_har(text: &str) -> Result<Vec<Stub>, Box<dyn Error + Sync + Send>> {
+    let mut stubs = vec![];
+    for (req, res) in import_har(text)? {
+        let mut stub = Stub::new().method(&req.HttpMethod).path(&req.HttpPath);
+        let mut params: Vec<(&String, &Vec<String>)> = req.HttpParam.iter().flatten().collect();
+        params.sort();
+        for (name, values) in params {
+            if let Some(value) = values.first() {
+                stub = stub.query(name, value);
+            }
+        }
+        if !req.HttpBody.is_null() {
+            stub = stub.body_json(req.HttpBody.clone());
+        }
+        let mut response = StubResponse {
+            status: res.StatusCode.clone(),
+            ..Default::default()
+        };
+        if res.HttpBodyBase64.is_empty() {
+            response.body_raw = Some(res.HttpBodyRaw.clone());
+        } else {
+            response.body_bytes = Some(general_purpose::STANDARD.decode(&res.HttpBodyBase64)?);
+        }
+        for pair in header_pairs(&res.HttpHeader) {
+            if TRANSFER_HEADERS.iter().any(|h| pair.name.eq_ignore_ascii_case(h)) {
+                continue;
+            }
+            response = response.header(&pair.name, &pair.value);
+        }
+        stubs.push(stub.respond(response));
+    }
+    Ok(stubs)
+}
+
+/// Adds the stubs of a HAR file to the mock `name`, returns how many were added
+pub fn add_har_stubs(name: &str, text: &str) -> Result<usize, Box<dyn Error + Sync + Send>> {
+    let stubs = stubs_from_har(text)?;
+    let count = stubs.len();
+    for stub in stubs {
+        add_stub(name, stub)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub::find_stub;
+    use serde_json::json;
+
+    /// Trimmed export of Chrome DevTools (Network > Save all as HAR), HTTP/2 pseudo headers and the `_`
+    /// fields of Chrome included
+    const DEVTOOLS_HAR: &str = r#"{
+  "log": {
+    "version": "1.2",
+    "creator": { "name": "WebInspector", "version": "537.36" },
+    "pages": [
+      { "startedDateTime": "2024-03-05T10:12:01.511Z", "id": "page_1", "title": "https://app.example.com/", "pageTimings": { "onContentLoad": 412.2, "onLoad": 530.9 } }
+    ],
+    "entries": [
+      {
+        "_initiator": { "type": "script" },
+        "_priority": "High",
+        "_resourceType": "fetch",
+        "cache": {},
+        "connection": "443",
+        "pageref": "page_1",
+        "request": {
+          "method": "GET",
+          "url": "https://api.example.com/users?page=2&tag=a%20b&tag=c",
+          "httpVersion": "http/2.0",
+          "headers": [
+            { "name": ":authority", "value": "api.example.com" },
+            { "name": ":method", "value": "GET" },
+            { "name": ":path", "value": "/users?page=2&tag=a%20b&tag=c" },
+            { "name": "accept", "value": "application/json" },
+            { "name": "cookie", "value": "session=abc; theme=dark" }
+          ],
+          "queryString": [
+            { "name": "page", "value": "2" },
+            { "name": "tag", "value": "a b" },
+            { "name": "tag", "value": "c" }
+          ],
+          "cookies": [
+            { "name": "session", "value": "abc", "path": "/", "domain": "api.example.com", "expires": "2024-04-05T10:12:01.000Z", "httpOnly": true, "secure": true, "sameSite": "Lax" },
+            { "name": "theme", "value": "dark", "expires": null, "httpOnly": false, "secure": false }
+          ],
+          "headersSize": -1,
+          "bodySize": 0
+        },
+        "response": {
+          "status": 200,
+          "statusText": "",
+          "httpVersion": "http/2.0",
+          "headers": [
+            { "name": "content-length", "value": "27" },
+            { "name": "content-type", "value": "application/json; charset=utf-8" },
+            { "name": "set-cookie", "value": "a=1; Path=/" },
+            { "name": "set-cookie", "value": "b=2; Path=/" }
+          ],
+          "cookies": [
+            { "name": "a", "value": "1", "path": "/" },
+            { "name": "b", "value": "2", "path": "/" }
+          ],
+          "content": { "size": 27, "mimeType": "application/json", "text": "[{\"id\":1},{\"id\":2},{\"id\":3}]" },
+          "redirectURL": "",
+          "headersSize": -1,
+          "bodySize": -1,
+          "_transferSize": 412,
+          "_error": null
+        },
+        "serverIPAddress": "93.184.216.34",
+        "startedDateTime": "2024-03-05T10:12:01.734Z",
+        "time": 88.317,
+        "timings": { "blocked": 1.9, "dns": -1, "ssl": -1, "connect": -1, "send": 0.2, "wait": 85.1, "receive": 1.1, "_blocked_queueing": 1.2 }
+      },
+      {
+        "_resourceType": "fetch",
+        "cache": {},
+        "request": {
+          "method": "POST",
+          "url": "https://api.example.com/users",
+          "httpVersion": "http/2.0",
+          "headers": [{ "name": "content-type", "value": "application/json" }],
+          "queryString": [],
+          "cookies": [],
+          "headersSize": -1,
+          "bodySize": 14,
+          "postData": { "mimeType": "application/json", "text": "{\"name\":\"Ada\"}" }
+        },
+        "response": {
+          "status": 201,
+          "statusText": "",
+          "httpVersion": "http/2.0",
+          "headers": [{ "name": "content-type", "value": "application/json" }],
+          "cookies": [],
+          "content": { "size": 8, "mimeType": "application/json", "text": "eyJpZCI6N30=", "encoding": "base64" },
+          "redirectURL": "",
+          "headersSize": -1,
+          "bodySize": -1
+        },
+        "startedDateTime": "2024-03-05T10:12:02.001Z",
+        "time": 40.5,
+        "timings": { "blocked": 0.8, "dns": -1, "ssl": -1, "connect": -1, "send": 0.1, "wait": 39.0, "receive": 0.6 }
+      },
+      {
+        "_resourceType": "image",
+        "cache": {},
+        "request": {
+          "method": "GET",
+          "url": "https://api.example.com/logo.png",
+          "httpVersion": "http/2.0",
+          "headers": [{ "name": "accept", "value": "image/avif,image/webp,*/*" }],
+          "queryString": [],
+          "cookies": [],
+          "headersSize": -1,
+          "bodySize": 0
+        },
+        "response": {
+          "status": 200,
+          "statusText": "",
+          "httpVersion": "http/2.0",
+          "headers": [{ "name": "content-type", "value": "image/png" }],
+          "cookies": [],
+          "content": { "size": 70, "mimeType": "image/png", "text": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==", "encoding": "base64" },
+          "redirectURL": "",
+          "headersSize": -1,
+          "bodySize": -1
+        },
+        "startedDateTime": "2024-03-05T10:12:02.120Z",
+        "time": 12.0,
+        "timings": { "blocked": 0.5, "dns": -1, "ssl": -1, "connect": -1, "send": 0.1, "wait": 10.9, "receive": 0.5 }
+      },
+      {
+        "_resourceType": "fetch",
+        "cache": {},
+        "request": {
+          "method": "GET",
+          "url": "https://api.example.com/orders",
+          "httpVersion": "HTTP/1.1",
+          "headers": [{ "name": "accept-encoding", "value": "gzip, deflate, br" }],
+          "queryString": [],
+          "cookies": [],
+          "headersSize": 96,
+          "bodySize": 0
+        },
+        "response": {
+          "status": 200,
+          "statusText": "OK",
+          "httpVersion": "HTTP/1.1",
+          "headers": [
+            { "name": "Content-Type", "value": "application/json" },
+            { "name": "Content-Encoding", "value": "gzip" },
+            { "name": "Transfer-Encoding", "value": "chunked" },
+            { "name": "Vary", "value": "Accept-Encoding" }
+          ],
+          "cookies": [],
+          "content": { "size": 22, "compression": 9, "mimeType": "application/json", "text": "[{\"id\":1,\"total\":42}]" },
+          "redirectURL": "",
+          "headersSize": 160,
+          "bodySize": 13
+        },
+        "startedDateTime": "2024-03-05T10:12:02.250Z",
+        "time": 20.4,
+        "timings": { "blocked": 0.4, "dns": -1, "ssl": -1, "connect": -1, "send": 0.1, "wait": 19.3, "receive": 0.6 }
+      }
+    ]
+  }
+}"#;
+
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+
+    fn headers(pairs: &[(&str, &[&str])]) -> Option<HashMap<String, Value>> {
+        Some(pairs.iter().map(|(name, values)| (name.to_string(), json!(values))).collect())
+    }
+
+    fn same<T: Serialize>(a: &T, b: &T) {
+        assert_eq!(serde_json::to_value(a).unwrap(), serde_json::to_value(b).unwrap());
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let req = RequestReceivedInMock {
+            HttpParam: Some(HashMap::from([
+                ("page".to_string(), vec!["2".to_string()]),
+                ("tag".to_string(), vec!["a b".to_string(), "c&d".to_string()]),
+            ])),
+            HttpHeader: headers(&[("Accept", &["application/json", "text/plain"]), ("Content-Type", &["application/json"])]),
+            HttpCookie: Some(HashMap::from([("session".to_string(), "abc".to_string())])),
+            HttpBody: json!({"name": "Ada"}),
+            HttpBodyRaw: r#"{"name":"Ada"}"#.to_string(),
+            HttpProxyUrl: "api.example.com".to_string(),
+            HttpPath: "/users".to_string(),
+            HttpScheme: "https".to_string(),
+            HttpMethod: "POST".to_string(),
+            ..Default::default()
+        };
+        let har = HarRequest::from(&req);
+        assert_eq!(har.url, "https://api.example.com/users?page=2&tag=a%20b&tag=c%26d");
+        assert_eq!(har.headers.iter().filter(|h| h.name == "Accept").count(), 2);
+        assert_eq!(har.cookies[0].value, "abc");
+        assert_eq!(har.post_data.as_ref().unwrap().encoding, None);
+        same(&RequestReceivedInMock::from(&har), &req);
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let res = HttpResponse {
+            HttpHeader: headers(&[("Set-Cookie", &["a=1", "b=2"]), ("Content-Type", &["application/json"])]),
+            HttpCookie: Some(HashMap::from([("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())])),
+            HttpBody: json!([1, 2]),
+            HttpBodyRaw: "[1,2]".to_string(),
+            StatusCode: "201".to_string(),
+            ..Default::default()
+        };
+        let har = HarResponse::from(&res);
+        assert_eq!(har.status_text, "Created");
+        assert_eq!(har.content.size, 5);
+        same(&HttpResponse::from(&har), &res);
+    }
+
+    #[test]
+    fn binary_bodies_round_trip() {
+        let res = HttpResponse {
+            HttpHeader: headers(&[("Content-Type", &["image/png"])]),
+            HttpBodyBase64: PNG.to_string(),
+            StatusCode: "200".to_string(),
+            ..Default::default()
+        };
+        let har = HarResponse::from(&res);
+        assert_eq!(har.content.text.as_deref(), Some(PNG));
+        assert_eq!(har.content.encoding.as_deref(), Some("base64"));
+        assert_eq!(har.content.size, 70);
+        same(&HttpResponse::from(&har), &res);
+
+        let req = RequestReceivedInMock {
+            HttpHeader: headers(&[("Content-Type", &["application/octet-stream"])]),
+            HttpBodyBase64: general_purpose::STANDARD.encode([0u8, 0x9f, 0x92, 0x96, 0xff]),
+            HttpPath: "/upload".to_string(),
+            HttpScheme: "http".to_string(),
+            HttpProxyUrl: "localhost".to_string(),
+            HttpMethod: "PUT".to_string(),
+            ..Default::default()
+        };
+        let har = HarRequest::from(&req);
+        assert_eq!(har.post_data.as_ref().unwrap().encoding.as_deref(), Some("base64"));
+        assert_eq!(har.body_size, 5);
+        same(&RequestReceivedInMock::from(&har), &req);
+    }
+
+    #[test]
+    fn base64_text_is_text() {
+        let har = HarResponse {
+            content: HarContent {
+                size: 8,
+                mime_type: "application/octet-stream".to_string(),
+                text: Some("eyJpZCI6N30=".to_string()),
+                encoding: Some("base64".to_string()),
+            },
+            ..Default::default()
+        };
+        let res = HttpResponse::from(&har);
+        assert_eq!(res.HttpBodyRaw, r#"{"id":7}"#);
+        assert_eq!(res.HttpBody, json!({"id": 7}));
+        assert_eq!(res.HttpBodyBase64, "");
+    }
+
+    #[test]
+    fn ascii_body_of_a_binary_mime_is_not_labeled_base64() {
+        let res = HttpResponse {
+            HttpHeader: headers(&[("Content-Type", &["application/octet-stream"])]),
+            HttpBodyRaw: "aGVsbG8=".to_string(),
+            StatusCode: "200".to_string(),
+            ..Default::default()
+        };
+        let har = HarResponse::from(&res);
+        assert_eq!(har.content.encoding, None);
+        assert_eq!(HttpResponse::from(&har).HttpBodyRaw, "aGVsbG8=");
+    }
+
+    #[test]
+    fn devtools_har() {
+        let exchanges = import_har(DEVTOOLS_HAR).unwrap();
+        assert_eq!(exchanges.len(), 4);
+
+        let (req, res) = &exchanges[0];
+        assert_eq!(req.HttpMethod, "GET");
+        assert_eq!(req.HttpScheme, "https");
+        assert_eq!(req.HttpProxyUrl, "api.example.com");
+        assert_eq!(req.HttpPath, "/users");
+        assert_eq!(req.HttpParam.as_ref().unwrap()["tag"], vec!["a b", "c"]);
+        assert_eq!(req.HttpCookie.as_ref().unwrap()["session"], "abc");
+        let names: Vec<&String> = req.HttpHeader.as_ref().unwrap().keys().collect();
+        assert_eq!(names.len(), 2, "{:?}", names);
+        assert!(names.iter().all(|n| !n.starts_with(':')));
+        assert_eq!(res.HttpHeader.as_ref().unwrap()["set-cookie"], json!(["a=1; Path=/", "b=2; Path=/"]));
+        assert_eq!(res.HttpBody, json!([{"id": 1}, {"id": 2}, {"id": 3}]));
+        assert_eq!(res.HttpReq.HttpPath, "/users");
+
+        let (req, res) = &exchanges[1];
+        assert_eq!(req.HttpBody, json!({"name": "Ada"}));
+        assert_eq!(res.StatusCode, "201");
+        assert_eq!(res.HttpBodyRaw, r#"{"id":7}"#);
+
+        let (_, res) = &exchanges[2];
+        assert_eq!(res.HttpBodyRaw, "");
+        assert_eq!(res.HttpBodyBase64, PNG);
+
+        let again = import_har(&export_har(&exchanges).unwrap()).unwrap();
+        for ((req, res), (req2, res2)) in exchanges.iter().zip(&again) {
+            same(req, req2);
+            same(res, res2);
+        }
+    }
+
+    #[test]
+    fn devtools_har_stubs_serve_the_bytes() {
+        assert_eq!(add_har_stubs("/har-devtools", DEVTOOLS_HAR).unwrap(), 4);
+        let req = RequestReceivedInMock {
+            HttpMethod: "GET".to_string(),
+            HttpPath: "/logo.png".to_string(),
+            ..Default::default()
+        };
+        let stub = find_stub("/har-devtools", &req).unwrap();
+        let png = general_purpose::STANDARD.decode(PNG).unwrap();
+        assert_eq!(stub.response.body_bytes.as_deref(), Some(&png[..]));
+        assert!(png.starts_with(b"\x89PNG"));
+        let mut res = HttpResponse::default();
+        stub.response.apply(&mut res);
+        assert_eq!(res.HttpBodyBase64, PNG);
+        assert_eq!(res.HttpBodyRaw, "");
+
+        let req = RequestReceivedInMock {
+            HttpMethod: "POST".to_string(),
+            HttpPath: "/users".to_string(),
+            HttpBody: json!({"name": "Ada"}),
+            ..Default::default()
+        };
+        let stub = find_stub("/har-devtools", &req).unwrap();
+        assert_eq!(stub.response.body_raw.as_deref(), Some(r#"{"id":7}"#));
+
+        let req = RequestReceivedInMock {
+            HttpMethod: "GET".to_string(),
+            HttpPath: "/orders".to_string(),
+            ..Default::default()
+        };
+        let stub = find_stub("/har-devtools", &req).unwrap();
+        assert_eq!(stub.response.body_raw.as_deref(), Some(r#"[{"id":1,"total":42}]"#));
+        let names: Vec<&str> = stub.response.headers.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["Content-Type", "Vary"]);
+
+        let req = RequestReceivedInMock {
+            HttpMethod: "GET".to_string(),
+            HttpPath: "/users".to_string(),
+            HttpParam: Some(HashMap::from([
+                ("page".to_string(), vec!["2".to_string()]),
+                ("tag".to_string(), vec!["a b".to_string()]),
+            ])),
+            ..Default::default()
+        };
+        let stub = find_stub("/har-devtools", &req).unwrap();
+        assert!(stub.response.headers.iter().all(|(n, _)| n != "content-length"));
+        assert!(stub.response.headers.iter().any(|(n, _)| n == "content-type"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Typed access to the operations the Wasm Mock Server exposes through `host_call`.
+//!
+//! Every payload format (JSON, MessagePack, LittleEndian u64) is encoded and decoded here. The macros of
+//! this crate go through [`WapcHost`]; code written against [`HostClient`] can be handed a fake instead.
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+use wapc_guest::prelude::host_call;
+
+use crate::record::{AssertionRecord, StepRecord};
+use crate::{HttpRequest, HttpResponse, TcpReq};
+
+/// Error returned by [`HostClient`] operations
+#[derive(Debug)]
+pub enum HostError {
+    /// The host rejected the call or the operation failed inside the mock server
+    Call(String),
+    /// The reply was not valid utf-8
+    Utf8(std::str::Utf8Error),
+    /// JSON payload could not be encoded or decoded
+    Json(serde_json::Error),
+    /// MessagePack payload could not be encoded
+    MsgPackEncode(rmp_serde::encode::Error),
+    /// MessagePack payload could not be decoded
+    MsgPackDecode(rmp_serde::decode::Error),
+    /// The reply did not have the expected shape
+    InvalidReply(String),
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostError::Call(e) => write!(f, "host call failed: {}", e),
+            HostError::Utf8(e) => write!(f, "host reply is not utf-8: {}", e),
+            HostError::Json(e) => write!(f, "json payload: {}", e),
+            HostError::MsgPackEncode(e) => write!(f, "msgpack payload: {}", e),
+            HostError::MsgPackDecode(e) => write!(f, "msgpack payload: {}", e),
+            HostError::InvalidReply(e) => write!(f, "invalid host reply: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HostError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HostError::Utf8(e) => Some(e),
+            HostError::Json(e) => Some(e),
+            HostError::MsgPackEncode(e) => Some(e),
+            HostError::MsgPackDecode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::str::Utf8Error> for HostError {
+    fn from(e: std::str::Utf8Error) -> Self {
+        HostError::Utf8(e)
+    }
+}
+
+impl From<serde_json::Error> for HostError {
+    fn from(e: serde_json::Error) -> Self {
+        HostError::Json(e)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for HostError {
+    fn from(e: rmp_serde::encode::Error) -> Self {
+        HostError::MsgPackEncode(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for HostError {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        HostError::MsgPackDecode(e)
+    }
+}
+
+fn read_u64(res: &[u8]) -> Result<u64, HostError> {
+    if res.len() < 8 {
+        return Err(HostError::InvalidReply(format!("expected 8 bytes, got {}", res.len())));
+    }
+    Ok(LittleEndian::read_u64(res))
+}
+
+/// One method per host operation of the mock server.
+///
+/// Only [`HostClient::call`] has to be implemented; the other methods encode their payload and decode the
+/// reply on top of it. A fake can implement `call` to answer at the wire level, or override single methods.
+pub trait HostClient {
+    /// Raw host call
+    ///
+    /// # Arguments
+    ///
+    /// * `binding` - address, key or uid the operation applies to
+    /// * `namespace` - `foo` for most operations
+    /// * `operation` - operation name
+    /// * `payload` - encoded payload
+    fn call(&self, binding: &str, namespace: &str, operation: &str, payload: &[u8]) -> Result<Vec<u8>, HostError>;
+
+    /// HTTP request from the mock server, returning the JSON encoded `HttpResponse`
+    fn http_request_json(&self, addr: &str, request: &HttpRequest) -> Result<String, HostError> {
+        let payload = serde_json::to_vec(request)?;
+        let res = self.call(addr, "foo", "http_request", &payload)?;
+        Ok(std::str::from_utf8(&res)?.to_owned())
+    }
+    /// HTTP request from the mock server
+    fn http_request(&self, addr: &str, request: &HttpRequest) -> Result<HttpResponse, HostError> {
+        let j = self.http_request_json(addr, request)?;
+        Ok(serde_json::from_str(&j)?)
+    }
+    /// [`HostClient::tcp_request`], returning the MessagePack encoded reply as the host sent it
+    fn tcp_request_raw(&self, addr: &str, request: &TcpReq) -> Result<Vec<u8>, HostError> {
+        let payload = rmp_serde::to_vec(request)?;
+        self.call(addr, "foo", "tcp_request", &payload)
+    }
+    /// Sends a tcp payload from the mock server to the remote connection of `addr` ({local address}-:{remote address})
+    fn tcp_request(&self, addr: &str, request: &TcpReq) -> Result<TcpReq, HostError> {
+        let res = self.tcp_request_raw(addr, request)?;
+        Ok(rmp_serde::from_read_ref(&res)?)
+    }
+    /// [`HostClient::tcp_response`], returning the MessagePack encoded reply as the host sent it
+    fn tcp_response_raw(&self, addr: &str, request: &TcpReq) -> Result<Vec<u8>, HostError> {
+        let payload = rmp_serde::to_vec(request)?;
+        self.call(addr, "foo", "tcp_response", &payload)
+    }
+    /// Sends a tcp payload from the mock server to the local connection of `addr` ({local address}-:{remote address})
+    fn tcp_response(&self, addr: &str, request: &TcpReq) -> Result<TcpReq, HostError> {
+        let res = self.tcp_response_raw(addr, request)?;
+        Ok(rmp_serde::from_read_ref(&res)?)
+    }
+    /// RPC request with an encoded protobuf payload, only available with a customized mock server
+    fn rpc_request(&self, command: &str, payload: &[u8]) -> Result<Vec<u8>, HostError> {
+        self.call(command, "foo", "rpc_request", payload)
+    }
+    /// Redis command, `method` is get/delete
+    fn redis(&self, addr: &str, method: &str, key: &str) -> Result<Vec<u8>, HostError> {
+        self.call(addr, "foo", &format!("redis_{}", method), key.as_bytes())
+    }
+    /// Memcache command, `method` is get/delete
+    fn memcache(&self, addr: &str, method: &str, key: &str) -> Result<Vec<u8>, HostError> {
+        self.call(addr, "foo", &format!("memcache_{}", method), key.as_bytes())
+    }
+    /// Stores `value` under `key` in the mock server's indexdb
+    fn indexdb_store(&self, key: &str, value: &[u8]) -> Result<Vec<u8>, HostError> {
+        self.call(key, "foo", "indexdb_store", value)
+    }
+    /// Value of `key` in the mock server's indexdb, the server answers `default` when it is missing
+    fn indexdb_get(&self, key: &str, default: &str) -> Result<String, HostError> {
+        let res = self.call(key, "foo", "indexdb_get", default.as_bytes())?;
+        Ok(std::str::from_utf8(&res)?.to_owned())
+    }
+    /// MySQL query against the database configured as `key`
+    fn mysql(&self, key: &str, query: &str) -> Result<String, HostError> {
+        let res = self.call(key, "foo", "mysql", query.as_bytes())?;
+        Ok(std::str::from_utf8(&res)?.to_owned())
+    }
+    /// Automation loop index
+    fn get_index(&self, uid: &str) -> Result<i64, HostError> {
+        let res = self.call(uid, "foo", "get_index", b"")?;
+        Ok(read_u64(&res)? as i64)
+    }
+    /// Sleeps for `millis` milliseconds inside the host
+    fn sleep(&self, uid: &str, millis: u64) -> Result<Vec<u8>, HostError> {
+        let mut buf = [0; 8];
+        LittleEndian::write_u64(&mut buf, millis);
+        self.call(uid, "foo", "sleep", &buf)
+    }
+    /// Current time stamp of the host
+    fn now(&self) -> Result<i64, HostError> {
+        let res = self.call("default", "foo", "now", b"")?;
+        Ok(read_u64(&res)? as i64)
+    }
+    /// Saves `payload` into the file `path` inside the mock server
+    fn save_file(&self, path: &str, payload: &[u8]) -> Result<Vec<u8>, HostError> {
+        self.call(path, "foo", "savefile", payload)
+    }
+    /// Reports an assertion as passed or failed
+    fn assert_result(&self, uid: &str, passed: bool, message: &[u8]) -> Result<Vec<u8>, HostError> {
+        let operation = if passed { "assert_pass" } else { "assert_fail" };
+        self.call(uid, "foo", operation, message)
+    }
+    /// Reports an automation step as passed or failed
+    fn step_result(&self, uid: &str, passed: bool, message: &[u8]) -> Result<Vec<u8>, HostError> {
+        let operation = if passed { "step_pass" } else { "step_fail" };
+        self.call(uid, "foo", operation, message)
+    }
+    /// Reports a structured assertion through `assert_pass`/`assert_fail`
+    fn assert_record(&self, uid: &str, record: &AssertionRecord) -> Result<Vec<u8>, HostError> {
+        let payload = serde_json::to_vec(record)?;
+        self.assert_result(uid, record.passed, &payload)
+    }
+    /// Reports a structured automation step through `step_pass`/`step_fail`
+    fn step_record(&self, uid: &str, record: &StepRecord) -> Result<Vec<u8>, HostError> {
+        let payload = serde_json::to_vec(record)?;
+        self.step_result(uid, record.passed, &payload)
+    }
+    /// Md5 sum computed by the host
+    fn md5sum(&self, input: &str) -> Result<String, HostError> {
+        let res = self.call("default", "crypto", "md5sum", input.as_bytes())?;
+        Ok(std::str::from_utf8(&res)?.to_owned())
+    }
+    /// Forwards a message to the dynamic websocket wasm bound as `binding` ({ws uid}|{function})
+    fn websocket(&self, binding: &str, payload: &[u8]) -> Result<Vec<u8>, HostError> {
+        self.call(binding, "foo", "websocket", payload)
+    }
+    /// Calls the dynamic websocket wasm bound as `binding` ({ws uid}|{key}|{index})
+    fn websocket_call(&self, binding: &str, payload: &[u8]) -> Result<Vec<u8>, HostError> {
+        self.call(binding, "foo", "websocket_call", payload)
+    }
+}
+
+/// [`HostClient`] over `wapc_guest::host_call`, i.e. the mock server the wasm is loaded into
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WapcHost;
+
+impl HostClient for WapcHost {
+    fn call(&self, binding: &str, namespace: &str, operation: &str, payload: &[u8]) -> Result<Vec<u8>, HostError> {
+        host_call(binding, namespace, operation, payload).map_err(|e| HostError::Call(e.to_string()))
+    }
+}
+
+/// UID saved by the host through `save_uid`
+pub fn uid() -> String {
+    unsafe { String::from_utf8_lossy(&*std::ptr::addr_of!(crate::UID)).into_owned() }
+}
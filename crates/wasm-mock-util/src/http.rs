@@ -0,0 +1,186 @@
+//! HTTP client of the automation, going through the `http_request` operation of the mock server.
+//!
+//! [`send`] takes a [`HttpRequestBuilder`] and returns the [`HttpResponse`] or a [`HostError`], never
+//! panicking. Each request is reported as a step, as the `foo_http_request!` macros do.
+//!
+//! There is no timeout: the `http_request` operation of the mock server takes no deadline, and the guest
+//! waits for its reply.
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct User { id: u64 }
+//! let res = http::send(HttpRequestBuilder::get("http://localhost:8080/users/1"))?;
+//! let user: User = res.json()?;
+//! ```
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::host::{HostClient, HostError, WapcHost};
+use crate::record::{report_step, SourceLocation, StepRecord};
+use crate::{Http1xBuilder, HttpRequest, HttpResponse};
+
+/// Request sent by [`send`]
+///
+/// # Examples
+///
+/// ```
+/// use wasm_mock_util::http::HttpRequestBuilder;
+/// let (addr, req) = HttpRequestBuilder::get("http://localhost:8080/users?page=2")
+///     .query("size", "10")
+///     .header("Accept", "application/json")
+///     .proxy("http://proxy:3128")
+///     .build();
+/// assert_eq!(addr, "http://localhost:8080/users?page=2&size=10");
+/// assert_eq!(req.Http1x, "GET /users?page=2&size=10 HTTP/1.1\r\nAccept: application/json\r\n\r\n");
+/// assert_eq!(req.ProxyUrl, "http://proxy:3128");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequestBuilder {
+    origin: String,
+    head: Http1xBuilder,
+    body: Vec<u8>,
+    proxy_url: String,
+}
+
+impl HttpRequestBuilder {
+    /// Request of `method` on `url`, whose path and query become the request target
+    pub fn new(method: &str, url: &str) -> Self {
+        let start = url.find("://").map(|i| i + 3).unwrap_or(0);
+        let (origin, target) = match url[start..].find(['/', '?']) {
+            Some(i) => url.split_at(start + i),
+            None => (url, "/"),
+        };
+        HttpRequestBuilder {
+            origin: origin.to_string(),
+            head: Http1xBuilder::new(method).path(target),
+            body: vec![],
+            proxy_url: String::new(),
+        }
+    }
+    pub fn get(url: &str) -> Self {
+        Self::new("GET", url)
+    }
+    pub fn post(url: &str) -> Self {
+        Self::new("POST", url)
+    }
+    pub fn put(url: &str) -> Self {
+        Self::new("PUT", url)
+    }
+    pub fn patch(url: &str) -> Self {
+        Self::new("PATCH", url)
+    }
+    pub fn delete(url: &str) -> Self {
+        Self::new("DELETE", url)
+    }
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.head = self.head.header(name, value);
+        self
+    }
+    /// Adds a query parameter, encoded when the request is built
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.head = self.head.query(key, value);
+        self
+    }
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+    /// JSON body, with the `Content-Type: application/json` header
+    pub fn json(self, body: &Value) -> Self {
+        self.header("Content-Type", "application/json").body(body.to_string())
+    }
+    /// HTTP proxy the mock server sends the request through
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.proxy_url = url.to_string();
+        self
+    }
+    /// Address and payload of the `http_request` host call
+    pub fn build(&self) -> (String, HttpRequest) {
+        let request = HttpRequest {
+            Http1x: self.head.build(),
+            HttpBody: self.body.clone(),
+            ProxyUrl: self.proxy_url.clone(),
+        };
+        (format!("{}{}", self.origin, self.head.target()), request)
+    }
+}
+
+/// [`send`] through `host`, without reporting a step
+pub fn send_with<H: HostClient>(host: &H, req: &HttpRequestBuilder) -> Result<HttpResponse, HostError> {
+    let (addr, request) = req.build();
+    host.http_request(&addr, &request)
+}
+
+/// Sends `req` from the mock server and reports it as a step
+#[track_caller]
+pub fn send(req: HttpRequestBuilder) -> Result<HttpResponse, HostError> {
+    let caller = std::panic::Location::caller();
+    let location = SourceLocation::new(caller.file(), caller.line(), caller.column());
+    let (_, request) = req.build();
+    let request = serde_json::to_string(&request)?;
+    let res = send_with(&WapcHost, &req);
+    let (passed, outcome) = match &res {
+        Ok(res) => (true, format!("res:{}", serde_json::to_string(res)?)),
+        Err(e) => (false, format!("err:{}", e)),
+    };
+    report_step(StepRecord::new(passed, format!("HTTP req:{} {}", request, outcome), location))?;
+    res
+}
+
+/// `http_request` host call of the `foo_http_request!` macros: reports a step and decodes the reply as `T`
+#[doc(hidden)]
+pub fn request_as<T: DeserializeOwned>(addr: &str, request: HttpRequest, location: SourceLocation) -> Result<T, HostError> {
+    let body = serde_json::to_string(&request)?;
+    match WapcHost.http_request_json(addr, &request) {
+        Ok(j) => {
+            report_step(StepRecord::new(true, format!("HTTP req:{} res:{}", body, j), location))?;
+            Ok(serde_json::from_str(&j)?)
+        }
+        Err(e) => {
+            report_step(StepRecord::new(false, format!("HTTP req:{} err:{}", body, e), location))?;
+            Err(e)
+        }
+    }
+}
+
+impl HttpResponse {
+    /// Body deserialized into `T`, from `HttpBody` or `HttpBodyRaw` when the mock server left `HttpBody` empty
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, HostError> {
+        if self.HttpBody.is_null() {
+            Ok(serde_json::from_str(&self.HttpBodyRaw)?)
+        } else {
+            Ok(T::deserialize(&self.HttpBody)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_mock_host_abi::MockHost;
+
+    #[test]
+    fn send_with_makes_one_http_request() {
+        let host = MockHost::new();
+        host.reply_json(
+            "foo",
+            "http_request",
+            &HttpResponse {
+                StatusCode: String::from("200"),
+                ..Default::default()
+            },
+        );
+        let req = HttpRequestBuilder::get("http://localhost:8080/users/1").proxy("http://proxy:3128");
+        assert_eq!(send_with(&WapcHost, &req).unwrap().StatusCode, "200");
+        let calls = host.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].operation, "http_request");
+        assert_eq!(calls[0].binding, "http://localhost:8080/users/1");
+        let sent: HttpRequest = serde_json::from_slice(&calls[0].payload).unwrap();
+        assert_eq!(sent.ProxyUrl, "http://proxy:3128");
+
+        host.fail("foo", "http_request", "connection refused");
+        let e = send_with(&WapcHost, &req).unwrap_err();
+        assert!(matches!(e, HostError::Call(_)), "{:?}", e);
+    }
+}
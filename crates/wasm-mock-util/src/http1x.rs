@@ -0,0 +1,167 @@
+//! Builder of the HTTP/1.x request head carried in `HttpRequest::Http1x`.
+use std::fmt::Write;
+
+/// Percent-encodes everything but the RFC 3986 unreserved characters
+pub fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(b as char),
+            _ => {
+                let _ = write!(out, "%{:02X}", b);
+            }
+        }
+    }
+    out
+}
+
+fn hex(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Decodes `%XX` escapes and `+`, leaving malformed escapes as they are
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(h), Some(l)) => {
+                    out.push(h << 4 | l);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encodes `pairs` as `k1=v1&k2=v2`, percent-encoding keys and values
+pub fn query_string<I, K, V>(pairs: I) -> String
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut out = String::new();
+    for (k, v) in pairs {
+        if !out.is_empty() {
+            out.push('&');
+        }
+        out.push_str(&percent_encode(k.as_ref()));
+        out.push('=');
+        out.push_str(&percent_encode(v.as_ref()));
+    }
+    out
+}
+
+/// Appends the encoded `query` to `target`, which may already carry a query string
+pub fn with_query(target: &str, query: &str) -> String {
+    if query.is_empty() {
+        return target.to_string();
+    }
+    let sep = if !target.contains('?') {
+        "?"
+    } else if target.ends_with('?') || target.ends_with('&') {
+        ""
+    } else {
+        "&"
+    };
+    format!("{}{}{}", target, sep, query)
+}
+
+/// HTTP/1.x request line and headers
+///
+/// # Examples
+///
+/// ```
+/// use wasm_mock_util::Http1xBuilder;
+/// let http1x = Http1xBuilder::new("GET")
+///     .path("/search")
+///     .query("q", "wasm mock")
+///     .header("Accept", "application/json")
+///     .build();
+/// assert_eq!(http1x, "GET /search?q=wasm%20mock HTTP/1.1\r\nAccept: application/json\r\n\r\n");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Http1xBuilder {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    version: u8,
+    headers: Vec<(String, String)>,
+}
+
+impl Http1xBuilder {
+    /// `{method} / HTTP/1.1` without headers
+    pub fn new(method: &str) -> Self {
+        Http1xBuilder {
+            method: method.to_string(),
+            path: String::from("/"),
+            query: vec![],
+            version: 1,
+            headers: vec![],
+        }
+    }
+    pub fn method(mut self, method: &str) -> Self {
+        self.method = method.to_string();
+        self
+    }
+    /// Request path, may already contain an encoded query string. An empty path is sent as `/`
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = if path.is_empty() {
+            String::from("/")
+        } else {
+            path.to_string()
+        };
+        self
+    }
+    /// Adds a query parameter, encoded when the head is built
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+    /// Adds query parameters from a map or list of pairs
+    pub fn queries<I, K, V>(mut self, pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (k, v) in pairs {
+            self.query.push((k.as_ref().to_string(), v.as_ref().to_string()));
+        }
+        self
+    }
+    /// Minor HTTP version, `0` for HTTP/1.0 and `1` for HTTP/1.1
+    pub fn version(mut self, minor: u8) -> Self {
+        self.version = minor;
+        self
+    }
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if !name.is_empty() {
+            self.headers.push((name.to_string(), value.to_string()));
+        }
+        self
+    }
+    /// Path followed by the encoded query string
+    pub fn target(&self) -> String {
+        with_query(&self.path, &query_string(self.query.iter().map(|(k, v)| (k, v))))
+    }
+    pub fn build(&self) -> String {
+        let mut s = format!("{} {} HTTP/1.{}\r\n", self.method, self.target(), self.version);
+        for (name, value) in &self.headers {
+            s.push_str(name);
+            s.push_str(": ");
+            s.push_str(value);
+            s.push_str("\r\n");
+        }
+        s.push_str("\r\n");
+        s
+    }
+}
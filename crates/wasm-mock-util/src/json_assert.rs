@@ -0,0 +1,220 @@
+//! Assertions on JSON and text bodies behind `foo_assert_json_include!`, `foo_assert_json_eq!`,
+//! `foo_assert_json_path!`, `foo_assert_json_schema!` and `foo_assert_regex!`.
+//!
+//! Each function builds an [`AssertionRecord`] that the macros report like `foo_assert_eq!`, with the path
+//! diff or the schema errors in `diff` when the assertion fails.
+use assert_json_diff::{assert_json_matches_no_panic, CompareMode, Config};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::record::{AssertionRecord, Operand, SourceLocation};
+use crate::schema;
+
+/// `v` as JSON, `Value::Null` when it cannot be serialized
+pub fn to_json<T: Serialize + ?Sized>(v: &T) -> Value {
+    serde_json::to_value(v).unwrap_or(Value::Null)
+}
+
+fn operand(v: &Value) -> Operand {
+    Operand {
+        debug: v.to_string(),
+        json: Some(v.clone()),
+    }
+}
+
+fn text_operand(s: &str) -> Operand {
+    Operand {
+        debug: format!("{:?}", s),
+        json: Some(Value::String(s.to_string())),
+    }
+}
+
+/// `actual` compared with `expected`. Inclusive mode only requires the fields of `expected` to be in `actual`
+pub fn json_match(
+    actual: &Value,
+    expected: &Value,
+    strict: bool,
+    description: String,
+    location: SourceLocation,
+) -> AssertionRecord {
+    let mode = if strict { CompareMode::Strict } else { CompareMode::Inclusive };
+    let diff = assert_json_matches_no_panic(actual, expected, Config::new(mode)).err();
+    let passed = diff.is_none();
+    let kind = if strict { "json eq" } else { "json include" };
+    let message = if passed {
+        format!("`{}` {} assertion passed", description, kind)
+    } else {
+        format!("`{}` {} assertion failed", description, kind)
+    };
+    AssertionRecord {
+        passed,
+        description,
+        message,
+        left: Some(operand(actual)),
+        right: Some(operand(expected)),
+        diff,
+        index: None,
+        location,
+    }
+}
+
+/// Value at `path` in `value`. A path starting with `/` (or empty) is a JSON pointer, anything else a
+/// JSONPath expression. A definite JSONPath (names and single indexes only) gives the value it points to;
+/// wildcards, `..`, filters, unions and slices always give an array of their matches, even of one
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wasm_mock_util::json_assert::json_select;
+/// let body = json!({"data": {"users": [{"id": 1}, {"id": 2}]}});
+/// assert_eq!(json_select(&body, "/data/users/0/id"), Ok(json!(1)));
+/// assert_eq!(json_select(&body, "$.data.users[0].id"), Ok(json!(1)));
+/// assert_eq!(json_select(&body, "$.data.users[*].id"), Ok(json!([1, 2])));
+/// assert_eq!(json_select(&body, "$.data.users[?(@.id == 2)].id"), Ok(json!([2])));
+/// ```
+pub fn json_select(value: &Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() || path.starts_with('/') {
+        return value.pointer(path).cloned().ok_or_else(|| format!("{} not found", path));
+    }
+    let found = jsonpath_lib::select(value, path).map_err(|e| format!("invalid json path {}: {:?}", path, e))?;
+    if !is_definite(path) {
+        return Ok(Value::Array(found.into_iter().cloned().collect()));
+    }
+    match found.first() {
+        Some(v) => Ok((*v).clone()),
+        None => Err(format!("{} not found", path)),
+    }
+}
+
+/// Whether the JSONPath `path` selects at most one value
+fn is_definite(path: &str) -> bool {
+    let mut quote = None;
+    let mut in_brackets = false;
+    let mut prev = ' ';
+    for c in path.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '*' | '?') => return false,
+            (None, '.') if prev == '.' => return false,
+            (None, ',' | ':') if in_brackets => return false,
+            (None, '[') => in_brackets = true,
+            (None, ']') => in_brackets = false,
+            _ => {}
+        }
+        prev = c;
+    }
+    true
+}
+
+/// Value at `path` in `actual` strictly compared with `expected`
+pub fn json_path_match(
+    actual: &Value,
+    path: &str,
+    expected: &Value,
+    description: String,
+    location: SourceLocation,
+) -> AssertionRecord {
+    match json_select(actual, path) {
+        Ok(found) => {
+            let mut record = json_match(&found, expected, true, description, location);
+            record.message = format!("{} at {}", record.message, path);
+            record
+        }
+        Err(e) => AssertionRecord {
+            passed: false,
+            message: format!("`{}` json path assertion failed: {}", description, e),
+            description,
+            left: Some(operand(actual)),
+            right: Some(operand(expected)),
+            diff: Some(e),
+            index: None,
+            location,
+        },
+    }
+}
+
+/// `actual` validated against the JSON Schema `schema`, see [`schema::validate`]
+pub fn json_schema_match(actual: &Value, schema: &Value, description: String, location: SourceLocation) -> AssertionRecord {
+    let errors = schema::validate(schema, actual);
+    let passed = errors.is_empty();
+    let message = if passed {
+        format!("`{}` json schema assertion passed", description)
+    } else {
+        format!("`{}` json schema assertion failed: {} errors", description, errors.len())
+    };
+    AssertionRecord {
+        passed,
+        description,
+        message,
+        left: Some(operand(actual)),
+        right: Some(operand(schema)),
+        diff: if passed { None } else { Some(errors.join("\n")) },
+        index: None,
+        location,
+    }
+}
+
+/// `text` searched for the regular expression `pattern`
+pub fn regex_match(text: &str, pattern: &str, description: String, location: SourceLocation) -> AssertionRecord {
+    let (passed, diff) = match Regex::new(pattern) {
+        Ok(re) => (re.is_match(text), None),
+        Err(e) => (false, Some(format!("invalid regex: {}", e))),
+    };
+    let message = if passed {
+        format!("`{}` regex assertion passed: `{}`", description, pattern)
+    } else {
+        format!("`{}` regex assertion failed: `{}` not found", description, pattern)
+    };
+    AssertionRecord {
+        passed,
+        description,
+        message,
+        left: Some(text_operand(text)),
+        right: Some(text_operand(pattern)),
+        diff,
+        index: None,
+        location,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn body() -> Value {
+        json!({"items": [{"id": 1, "tags": ["a"]}, {"id": 2, "tags": []}], "a.b": {"c": true}})
+    }
+
+    #[test]
+    fn definite_paths_give_the_value() {
+        assert_eq!(json_select(&body(), "$.items[1].id"), Ok(json!(2)));
+        assert_eq!(json_select(&body(), "$.items[-1].id"), Ok(json!(2)));
+        assert_eq!(json_select(&body(), "$['a.b'].c"), Ok(json!(true)));
+        assert_eq!(json_select(&body(), "$.items[0].tags"), Ok(json!(["a"])));
+        assert!(json_select(&body(), "$.items[5].id").is_err());
+        assert!(json_select(&body(), "/items/5").is_err());
+    }
+
+    #[test]
+    fn one_match_of_an_indefinite_path_is_an_array() {
+        assert_eq!(json_select(&json!({"items": [{"id": 1}]}), "$.items[*].id"), Ok(json!([1])));
+        assert_eq!(json_select(&body(), "$..c"), Ok(json!([true])));
+        assert_eq!(json_select(&body(), "$.items[?(@.id == 1)].id"), Ok(json!([1])));
+        assert_eq!(json_select(&body(), "$.items[0,5].id"), Ok(json!([1])));
+        assert_eq!(json_select(&body(), "$.items[1:].id"), Ok(json!([2])));
+        assert_eq!(json_select(&body(), "$.items[*].missing"), Ok(json!([])));
+    }
+
+    #[test]
+    fn json_path_assertion_on_one_match() {
+        let location = SourceLocation::new("src/lib.rs", 1, 1);
+        let body = json!({"items": [{"id": 1}]});
+        assert!(json_path_match(&body, "$.items[*].id", &json!([1]), "ids".into(), location.clone()).passed);
+        assert!(!json_path_match(&body, "$.items[*].id", &json!(1), "ids".into(), location).passed);
+    }
+}
@@ -8,8 +8,47 @@ pub static mut COMMAND: Vec<u8> = vec![];
 use lazy_static::lazy_static;
 use std::sync::{Arc,Mutex};
 use std::vec;
-use byteorder::{ByteOrder, LittleEndian};
 pub use byteorder;
+#[cfg(test)]
+extern crate wasm_mock_host_abi;
+pub mod host;
+pub use host::{HostClient, HostError, WapcHost};
+pub mod record;
+pub use record::{report_setup, AssertionRecord, SourceLocation, StepRecord};
+pub mod report;
+pub use report::{render_report, save_report, ReportFormat, REPORT, SUITE_NAME};
+pub mod http1x;
+pub use http1x::{percent_decode, percent_encode, query_string, with_query, Http1xBuilder};
+pub mod cases;
+pub use cases::{case_field, cases_from_csv, cases_from_json, cases_or_report, current_case, fill_case, fill_case_path, CASE_MAP};
+pub mod lifecycle;
+pub use lifecycle::{add_hook, end_suite, enter_index, fixture, leave_index, set_fixture, Hook, HookResult, FIXTURE_MAP, HOOK_MAP};
+pub mod flow;
+pub use flow::{capture_var, interpolate, interpolate_head, interpolate_request, interpolate_target, set_variable, variable, Extract, VARIABLES};
+pub mod schema;
+pub mod json_assert;
+pub mod mock;
+pub use mock::{pair_requests, remember_request, request_of, PAIRED_REQUEST_SET, RECEIVED_REQUEST_MAP};
+pub mod stub;
+pub use stub::{add_stub, find_stub, stub_http_req, stub_http_res, Stub, StubResponse, STUB_MAP};
+pub mod scenario;
+pub mod compare;
+pub use compare::{ab_scores, AbDiff, AbScore, Comparison, DiffPath, Difference, AB_SCORE_MAP};
+pub mod http;
+pub use http::HttpRequestBuilder;
+pub mod retry;
+pub use retry::{eventually, retry_until, Backoff, Retry, RetryError};
+pub mod har;
+pub use har::{add_har_stubs, export_har, import_har, stubs_from_har, Har, HarEntry, HarRequest, HarResponse};
+pub mod fault;
+pub use fault::{add_fault, fault_http_res, inject_fault, Fault, FAULT_MAP};
+pub mod replay;
+pub use replay::{replay_tcp, reset_replay, REPLAY_CURSOR_MAP};
+pub mod rng;
+pub use rng::{seed_rng, uuid_v4, SplitMix64};
+pub mod template;
+pub use template::{render_response, value_at};
+pub use scenario::{add_scenario, reset_scenario, reset_scenarios, scenario_state, set_scenario_state, Scenario, ScenarioStore, SCENARIO_MAP};
 lazy_static!{
     /// HashMap for storing WAPC HandlerSignatures. These will handler signatures will be registered when the host calls save_uid 
     pub static ref REGISTRY: Arc<Mutex<HashMap<String,fn(&[u8]) -> CallResult>>> = Arc::new(Mutex::new(HashMap::new()));
@@ -40,35 +79,59 @@ pub extern "C" fn wapc_init() {
         Ok(ignore_s.into_bytes())
     });
     register_function("add_functions",add_functions);
+    register_function("end_suite", |_|->CallResult{
+        end_suite();
+        Ok(vec![])
+    });
+    register_function("report", |msg:&[u8]|->CallResult{
+        end_suite();
+        let format = ReportFormat::parse(std::str::from_utf8(msg)?);
+        Ok(render_report(format).into_bytes())
+    });
+    register_function("reset_scenarios", |msg:&[u8]|->CallResult{
+        let name = std::str::from_utf8(msg)?;
+        if name.is_empty(){
+            reset_scenarios()?;
+        }else{
+            reset_scenario(name)?;
+        }
+        Ok(vec![])
+    });
     register_function("add_ws_functions",add_ws_functions);
     register_function("command", |msg:&[u8]|->CallResult{
         let index = foo_index() as i32;
+        enter_index(index);
         if let Some(i)= COMMAND_MAP.lock().unwrap().get(&index){
-            return Ok(i.clone().into_bytes())
+            return Ok(interpolate_target(i).into_bytes())
         }
         Ok(vec![])
     });
     register_function("request", |msg:&[u8]|->CallResult{
         let index = foo_index() as i32 ;
+        enter_index(index);
         if let Some(i)= REQUEST_MAP.lock().unwrap().get(&index){
-            let request = serde_json::to_string(&i).unwrap();
+            let request = serde_json::to_string(&interpolate_request(i)?).unwrap();
             return Ok(request.into_bytes())
         }
         Ok(vec![])
     });
     register_function("request_marshalling", |msg:&[u8]|->CallResult{
         let index = foo_index() as i32;
+        enter_index(index);
         if let Some(i)= REQUEST_MAR_MAP.lock().unwrap().get(&index){
-            return Ok(i.clone().into_bytes())
+            return Ok(interpolate_head(i)?.into_bytes())
         }
         Ok(vec![])
     });
     register_function("response_marshalling", |msg:&[u8]|->CallResult{
         let index = foo_index() as i32;
-        if let Some(i)= RESPONSE_MAR_MAP.lock().unwrap().get(&index){
-            return i(msg.clone());
-        }
-        return Ok(msg.to_vec())
+        let handler = RESPONSE_MAR_MAP.lock().unwrap().get(&index).cloned();
+        let res = match handler{
+            Some(i)=>i(msg.clone()),
+            None=>Ok(msg.to_vec()),
+        };
+        leave_index(index);
+        res
     });
 }
 fn do_nothing(msg:&[u8]) -> CallResult{
@@ -154,15 +217,10 @@ fn add_ws_functions(msg: &[u8]) -> CallResult{
 }
 /// Get index. Index increments per iteration of the loop defined in automation
 pub fn foo_index()->i64{
-    
-    let mut _uid = "";
-    unsafe {
-        _uid = std::str::from_utf8(&UID).unwrap();
-    }
-    let s = host_call(_uid, "foo", "get_index", b"").unwrap();
-    LittleEndian::read_u64(&s) as i64
+    WapcHost.get_index(&host::uid()).unwrap()
 }
-/// Assert equal macro
+/// Assert equal macro. Reports an [`AssertionRecord`] with both operands, captured as JSON when they
+/// implement `Serialize`, and their diff when the assertion fails.
 ///
 /// # Examples
 ///
@@ -180,21 +238,26 @@ macro_rules! foo_assert_eq {
     ($left:expr, $right:expr, $desc:expr) => {{
         match (&$left, &$right) {
             (left_val, right_val) => {
-                let mut uid = "";
-                unsafe {
-                    uid = std::str::from_utf8(&UID)?;
-                }
-                if !(*left_val == *right_val) {
-                    let s = format!(
+                use $crate::record::{CaptureDebug as _, CaptureJson as _};
+                let passed = *left_val == *right_val;
+                let message = if passed {
+                    format!("`{}` assertion passed: `{:?}`", $desc, &*left_val)
+                } else {
+                    format!(
                         "`{}` assertion failed: `(left == right)`
               left: `{:?}`, right: `{:?}",
                         $desc, &*left_val, &*right_val
-                    );
-                    host_call(uid, "foo", "assert_fail", s.as_bytes()).unwrap();
-                } else {
-                    let s = format!("`{}` assertion passed: `{:?}`", $desc, &*left_val);
-                    host_call(uid, "foo", "assert_pass", s.as_bytes()).unwrap();
-                }
+                    )
+                };
+                let record = $crate::record::AssertionRecord::compare(
+                    passed,
+                    format!("{}", $desc),
+                    message,
+                    (&$crate::record::Capture(left_val)).capture(),
+                    (&$crate::record::Capture(right_val)).capture(),
+                    $crate::record::SourceLocation::new(file!(), line!(), column!()),
+                );
+                $crate::record::report_assertion(record).unwrap();
             }
         }
     }};
@@ -204,29 +267,11 @@ macro_rules! foo_assert_eq {
 macro_rules! foo_assert_eq_toggle {
     ($left:expr, $right:expr, $desc:expr,$indexdbkey:expr) => {{
         if foo_indexdb_get!($indexdbkey, "nil") == "true" {
-            match (&$left, &$right) {
-                (left_val, right_val) => {
-                    let mut uid = "";
-                    unsafe {
-                        uid = std::str::from_utf8(&UID)?;
-                    }
-                    if !(*left_val == *right_val) {
-                        let s = format!(
-                            "`{}` assertion failed: `(left == right)`
-              left: `{:?}`, right: `{:?}",
-                            $desc, &*left_val, &*right_val
-                        );
-                        host_call(uid, "foo", "assert_fail", s.as_bytes()).unwrap();
-                    } else {
-                        let s = format!("`{}` assertion passed: `{:?}`", $desc, &*left_val);
-                        host_call(uid, "foo", "assert_pass", s.as_bytes()).unwrap();
-                    }
-                }
-            }
+            foo_assert_eq!($left, $right, $desc);
         }
     }};
 }
-/// Assert true/false macro
+/// Assert true/false macro. Reports an [`AssertionRecord`]
 ///
 /// # Arguments
 ///
@@ -235,18 +280,96 @@ macro_rules! foo_assert_eq_toggle {
 #[macro_export]
 macro_rules! foo_assert {
     ($cond:expr,$desc:expr) => {{
-        let mut uid = "";
-        unsafe {
-            uid = std::str::from_utf8(&UID)?;
-        }
-        if $cond {
-            host_call(uid, "foo", "assert_pass", $desc.as_bytes()).unwrap();
-        } else {
-            host_call(uid, "foo", "assert_fail", $desc.as_bytes()).unwrap();
-        }
+        let record = $crate::record::AssertionRecord::new(
+            $cond,
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_assertion(record).unwrap();
     }};
 }
-/// Automation step validation if the step passes or fails. It is usually used by other macros.
+/// Inclusive JSON assertion: every field of `expected` has to be in `actual`, extra fields are allowed.
+/// The failure carries the path diff
+///
+/// # Examples
+///
+/// ```ignore
+/// foo_assert_json_include!(res.body_json(), json!({"user": {"id": 1}}), "user id");
+/// ```
+#[macro_export]
+macro_rules! foo_assert_json_include {
+    ($actual:expr, $expected:expr, $desc:expr) => {{
+        let record = $crate::json_assert::json_match(
+            &$crate::json_assert::to_json(&$actual),
+            &$crate::json_assert::to_json(&$expected),
+            false,
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_assertion(record).unwrap();
+    }};
+}
+/// Strict JSON assertion, `actual` and `expected` have to be equal. The failure carries the path diff
+#[macro_export]
+macro_rules! foo_assert_json_eq {
+    ($actual:expr, $expected:expr, $desc:expr) => {{
+        let record = $crate::json_assert::json_match(
+            &$crate::json_assert::to_json(&$actual),
+            &$crate::json_assert::to_json(&$expected),
+            true,
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_assertion(record).unwrap();
+    }};
+}
+/// Asserts the value at a JSON pointer (`/data/id`) or JSONPath (`$.data.items[*].id`) of `actual`
+///
+/// # Examples
+///
+/// ```ignore
+/// foo_assert_json_path!(res.body_json(), "$.items[*].id", json!([1, 2]), "item ids");
+/// ```
+#[macro_export]
+macro_rules! foo_assert_json_path {
+    ($actual:expr, $path:expr, $expected:expr, $desc:expr) => {{
+        let record = $crate::json_assert::json_path_match(
+            &$crate::json_assert::to_json(&$actual),
+            &$path,
+            &$crate::json_assert::to_json(&$expected),
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_assertion(record).unwrap();
+    }};
+}
+/// Validates `actual` against a JSON Schema, the failure lists every schema error
+#[macro_export]
+macro_rules! foo_assert_json_schema {
+    ($actual:expr, $schema:expr, $desc:expr) => {{
+        let record = $crate::json_assert::json_schema_match(
+            &$crate::json_assert::to_json(&$actual),
+            &$crate::json_assert::to_json(&$schema),
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_assertion(record).unwrap();
+    }};
+}
+/// Asserts that the regular expression `pattern` matches somewhere in `text`, e.g. `res.HttpBodyRaw`
+#[macro_export]
+macro_rules! foo_assert_regex {
+    ($text:expr, $pattern:expr, $desc:expr) => {{
+        let record = $crate::json_assert::regex_match(
+            &$text,
+            &$pattern,
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_assertion(record).unwrap();
+    }};
+}
+/// Automation step validation if the step passes or fails. It is usually used by other macros. Reports a [`StepRecord`]
 ///
 /// # Arguments
 ///
@@ -255,15 +378,12 @@ macro_rules! foo_assert {
 #[macro_export]
 macro_rules! foo_step {
     ($cond:expr,$desc:expr) => {{
-        let mut uid = "";
-        unsafe {
-            uid = std::str::from_utf8(&UID)?;
-        }
-        if $cond {
-            host_call(uid, "foo", "step_pass", $desc.as_bytes()).unwrap();
-        } else {
-            host_call(uid, "foo", "step_fail", $desc.as_bytes()).unwrap();
-        }
+        let record = $crate::record::StepRecord::new(
+            $cond,
+            format!("{}", $desc),
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        );
+        $crate::record::report_step(record).unwrap();
     }};
 }
 /// Embedded version code used by wasm-mock-util
@@ -328,7 +448,7 @@ macro_rules! foo_rpc_request {
         let reqjson = serde_json::to_string(&$request.clone())?;
         let mut payload = vec![];
         $request.encode(&mut payload)?;
-        match host_call($command, "foo", "rpc_request", &*payload) {
+        match $crate::host::HostClient::rpc_request(&$crate::host::WapcHost, &$command, &*payload) {
             Ok(res) => {
                 let m = <$type>::decode(&*res)?;
                 let j = serde_json::to_string(&m)?;
@@ -343,105 +463,50 @@ macro_rules! foo_rpc_request {
                     false,
                     format!("RPC cmd:{} req:{} res:{}", $command, reqjson, e)
                 );
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
 }
 /// Macro that does external HTTP host call and returns json HTTP response body. Usually used as oracle of truth during assertion.
 ///
+/// The request is reported as a step and the reply decoded into the expected type; see [`http::send`] for the
+/// function-based client.
+///
 /// # Arguments
 ///
 /// * `addr` - true/false
 /// * `request` - Http1x string
-/// * `body` - Body in bytes
+/// * `body` - Body as a string
 /// * `proxy_url` - HTTP Proxy URL
 #[macro_export]
 macro_rules! foo_http_request {
-    ($addr:expr,$request:expr,$body:expr,$proxy_url:expr) => {{
-        let r = HttpRequest {
-            Http1x: $request.to_string(),
-            HttpBody: $body.as_bytes().to_vec(),
-            ProxyUrl: $proxy_url.to_string(),
-        };
-        let request = serde_json::to_string(&r)?;
-        match host_call($addr, "foo", "http_request", request.as_bytes()) {
-            Ok(res) => {
-                let j = std::str::from_utf8(&res)?;
-                foo_step!(true, format!("HTTP req:{} res:{}", request, j));
-                match serde_json::from_str(j) {
-                    Ok(res) => Ok(res),
-                    Err(e) => {
-                        let io_error: std::io::Error = e.into();
-                        let err_ref = io_error.into_inner().unwrap();
-                        Err(err_ref)
-                    }
-                }
-            }
-            Err(e) => {
-                foo_step!(false, format!("HTTP req:{} err:{}", request, e));
-                Err(e)
-            }
-        }
-    }};
+    ($addr:expr,$request:expr,$body:expr,$proxy_url:expr) => {
+        $crate::foo_http_request2!($addr, $request, $body.as_bytes().to_vec(), $proxy_url)
+    };
 }
+/// [`foo_http_request!`] with the body in bytes
 #[macro_export]
 macro_rules! foo_http_request2 {
-    ($addr:expr,$request:expr,$body:expr,$proxy_url:expr) => {{
-        let r = HttpRequest {
-            Http1x: $request.to_string(),
-            HttpBody: $body,
-            ProxyUrl: $proxy_url.to_string(),
-        };
-        let request = serde_json::to_string(&r)?;
-        match host_call($addr, "foo", "http_request", request.as_bytes()) {
-            Ok(res) => {
-                let j = std::str::from_utf8(&res)?;
-                foo_step!(true, format!("HTTP req:{} res:{}", request, j));
-                match serde_json::from_str(j) {
-                    Ok(res) => Ok(res),
-                    Err(e) => {
-                        let io_error: std::io::Error = e.into();
-                        let err_ref = io_error.into_inner().unwrap();
-                        Err(err_ref)
-                    }
-                }
-            }
-            Err(e) => {
-                foo_step!(false, format!("HTTP req:{} err:{}", request, e));
-                Err(e)
-            }
-        }
-    }};
+    ($addr:expr,$request:expr,$body:expr,$proxy_url:expr) => {
+        $crate::http::request_as(
+            &$addr,
+            $crate::HttpRequest {
+                Http1x: $request.to_string(),
+                HttpBody: $body,
+                ProxyUrl: $proxy_url.to_string(),
+            },
+            $crate::record::SourceLocation::new(file!(), line!(), column!()),
+        )
+        .map_err(Box::<dyn std::error::Error + Sync + Send>::from)
+    };
 }
+/// Same as [`foo_http_request!`]
 #[macro_export]
 macro_rules! foo_http_requestOld {
-    ($addr:expr,$request:expr,$body:expr,$proxy_url:expr) => {{
-        let r = HttpRequest {
-            Http1x: $request.to_string(),
-            HttpBody: $body.as_bytes().to_vec(),
-            ProxyUrl: $proxy_url.to_string(),
-        };
-        let request = serde_json::to_string(&r)?;
-        match host_call($addr, "foo", "http_request", request.as_bytes()) {
-            Ok(res) => {
-                let j = std::str::from_utf8(&res)?;
-                foo_step!(true, format!("HTTP req:{} res:{}", request, j));
-                match serde_json::from_str(j) {
-                    Ok(res) => Ok(res),
-                    Err(e) => {
-                        let io_error: std::io::Error = e.into();
-                        let err_ref = io_error.into_inner().unwrap();
-                        Err(err_ref)
-                    }
-                }
-            }
-            Err(e) => {
-                foo_step!(false, format!("HTTP req:{} err:{}", request, e));
-                Err(e)
-            }
-        }
-    }};
+    ($addr:expr,$request:expr,$body:expr,$proxy_url:expr) => {
+        $crate::foo_http_request!($addr, $request, $body, $proxy_url)
+    };
 }
 /// Macro that sends a tcp payload from mock server to remote connection
 /// # Arguments
@@ -451,18 +516,15 @@ macro_rules! foo_http_requestOld {
 #[macro_export]
 macro_rules! foo_tcp_request {
     ($addr:expr,$request:expr) => {{
-        use std::boxed::Box;
-        let mut buf = vec![];
-        $request.serialize(&mut Serializer::new(&mut buf))?;
-        match host_call($addr, "foo", "tcp_request", &buf) {
+        match $crate::host::HostClient::tcp_request_raw(&$crate::host::WapcHost, &$addr, &$request) {
             Ok(res) => {
-                let tcp_res: TcpReq = rmp_serde::from_read_ref(&res)?;
+                let tcp_res: $crate::TcpReq = $crate::tcp_foo_unmarshall(&res)?;
                 foo_step!(true, format!("TCP req:{} res:{}", $request.String, tcp_res.String));
                 Ok(res)
             }
             Err(e) => {
                 foo_step!(false, format!("TCP req:{} err:{}", $request.String, e));
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
@@ -475,18 +537,15 @@ macro_rules! foo_tcp_request {
 #[macro_export]
 macro_rules! foo_tcp_response {
     ($addr:expr,$request:expr) => {{
-        use std::boxed::Box;
-        let mut buf = vec![];
-        $request.serialize(&mut Serializer::new(&mut buf))?;
-        match host_call($addr, "foo", "tcp_response", &buf) {
+        match $crate::host::HostClient::tcp_response_raw(&$crate::host::WapcHost, &$addr, &$request) {
             Ok(res) => {
-                let tcp_res: TcpReq = rmp_serde::from_read_ref(&res)?;
+                let tcp_res: $crate::TcpReq = $crate::tcp_foo_unmarshall(&res)?;
                 foo_step!(true, format!("TCP res:{} res:{}", $request.String, tcp_res.String));
                 Ok(res)
             }
             Err(e) => {
                 foo_step!(false, format!("TCP req:{} err:{}", $request.String, e));
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
@@ -500,12 +559,7 @@ macro_rules! foo_tcp_response {
 #[macro_export]
 macro_rules! foo_redis {
     ($addr:expr,$method:expr,$key:expr) => {{
-        match host_call(
-            $addr,
-            "foo",
-            &*format!("redis_{}", $method),
-            $key.as_bytes(),
-        ) {
+        match $crate::host::HostClient::redis(&$crate::host::WapcHost, &$addr, &$method, &$key) {
             Ok(res) => {
                 let j = std::str::from_utf8(&res)?;
                 if $method == "delete" {
@@ -521,7 +575,7 @@ macro_rules! foo_redis {
                 } else {
                     foo_assert!(false, format!("REDIS {} {} {}", &$method, $key, e));
                 }
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
@@ -535,12 +589,7 @@ macro_rules! foo_redis {
 #[macro_export]
 macro_rules! foo_memcache {
     ($method:expr,$addr:expr,$key:expr) => {{
-        match host_call(
-            $addr,
-            "foo",
-            &*format!("memcache_{}", $method),
-            $key.as_bytes(),
-        ) {
+        match $crate::host::HostClient::memcache(&$crate::host::WapcHost, &$addr, &$method, &$key) {
             Ok(res) => {
                 let j = std::str::from_utf8(&res)?;
                 if $method == "delete" {
@@ -556,7 +605,7 @@ macro_rules! foo_memcache {
                 } else {
                     foo_assert!(false, format!("MEMCACHE {} {} {}", &$method, $key, e));
                 }
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
@@ -569,11 +618,11 @@ macro_rules! foo_memcache {
 #[macro_export]
 macro_rules! foo_indexdb_store {
     ($key:expr,$value:expr) => {{
-        match host_call($key, "foo", "indexdb_store", $value.as_bytes()) {
+        match $crate::host::HostClient::indexdb_store(&$crate::host::WapcHost, &$key, $value.as_bytes()) {
             Ok(res) => Ok(res),
             Err(e) => {
                 foo_step!(false, format!("IndexDbStore {} {}", $key, e));
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
@@ -590,13 +639,12 @@ macro_rules! foo_indexdb_store {
 #[macro_export]
 macro_rules! foo_indexdb_get {
     ($key:expr,$default:expr) => {{
-        match host_call($key, "foo", "indexdb_get", $default.as_bytes()) {
-            Ok(res) => {
-                let s = std::str::from_utf8(&res).unwrap().to_owned();
+        match $crate::host::HostClient::indexdb_get(&$crate::host::WapcHost, &$key, &$default) {
+            Ok(s) => {
                 foo_step!(true, format!("IndexDbGet {} {}", $key, s));
                 s
             }
-            Err(e) => {
+            Err(_) => {
                 foo_step!(false, format!("IndexDbGet {} default {}", $key, $default));
                 $default.to_owned()
             }
@@ -615,13 +663,12 @@ macro_rules! foo_indexdb_get {
 #[macro_export]
 macro_rules! foo_mysql {
     ($key:expr,$default:expr) => {{
-        match host_call($key, "foo", "mysql", $default.as_bytes()) {
-            Ok(res) => {
-                let s = std::str::from_utf8(&res).unwrap().to_owned();
+        match $crate::host::HostClient::mysql(&$crate::host::WapcHost, &$key, &$default) {
+            Ok(s) => {
                 foo_step!(true, format!("mysql {} {}", $key, s));
                 s
             }
-            Err(e) => {
+            Err(_) => {
                 foo_step!(false, format!("mysql {} default {}", $key, $default));
                 $default.to_owned()
             }
@@ -632,34 +679,21 @@ macro_rules! foo_mysql {
 #[macro_export]
 macro_rules! foo_index {
     () => {{
-        use byteorder::{ByteOrder, LittleEndian};
-        let mut uid = "";
-        unsafe {
-            uid = std::str::from_utf8(&UID).unwrap();
-        }
-        let s = host_call(uid, "foo", "get_index", b"")?;
-        LittleEndian::read_u64(&s) as i64
+        $crate::host::HostClient::get_index(&$crate::host::WapcHost, &$crate::host::uid())?
     }};
 }
 /// Macro that sleep for duration in milliseconds
 #[macro_export]
 macro_rules! foo_sleep {
     ($key:expr) => {{
-        use byteorder::{ByteOrder, LittleEndian};
-        let mut uid = "";
-        unsafe {
-            uid = std::str::from_utf8(&UID).unwrap();
-        }
-        let mut buf = [0; 8];
-        LittleEndian::write_u64(&mut buf, $key);
-        match host_call(uid, "foo", "sleep", &buf) {
+        match $crate::host::HostClient::sleep(&$crate::host::WapcHost, &$crate::host::uid(), $key) {
             Ok(res) => {
                 foo_step!(true, format!("Sleep {} milliseconds", $key));
                 Ok(res)
             }
             Err(e) => {
                 foo_step!(false, format!("Sleep {} milliseconds, error: {}", $key, e));
-                Err(e)
+                Err(Box::<dyn std::error::Error + Sync + Send>::from(e))
             }
         }
     }};
@@ -673,14 +707,8 @@ macro_rules! foo_sleep {
 #[macro_export]
 macro_rules! foo_save_file {
     ($payload:expr,$path:expr) => {{
-        match host_call($path, "foo", "savefile", $payload) {
-            Ok(res) => {
-                Ok(res)
-            }
-            Err(e) => {
-                Err(e)
-            }
-        }
+        $crate::host::HostClient::save_file(&$crate::host::WapcHost, &$path, $payload)
+            .map_err(Box::<dyn std::error::Error + Sync + Send>::from)
     }};
 }
 /// Only used to construct the dynamic websocket wasm
@@ -696,7 +724,8 @@ macro_rules! foo_websocket {
             fun = std::str::from_utf8(&COMMAND).unwrap();
         }
         let binding = format!("{}|{}", uid, fun);
-        host_call(&binding, "foo", "websocket", $payload)
+        $crate::host::HostClient::websocket(&$crate::host::WapcHost, &binding, $payload)
+            .map_err(Box::<dyn std::error::Error + Sync + Send>::from)
     }};
 }
 fn foo_websocket(payload:&[u8])->CallResult{
@@ -709,7 +738,7 @@ fn foo_websocket(payload:&[u8])->CallResult{
         _fun = std::str::from_utf8(&COMMAND).unwrap();
     }
     let binding = format!("{}|{}", _uid, _fun);
-    host_call(&binding, "foo", "websocket", payload)
+    Ok(WapcHost.websocket(&binding, payload)?)
 }
 /// Only used to construct the dynamic websocket wasm
 #[macro_export]
@@ -724,7 +753,8 @@ macro_rules! foo_websocket_req_json {
             fun = std::str::from_utf8(&COMMAND).unwrap();
         }
         let binding = format!("{}|{}_req_json", uid, fun);
-        host_call(&binding, "foo", "websocket", $payload)
+        $crate::host::HostClient::websocket(&$crate::host::WapcHost, &binding, $payload)
+            .map_err(Box::<dyn std::error::Error + Sync + Send>::from)
     }};
 }
 fn foo_websocket_req_json(payload:&[u8])->CallResult{
@@ -737,7 +767,7 @@ fn foo_websocket_req_json(payload:&[u8])->CallResult{
         _fun = std::str::from_utf8(&COMMAND).unwrap();
     }
     let binding = format!("{}|{}_req_json", _uid, _fun);
-    host_call(&binding, "foo", "websocket", payload)
+    Ok(WapcHost.websocket(&binding, payload)?)
 }
 fn foo_websocket_res_json(payload:&[u8])->CallResult{
     let mut _uid = "";
@@ -749,7 +779,7 @@ fn foo_websocket_res_json(payload:&[u8])->CallResult{
         _fun = std::str::from_utf8(&COMMAND).unwrap();
     }
     let binding = format!("{}|{}_res_json", _uid, _fun);
-    host_call(&binding, "foo", "websocket", payload)
+    Ok(WapcHost.websocket(&binding, payload)?)
 }
 /// Only used to construct the dynamic websocket wasm
 #[macro_export]
@@ -764,7 +794,8 @@ macro_rules! foo_websocket_res_json {
             fun = std::str::from_utf8(&COMMAND).unwrap();
         }
         let binding = format!("{}|{}_res_json", uid, fun);
-        host_call(&binding, "foo", "websocket", $payload)
+        $crate::host::HostClient::websocket(&$crate::host::WapcHost, &binding, $payload)
+            .map_err(Box::<dyn std::error::Error + Sync + Send>::from)
     }};
 }
 /// Only used to construct the dynamic websocket wasm
@@ -777,7 +808,8 @@ macro_rules! foo_websocket_call {
         }
         let index = foo_index!();
         let binding = format!("{}|{}|{}", uid, $key, index);
-        host_call(&binding, "foo", "websocket_call", $payload)
+        $crate::host::HostClient::websocket_call(&$crate::host::WapcHost, &binding, $payload)
+            .map_err(Box::<dyn std::error::Error + Sync + Send>::from)
     }};
 }
 /// Macro that compares between two http_headers
@@ -856,8 +888,7 @@ pub fn mock_register_function_tff(
 }
 /// Get current time stamp
 pub fn now() -> Result<i64, Box<dyn Error + Sync + Send>> {
-    let now_payload = host_call("default", "foo", "now", b"")?;
-    Ok(LittleEndian::read_u64(&now_payload) as i64)
+    Ok(WapcHost.now()?)
 }
 // /// Get index by uid
 // pub fn foo_index(uid: &str) -> Result<i64, Box<dyn Error + Sync + Send>> {
@@ -948,6 +979,9 @@ pub struct HttpResponse {
     pub HttpBody: serde_json::Value,
     #[serde(rename = "http_body_raw")]
     pub HttpBodyRaw: String,
+    /// Body that is not UTF-8 text, base64 encoded; `HttpBodyRaw` is empty then
+    #[serde(rename = "http_body_base64", default, skip_serializing_if = "String::is_empty")]
+    pub HttpBodyBase64: String,
     #[serde(rename = "status_code")]
     pub StatusCode: String,
     #[serde(rename = "error")]
@@ -955,6 +989,16 @@ pub struct HttpResponse {
     #[serde(rename = "http_req")]
     pub HttpReq: RequestReceivedInMock,
 }
+impl HttpResponse {
+    /// `HttpBody`, or `HttpBodyRaw` parsed as JSON when the mock server left `HttpBody` empty
+    pub fn body_json(&self) -> serde_json::Value {
+        if self.HttpBody.is_null() {
+            serde_json::from_str(&self.HttpBodyRaw).unwrap_or(serde_json::Value::Null)
+        } else {
+            self.HttpBody.clone()
+        }
+    }
+}
 /// Return type for guest call "request"
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug,Clone)]
@@ -993,6 +1037,9 @@ pub struct RequestReceivedInMock {
     pub HttpBody: serde_json::Value,
     #[serde(rename = "http_body_raw")]
     pub HttpBodyRaw: String,
+    /// Body that is not UTF-8 text, base64 encoded; `HttpBodyRaw` is empty then
+    #[serde(rename = "http_body_base64", default, skip_serializing_if = "String::is_empty")]
+    pub HttpBodyBase64: String,
     #[serde(rename = "http_proxy_url")]
     pub HttpProxyUrl: String,
     #[serde(rename = "http_path")]
@@ -1087,23 +1134,16 @@ pub struct MysqlRequest {
     pub QueryColumn: String,
 }
 /// Utility function of converting Http Request to HTTP 1x which is used in foo_http_request
+///
+/// The request line keeps the method, path (`/` when unset) and minor version of `r`.
 pub fn request_to_http1x(r: &httparse::Request)->String{
-  let mut owned_string: String = "".to_owned();
-  if let Some(m) = r.method{
-    owned_string.push_str(m);
-    owned_string.push_str(" / HTTP/1.1\r\n");
-  }
-  for x in 0..r.headers.len(){
-    let httparse::Header{name,value} = r.headers[x];
-    if name!=""{
-      owned_string.push_str(name);
-      owned_string.push_str(": ");
-      owned_string.push_str(std::str::from_utf8(value).unwrap_or("default"));
-      owned_string.push_str("\r\n");
-    }
+  let mut b = Http1xBuilder::new(r.method.unwrap_or("GET"))
+    .path(r.path.unwrap_or("/"))
+    .version(r.version.unwrap_or(1));
+  for h in r.headers.iter(){
+    b = b.header(h.name, std::str::from_utf8(h.value).unwrap_or("default"));
   }
-  owned_string.push_str("\r\n");
-  owned_string
+  b.build()
 }
 /// Utility function of converting Http Headers to string
 pub fn header_to_string(h: &httparse::Header)->String{
@@ -1111,39 +1151,48 @@ pub fn header_to_string(h: &httparse::Header)->String{
   owned_string.push_str(std::str::from_utf8(h.value).unwrap());
   owned_string
 }
-
+/// HTTP 1x of `method` on `/` with the JSON headers received by the mock server
 pub fn json_http1x_to_string(method:String,header: &HashMap<String, serde_json::Value>)->String{
-    let mut owned_string: String = "".to_owned();
-    owned_string.push_str(&method);
-    owned_string.push_str(" / HTTP/1.1\r\n");
+    json_http1x_to_target(method,"/",header)
+}
+/// HTTP 1x of `method` on `target` (path and query) with the JSON headers received by the mock server.
+/// A header value is either a string or a list of strings, each one sent as its own header line
+pub fn json_http1x_to_target(method:String,target:&str,header: &HashMap<String, serde_json::Value>)->String{
+    let mut b = Http1xBuilder::new(&method).path(target);
     for (name,v) in header{
-        if name!=""{
-            owned_string.push_str(name);
-            owned_string.push_str(": '");
-            owned_string.push_str(v.get(0).unwrap().as_str().unwrap());
-            owned_string.push_str("'\r\n");
-          }
+        match v{
+            serde_json::Value::Array(values)=>{
+                for value in values{
+                    if let Some(value) = value.as_str(){
+                        b = b.header(name,value);
+                    }
+                }
+            },
+            serde_json::Value::String(value)=>{
+                b = b.header(name,value);
+            },
+            _=>{}
+        }
     }
-    owned_string.push_str("\r\n");
-    owned_string
+    b.build()
 }
 pub fn http_request_from_mock(dst:String,req:RequestReceivedInMock)->CallResult{
-    
-    let http1x = json_http1x_to_string(req.HttpMethod,&req.HttpHeader.unwrap());
-    let p = format!("{}{}",dst,req.HttpPath);
-    //return Ok(vec![]);
-     if req.HttpBodyRaw!=""{
-        //return Ok(vec![]);
-        return foo_http_request2!(&p,http1x,vec![],req.HttpProxyUrl);
-    }else{
-        return foo_http_request2!(&p,http1x,vec![],req.HttpProxyUrl);
-
+    let mut query:Vec<(String,String)> = vec![];
+    if let Some(params) = &req.HttpParam{
+        let mut keys:Vec<&String> = params.keys().collect();
+        keys.sort();
+        for k in keys{
+            for v in &params[k]{
+                query.push((k.clone(),v.clone()));
+            }
+        }
     }
-    
+    let target = with_query(&req.HttpPath,&query_string(query));
+    let http1x = json_http1x_to_target(req.HttpMethod,&target,&req.HttpHeader.unwrap_or_default());
+    let p = format!("{}{}",dst,target);
+    let res: HttpResponse = foo_http_request!(&p,http1x,req.HttpBodyRaw,req.HttpProxyUrl)?;
+    Ok(serde_json::to_vec(&res)?)
 }
 pub fn cryto_md5sum(s:String)->Result<String, Box<dyn Error + Sync + Send>>{
-    let input = s.as_bytes();
-    let res = host_call("default","crypto","md5sum",input)?;
-    let s = std::str::from_utf8(&res)?;
-    Ok(s.to_owned())
+    Ok(WapcHost.md5sum(&s)?)
 }
\ No newline at end of file
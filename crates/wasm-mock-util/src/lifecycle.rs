@@ -0,0 +1,197 @@
+//! `before_all`/`before_each`/`after_each`/`after_all` hooks and named fixtures of `test_suite!`.
+//!
+//! The guest calls `command`, `request` and `request_marshalling` enter an automation index and
+//! `response_marshalling` leaves it. `before_all` runs when the first index is entered, `before_each` and
+//! `after_each` around every index and `after_all` when the suite ends: after the last index registered in
+//! `AT_COUNTER2`, or when the server calls `end_suite` or `report`, whichever comes first.
+//! A hook that returns an error is reported as a failed step.
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::record::{report_step, SourceLocation, StepRecord};
+use crate::AT_COUNTER2;
+
+/// Result of a hook, `?` can be used on the host call macros inside it
+pub type HookResult = Result<(), Box<dyn Error + Sync + Send>>;
+pub type HookFn = fn() -> HookResult;
+
+/// Point of the automation loop a hook runs at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hook {
+    BeforeAll,
+    BeforeEach,
+    AfterEach,
+    AfterAll,
+}
+
+impl Hook {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::BeforeAll => "before_all",
+            Hook::BeforeEach => "before_each",
+            Hook::AfterEach => "after_each",
+            Hook::AfterAll => "after_all",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Lifecycle {
+    started: bool,
+    current: Option<i32>,
+}
+
+lazy_static! {
+    /// Hooks in declaration order. Fixtures are `BeforeAll` hooks
+    pub static ref HOOK_MAP: Arc<Mutex<HashMap<Hook, Vec<HookFn>>>> = Arc::new(Mutex::new(HashMap::new()));
+    /// Values of the named fixtures
+    pub static ref FIXTURE_MAP: Arc<Mutex<HashMap<String, serde_json::Value>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref LIFECYCLE: Mutex<Lifecycle> = Mutex::new(Lifecycle::default());
+}
+
+pub fn add_hook(hook: Hook, f: HookFn) {
+    HOOK_MAP.lock().unwrap().entry(hook).or_default().push(f);
+}
+
+/// Runs the hooks of `hook`, reporting the ones that fail
+pub fn run_hooks(hook: Hook) {
+    let hooks = HOOK_MAP.lock().unwrap().get(&hook).cloned().unwrap_or_default();
+    for f in hooks {
+        if let Err(e) = f() {
+            let _ = report_step(StepRecord::new(
+                false,
+                format!("{} failed: {}", hook.name(), e),
+                SourceLocation::new(file!(), line!(), column!()),
+            ));
+        }
+    }
+}
+
+/// Runs `before_all` on the first index of a loop and `before_each` once per index
+pub fn enter_index(index: i32) {
+    let (all, each) = {
+        let mut l = LIFECYCLE.lock().unwrap();
+        let all = !l.started;
+        let each = l.current != Some(index);
+        l.started = true;
+        l.current = Some(index);
+        (all, each)
+    };
+    if all {
+        run_hooks(Hook::BeforeAll);
+    }
+    if each {
+        run_hooks(Hook::BeforeEach);
+    }
+}
+
+/// Runs `after_each`, and ends the suite when `index` is the last registered index
+pub fn leave_index(index: i32) {
+    {
+        let mut l = LIFECYCLE.lock().unwrap();
+        if l.current != Some(index) {
+            return;
+        }
+        l.current = None;
+    }
+    run_hooks(Hook::AfterEach);
+    let last = *AT_COUNTER2.lock().unwrap() - 1;
+    if index >= last {
+        end_suite();
+    }
+}
+
+/// Runs `after_each` for an index that was entered but never left, then `after_all` once if the suite started.
+/// The guest calls `end_suite` and `report` end the suite, so `after_all` runs even when the last index does
+/// not reach `response_marshalling`
+pub fn end_suite() {
+    let (each, all) = {
+        let mut l = LIFECYCLE.lock().unwrap();
+        let each = l.current.take().is_some();
+        let all = std::mem::take(&mut l.started);
+        (each, all)
+    };
+    if each {
+        run_hooks(Hook::AfterEach);
+    }
+    if all {
+        run_hooks(Hook::AfterAll);
+    }
+}
+
+/// Stores the fixture `name` for the rest of the suite
+pub fn set_fixture<T: Serialize>(name: &str, value: &T) -> HookResult {
+    let value = serde_json::to_value(value)?;
+    FIXTURE_MAP.lock().unwrap().insert(name.to_string(), value);
+    Ok(())
+}
+
+/// Value of the fixture `name`
+///
+/// # Examples
+///
+/// ```ignore
+/// test_suite!{
+///     fixture token { foo_indexdb_get!("token", "guest") }
+///     test http_get "/me" ([])(vec![])(res) {
+///         let token: String = fixture("token")?;
+///     }
+/// }
+/// ```
+pub fn fixture<T: DeserializeOwned>(name: &str) -> Result<T, Box<dyn Error + Sync + Send>> {
+    match FIXTURE_MAP.lock().unwrap().get(name) {
+        Some(v) => Ok(serde_json::from_value(v.clone())?),
+        None => Err(format!("fixture {} is not set", name).into()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    lazy_static! {
+        static ref LOG: Mutex<Vec<&'static str>> = Mutex::new(vec![]);
+    }
+
+    fn log(hook: Hook) -> HookFn {
+        match hook {
+            Hook::BeforeAll => || {
+                LOG.lock().unwrap().push("all");
+                Ok(())
+            },
+            Hook::BeforeEach => || {
+                LOG.lock().unwrap().push("each");
+                Ok(())
+            },
+            Hook::AfterEach => || {
+                LOG.lock().unwrap().push("after");
+                Ok(())
+            },
+            Hook::AfterAll => || {
+                LOG.lock().unwrap().push("done");
+                Ok(())
+            },
+        }
+    }
+
+    #[test]
+    fn suite_end_runs_the_pending_hooks_once() {
+        for hook in [Hook::BeforeAll, Hook::BeforeEach, Hook::AfterEach, Hook::AfterAll] {
+            add_hook(hook, log(hook));
+        }
+        *AT_COUNTER2.lock().unwrap() = 2;
+        enter_index(0);
+        enter_index(0);
+        leave_index(0);
+        // the last index never reaches response_marshalling
+        enter_index(1);
+        end_suite();
+        end_suite();
+        assert_eq!(*LOG.lock().unwrap(), ["all", "each", "after", "each", "after", "done"]);
+    }
+}
@@ -0,0 +1,146 @@
+//! Requests seen by the HTTP fiddler mocks.
+//!
+//! `{name}_http_modify_req` remembers the request it was called with, so `{name}_http_modify_res` can
+//! look at the request that produced the response even when the mock server leaves `http_req` empty.
+//! Only the mocks whose response handler looks at the request, marked with [`pair_requests`], keep them.
+//! The mock server does not tell which exchange a response belongs to, so a response without `http_req`
+//! is only paired with a remembered request when exactly one request of the mock is waiting for its
+//! response; with several waiting [`request_of`] fails instead of guessing.
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::{HttpResponse, RequestReceivedInMock};
+
+lazy_static! {
+    /// Requests waiting for their response, keyed by the name given to `modify`/`stub`
+    pub static ref RECEIVED_REQUEST_MAP: Arc<Mutex<HashMap<String, VecDeque<RequestReceivedInMock>>>> = Arc::new(Mutex::new(HashMap::new()));
+    /// Mocks whose responses are paired with their requests
+    pub static ref PAIRED_REQUEST_SET: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+}
+
+/// Requests kept per mock at most, the oldest is forgotten past it
+pub const MAX_WAITING_REQUESTS: usize = 64;
+
+/// Makes [`remember_request`] keep the requests of the mock `name`, for a response handler that looks at them
+pub fn pair_requests(name: &str) {
+    PAIRED_REQUEST_SET.lock().unwrap().insert(name.to_string());
+}
+
+/// Remembers `req` as a request of the mock `name` waiting for its response, when the mock was given to
+/// [`pair_requests`]
+pub fn remember_request(name: &str, req: &RequestReceivedInMock) {
+    if !PAIRED_REQUEST_SET.lock().unwrap().contains(name) {
+        return;
+    }
+    let mut map = RECEIVED_REQUEST_MAP.lock().unwrap();
+    let waiting = map.entry(name.to_string()).or_default();
+    if waiting.len() == MAX_WAITING_REQUESTS {
+        waiting.pop_front();
+    }
+    waiting.push_back(req.clone());
+}
+
+/// Request that produced `res` in the mock `name`: `res.HttpReq` when the mock server filled it in,
+/// the request remembered by `{name}_http_modify_req` otherwise, an empty request when none is waiting.
+/// Fails when several requests of the mock are waiting, since the response cannot be paired with one of them
+///
+/// # Examples
+///
+/// ```
+/// use wasm_mock_util::{pair_requests, remember_request, request_of, HttpResponse, RequestReceivedInMock};
+/// let mut req = RequestReceivedInMock::default();
+/// req.HttpPath = "/users/1".into();
+/// pair_requests("/doc");
+/// remember_request("/doc", &req);
+/// assert_eq!(request_of("/doc", &HttpResponse::default()).unwrap().HttpPath, "/users/1");
+/// remember_request("/doc", &req);
+/// remember_request("/doc", &req);
+/// assert!(request_of("/doc", &HttpResponse::default()).is_err());
+/// ```
+pub fn request_of(name: &str, res: &HttpResponse) -> Result<RequestReceivedInMock, Box<dyn Error + Sync + Send>> {
+    let mut map = RECEIVED_REQUEST_MAP.lock().unwrap();
+    let waiting = map.entry(name.to_string()).or_default();
+    if !res.HttpReq.HttpPath.is_empty() || !res.HttpReq.HttpMethod.is_empty() {
+        let req = &res.HttpReq;
+        if let Some(at) = waiting
+            .iter()
+            .position(|r| r.HttpPath == req.HttpPath && r.HttpMethod == req.HttpMethod)
+        {
+            waiting.remove(at);
+        }
+        return Ok(req.clone());
+    }
+    match waiting.len() {
+        0 | 1 => Ok(waiting.pop_front().unwrap_or_default()),
+        n => {
+            waiting.clear();
+            Err(format!(
+                "{}: {} requests are waiting and the mock server did not tell which one produced this response",
+                name, n
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str) -> RequestReceivedInMock {
+        RequestReceivedInMock {
+            HttpMethod: method.into(),
+            HttpPath: path.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn one_waiting_request_per_response() {
+        pair_requests("/one");
+        remember_request("/one", &request("GET", "/a"));
+        assert_eq!(request_of("/one", &HttpResponse::default()).unwrap().HttpPath, "/a");
+        assert_eq!(request_of("/one", &HttpResponse::default()).unwrap().HttpPath, "");
+        remember_request("/one", &request("GET", "/b"));
+        assert_eq!(request_of("/one", &HttpResponse::default()).unwrap().HttpPath, "/b");
+    }
+
+    #[test]
+    fn interleaved_requests_fail_loudly() {
+        pair_requests("/interleaved");
+        remember_request("/interleaved", &request("GET", "/a"));
+        remember_request("/interleaved", &request("POST", "/b"));
+        let e = request_of("/interleaved", &HttpResponse::default()).unwrap_err();
+        assert!(e.to_string().contains("2 requests are waiting"), "{}", e);
+        remember_request("/interleaved", &request("GET", "/c"));
+        assert_eq!(request_of("/interleaved", &HttpResponse::default()).unwrap().HttpPath, "/c");
+    }
+
+    #[test]
+    fn request_filled_in_by_the_server_wins() {
+        pair_requests("/filled");
+        remember_request("/filled", &request("GET", "/a"));
+        remember_request("/filled", &request("POST", "/b"));
+        let res = HttpResponse {
+            HttpReq: request("POST", "/b"),
+            ..Default::default()
+        };
+        assert_eq!(request_of("/filled", &res).unwrap().HttpMethod, "POST");
+        assert_eq!(request_of("/filled", &HttpResponse::default()).unwrap().HttpPath, "/a");
+    }
+
+    #[test]
+    fn only_paired_mocks_keep_their_requests() {
+        remember_request("/unpaired", &request("GET", "/a"));
+        assert!(!RECEIVED_REQUEST_MAP.lock().unwrap().contains_key("/unpaired"));
+        pair_requests("/capped");
+        for n in 0..MAX_WAITING_REQUESTS + 2 {
+            remember_request("/capped", &request("GET", &format!("/{}", n)));
+        }
+        let map = RECEIVED_REQUEST_MAP.lock().unwrap();
+        assert_eq!(map["/capped"].len(), MAX_WAITING_REQUESTS);
+        assert_eq!(map["/capped"][0].HttpPath, "/2");
+    }
+}
@@ -0,0 +1,251 @@
+//! Structured records of automation assertions and steps.
+//!
+//! `foo_assert_eq!`, `foo_assert!` and `foo_step!` build an [`AssertionRecord`] or [`StepRecord`] and send it
+//! as JSON to `assert_pass`/`assert_fail`/`step_pass`/`step_fail`, so the report can tell the description,
+//! operands, loop index and source location apart.
+use assert_json_diff::{assert_json_matches_no_panic, CompareMode, Config};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::{fmt, mem};
+
+use crate::host::{self, HostClient, HostError, WapcHost};
+use crate::report::{ReportEvent, REPORT};
+
+/// Place in the guest source where an assertion or step was made
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl SourceLocation {
+    pub fn new(file: &str, line: u32, column: u32) -> Self {
+        SourceLocation {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Operand of an assertion. `json` is set when the value implements `Serialize`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Operand {
+    pub debug: String,
+    pub json: Option<serde_json::Value>,
+}
+
+/// Outcome of `foo_assert_eq!` or `foo_assert!`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AssertionRecord {
+    pub passed: bool,
+    /// Name of the assertion given by the user
+    pub description: String,
+    /// Human readable summary, the text reported before records existed
+    pub message: String,
+    pub left: Option<Operand>,
+    pub right: Option<Operand>,
+    /// Path diff between `left` and `right` when both are JSON and differ
+    pub diff: Option<String>,
+    /// Automation loop index, `None` outside automation
+    pub index: Option<i64>,
+    pub location: SourceLocation,
+}
+
+impl AssertionRecord {
+    /// Record of a boolean assertion
+    pub fn new(passed: bool, description: String, location: SourceLocation) -> Self {
+        AssertionRecord {
+            passed,
+            message: description.clone(),
+            description,
+            location,
+            ..Default::default()
+        }
+    }
+    /// Record of an equality assertion between `left` and `right`
+    pub fn compare(
+        passed: bool,
+        description: String,
+        message: String,
+        left: Operand,
+        right: Operand,
+        location: SourceLocation,
+    ) -> Self {
+        let diff = match (&left.json, &right.json) {
+            (Some(l), Some(r)) if !passed => json_diff(l, r),
+            _ => None,
+        };
+        AssertionRecord {
+            passed,
+            description,
+            message,
+            left: Some(left),
+            right: Some(right),
+            diff,
+            index: None,
+            location,
+        }
+    }
+}
+
+/// Outcome of an automation step, reported by `foo_step!` and the host call macros
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct StepRecord {
+    pub passed: bool,
+    pub description: String,
+    /// Automation loop index, `None` outside automation
+    pub index: Option<i64>,
+    pub location: SourceLocation,
+}
+
+impl StepRecord {
+    pub fn new(passed: bool, description: String, location: SourceLocation) -> Self {
+        StepRecord {
+            passed,
+            description,
+            index: None,
+            location,
+        }
+    }
+}
+
+/// Strict JSON comparison, returns the path diff when `left` and `right` differ
+pub fn json_diff(left: &serde_json::Value, right: &serde_json::Value) -> Option<String> {
+    assert_json_matches_no_panic(left, right, Config::new(CompareMode::Strict)).err()
+}
+
+fn current_index() -> Option<i64> {
+    WapcHost.get_index(&host::uid()).ok()
+}
+
+/// Sends `record` to `assert_pass`/`assert_fail` and the guest's report, filling in the loop index.
+/// It is held back instead while a [`QuietReports`] is alive
+pub fn report_assertion(mut record: AssertionRecord) -> Result<AssertionRecord, HostError> {
+    if held(|| ReportEvent::Assertion(Box::new(record.clone()))) {
+        return Ok(record);
+    }
+    if record.index.is_none() {
+        record.index = current_index();
+    }
+    REPORT.lock().unwrap().push(ReportEvent::Assertion(Box::new(record.clone())));
+    WapcHost.assert_record(&host::uid(), &record)?;
+    Ok(record)
+}
+
+thread_local! {
+    /// Records held back by the live [`QuietReports`], the innermost last
+    static HELD: RefCell<Vec<Vec<ReportEvent>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps the event for the innermost [`QuietReports`], false when none is alive
+fn held(event: impl FnOnce() -> ReportEvent) -> bool {
+    HELD.with(|held| match held.borrow_mut().last_mut() {
+        Some(events) => {
+            events.push(event());
+            true
+        }
+        None => false,
+    })
+}
+
+/// Holds back what [`report_step`] and [`report_assertion`] report until it is dropped, which discards it.
+/// A retry holds back each attempt, then reports the assertions of the last one with [`QuietReports::assertions`]
+#[must_use]
+pub struct QuietReports(());
+
+impl QuietReports {
+    pub fn new() -> Self {
+        HELD.with(|held| held.borrow_mut().push(vec![]));
+        QuietReports(())
+    }
+    /// Assertions held back so far, the steps are discarded
+    pub fn assertions(self) -> Vec<AssertionRecord> {
+        let events = HELD.with(|held| held.borrow_mut().last_mut().map(mem::take).unwrap_or_default());
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                ReportEvent::Assertion(record) => Some(*record),
+                ReportEvent::Step(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl Default for QuietReports {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for QuietReports {
+    fn drop(&mut self) {
+        HELD.with(|held| held.borrow_mut().pop());
+    }
+}
+
+/// Sends `record` to `step_pass`/`step_fail` and the guest's report, filling in the loop index.
+/// It is held back instead while a [`QuietReports`] is alive
+pub fn report_step(mut record: StepRecord) -> Result<StepRecord, HostError> {
+    if held(|| ReportEvent::Step(record.clone())) {
+        return Ok(record);
+    }
+    if record.index.is_none() {
+        record.index = current_index();
+    }
+    REPORT.lock().unwrap().push(ReportEvent::Step(record.clone()));
+    WapcHost.step_record(&host::uid(), &record)?;
+    Ok(record)
+}
+
+/// Reports `result` as a failed step when it is an error, for the setup the suite macros do in `_start`
+/// where there is no caller to return it to
+pub fn report_setup<E: fmt::Display>(what: &str, result: Result<(), E>) {
+    if let Err(e) = result {
+        let _ = report_step(StepRecord::new(
+            false,
+            format!("{}: {}", what, e),
+            SourceLocation::new(file!(), line!(), column!()),
+        ));
+    }
+}
+
+/// Wraps an assertion operand so the macros can capture it as JSON when it implements `Serialize`
+/// and as `Debug` text otherwise: `(&Capture(v)).capture()`.
+#[doc(hidden)]
+pub struct Capture<'a, T: ?Sized>(pub &'a T);
+
+#[doc(hidden)]
+pub trait CaptureJson {
+    fn capture(&self) -> Operand;
+}
+
+impl<'a, T: Serialize + fmt::Debug + ?Sized> CaptureJson for Capture<'a, T> {
+    fn capture(&self) -> Operand {
+        Operand {
+            debug: format!("{:?}", self.0),
+            json: serde_json::to_value(self.0).ok(),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub trait CaptureDebug {
+    fn capture(&self) -> Operand;
+}
+
+impl<'a, T: fmt::Debug + ?Sized> CaptureDebug for &Capture<'a, T> {
+    fn capture(&self) -> Operand {
+        Operand {
+            debug: format!("{:?}", self.0),
+            json: None,
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Replay of recorded TCP responses, used by the mock suites generated by `wasm-mock-replay`.
+//!
+//! Every `{name}_tcp_modify_res` call answers with the next recorded item of the mock. Once the recording
+//! is exhausted the response of the remote connection is forwarded unchanged.
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::{TcpItem, TcpPayload};
+
+lazy_static! {
+    /// Number of recorded items already replayed by each TCP mock
+    pub static ref REPLAY_CURSOR_MAP: Arc<Mutex<HashMap<String, usize>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Next item of `recorded` (base64 payload, readable text) for the TCP mock `name`, addressed like `payload`
+pub fn replay_tcp(name: &str, payload: &TcpPayload, recorded: &[(&str, &str)]) -> Result<Vec<TcpItem>, Box<dyn Error + Sync + Send>> {
+    let mut map = REPLAY_CURSOR_MAP.lock().unwrap();
+    let cursor = map.entry(name.to_string()).or_insert(0);
+    let item = recorded.get(*cursor).map(|(encoded, text)| TcpItem {
+        Payload: encoded.to_string(),
+        String: text.to_string(),
+        Id: String::new(),
+        Laddr: payload.Laddr.clone(),
+        Raddr: payload.Raddr.clone(),
+    });
+    *cursor += 1;
+    Ok(item.into_iter().collect())
+}
+
+/// Replays the recording of the TCP mock `name` from its first item again
+pub fn reset_replay(name: &str) {
+    REPLAY_CURSOR_MAP.lock().unwrap().remove(name);
+}
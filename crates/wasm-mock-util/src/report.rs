@@ -0,0 +1,341 @@
+//! Automation results collected inside the guest and exported as JUnit XML or TAP.
+//!
+//! Every [`AssertionRecord`] and [`StepRecord`] reported by `foo_assert*`/`foo_step!` is kept in [`REPORT`].
+//! Events are grouped into one test case per automation index, i.e. per `test` item of `test_suite!` and
+//! per `foo_index()` iteration, named after the item's entry in `COMMAND_MAP`. The report is returned by
+//! the guest call `report` (payload `junit` or `tap`) or written with [`save_report`].
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::host::{HostClient, HostError, WapcHost};
+use crate::record::{AssertionRecord, StepRecord};
+use crate::COMMAND_MAP;
+
+lazy_static! {
+    /// Events reported so far by this guest
+    pub static ref REPORT: Arc<Mutex<ReportBuilder>> = Arc::new(Mutex::new(ReportBuilder::default()));
+    /// Name of the suite, set by `test_suite!`
+    pub static ref SUITE_NAME: Arc<Mutex<String>> = Arc::new(Mutex::new(String::from("wasm_mock")));
+}
+
+/// Output format of [`ReportBuilder::render`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    JUnit,
+    Tap,
+}
+
+impl ReportFormat {
+    /// Parses `junit`/`xml` or `tap`, defaulting to JUnit
+    pub fn parse(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "tap" => ReportFormat::Tap,
+            _ => ReportFormat::JUnit,
+        }
+    }
+}
+
+/// A pass/fail event reported by the assertion macros
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportEvent {
+    Assertion(Box<AssertionRecord>),
+    Step(StepRecord),
+}
+
+impl ReportEvent {
+    pub fn passed(&self) -> bool {
+        match self {
+            ReportEvent::Assertion(a) => a.passed,
+            ReportEvent::Step(s) => s.passed,
+        }
+    }
+    pub fn index(&self) -> Option<i64> {
+        match self {
+            ReportEvent::Assertion(a) => a.index,
+            ReportEvent::Step(s) => s.index,
+        }
+    }
+    fn kind(&self) -> &'static str {
+        match self {
+            ReportEvent::Assertion(_) => "assert",
+            ReportEvent::Step(_) => "step",
+        }
+    }
+    fn summary(&self) -> &str {
+        match self {
+            ReportEvent::Assertion(a) => &a.message,
+            ReportEvent::Step(s) => &s.description,
+        }
+    }
+    fn detail(&self) -> String {
+        match self {
+            ReportEvent::Assertion(a) => {
+                let mut s = format!("{} ({})", a.message, a.location);
+                if let Some(diff) = &a.diff {
+                    s.push('\n');
+                    s.push_str(diff);
+                }
+                s
+            }
+            ReportEvent::Step(st) => format!("{} ({})", st.description, st.location),
+        }
+    }
+}
+
+/// Events of one automation index
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    /// Automation index, `None` for events reported outside automation
+    pub index: Option<i64>,
+    pub name: String,
+    pub events: Vec<ReportEvent>,
+}
+
+impl TestCase {
+    pub fn passed(&self) -> bool {
+        self.events.iter().all(|e| e.passed())
+    }
+    pub fn failures(&self) -> impl Iterator<Item = &ReportEvent> {
+        self.events.iter().filter(|e| !e.passed())
+    }
+}
+
+/// Collects report events and renders them
+#[derive(Debug, Clone, Default)]
+pub struct ReportBuilder {
+    events: Vec<ReportEvent>,
+}
+
+impl ReportBuilder {
+    pub fn push(&mut self, event: ReportEvent) {
+        self.events.push(event);
+    }
+    pub fn events(&self) -> &[ReportEvent] {
+        &self.events
+    }
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+    /// Events grouped per automation index, in index order
+    pub fn test_cases(&self) -> Vec<TestCase> {
+        let mut groups: BTreeMap<Option<i64>, Vec<ReportEvent>> = BTreeMap::new();
+        for e in &self.events {
+            groups.entry(e.index()).or_default().push(e.clone());
+        }
+        let commands = COMMAND_MAP.lock().unwrap();
+        groups
+            .into_iter()
+            .map(|(index, events)| {
+                let name = match index {
+                    Some(i) => match commands.get(&(i as i32)) {
+                        Some(command) => format!("[{}] {}", i, command),
+                        None => format!("[{}]", i),
+                    },
+                    None => String::from("setup"),
+                };
+                TestCase { index, name, events }
+            })
+            .collect()
+    }
+    pub fn render(&self, suite: &str, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::JUnit => self.to_junit(suite),
+            ReportFormat::Tap => self.to_tap(),
+        }
+    }
+    /// JUnit XML with one `testcase` per automation index
+    pub fn to_junit(&self, suite: &str) -> String {
+        let cases = self.test_cases();
+        let failures = cases.iter().filter(|c| !c.passed()).count();
+        let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(s, "<testsuites tests=\"{}\" failures=\"{}\">", cases.len(), failures);
+        let _ = writeln!(
+            s,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            xml_escape(suite),
+            cases.len(),
+            failures
+        );
+        for case in &cases {
+            let _ = writeln!(
+                s,
+                "    <testcase name=\"{}\" classname=\"{}\">",
+                xml_escape(&case.name),
+                xml_escape(suite)
+            );
+            for f in case.failures() {
+                let _ = writeln!(
+                    s,
+                    "      <failure message=\"{}\" type=\"{}\">{}</failure>",
+                    xml_escape(f.summary()),
+                    f.kind(),
+                    xml_escape(&f.detail())
+                );
+            }
+            let mut out = String::new();
+            for e in &case.events {
+                let _ = writeln!(out, "{} {} {}", if e.passed() { "PASS" } else { "FAIL" }, e.kind(), e.detail());
+            }
+            let _ = writeln!(s, "      <system-out>{}</system-out>", xml_escape(&out));
+            s.push_str("    </testcase>\n");
+        }
+        s.push_str("  </testsuite>\n</testsuites>\n");
+        s
+    }
+    /// TAP version 13 with one test point per automation index
+    pub fn to_tap(&self) -> String {
+        let cases = self.test_cases();
+        let mut s = String::from("TAP version 13\n");
+        let _ = writeln!(s, "1..{}", cases.len());
+        for (n, case) in cases.iter().enumerate() {
+            let status = if case.passed() { "ok" } else { "not ok" };
+            let _ = writeln!(s, "{} {} - {}", status, n + 1, case.name.replace('#', "\\#"));
+            for e in &case.events {
+                let _ = writeln!(s, "# {} {} {}", if e.passed() { "PASS" } else { "FAIL" }, e.kind(), e.summary().replace('\n', " "));
+            }
+            if !case.passed() {
+                s.push_str("  ---\n  failures:\n");
+                for f in case.failures() {
+                    s.push_str("    - |\n");
+                    for line in f.detail().lines() {
+                        let _ = writeln!(s, "      {}", line);
+                    }
+                }
+                s.push_str("  ...\n");
+            }
+        }
+        s
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if (c as u32) < 0x20 && c != '\n' && c != '\r' && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Report of this guest in `format`
+pub fn render_report(format: ReportFormat) -> String {
+    let suite = SUITE_NAME.lock().unwrap().clone();
+    REPORT.lock().unwrap().render(&suite, format)
+}
+
+/// Writes the report into the file `path` inside the mock server
+pub fn save_report(format: ReportFormat, path: &str) -> Result<Vec<u8>, HostError> {
+    WapcHost.save_file(path, render_report(format).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::SourceLocation;
+
+    fn step(passed: bool, description: &str, index: Option<i64>) -> ReportEvent {
+        let mut record = StepRecord::new(passed, description.to_string(), SourceLocation::new("src/lib.rs", 7, 5));
+        record.index = index;
+        ReportEvent::Step(record)
+    }
+
+    fn assertion(passed: bool, message: &str, index: Option<i64>) -> ReportEvent {
+        let mut record = AssertionRecord::new(passed, message.to_string(), SourceLocation::new("src/lib.rs", 9, 1));
+        record.index = index;
+        record.diff = (!passed).then(|| String::from("json atom at path \".id\" is missing"));
+        ReportEvent::Assertion(Box::new(record))
+    }
+
+    /// Indexes 9100.. are not used by other tests sharing `COMMAND_MAP`
+    fn builder() -> ReportBuilder {
+        COMMAND_MAP.lock().unwrap().insert(9101, String::from("get <user> & \"id\""));
+        let mut report = ReportBuilder::default();
+        report.push(step(true, "login", None));
+        report.push(step(true, "GET /users", Some(9101)));
+        report.push(assertion(false, "status <400> & \"ok\"", Some(9101)));
+        report.push(assertion(true, "body # 'x'", Some(9102)));
+        report
+    }
+
+    #[test]
+    fn xml_escape_markup_and_control_characters() {
+        assert_eq!(xml_escape(r#"<a href="x">&'</a>"#), "&lt;a href=&quot;x&quot;&gt;&amp;&apos;&lt;/a&gt;");
+        assert_eq!(xml_escape("a\u{1}\u{1b}b\n\tc\r"), "ab\n\tc\r");
+        assert_eq!(xml_escape("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn junit_fixture() {
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="3" failures="1">
+  <testsuite name="suite &lt;a&gt;" tests="3" failures="1">
+    <testcase name="setup" classname="suite &lt;a&gt;">
+      <system-out>PASS step login (src/lib.rs:7:5)
+</system-out>
+    </testcase>
+    <testcase name="[9101] get &lt;user&gt; &amp; &quot;id&quot;" classname="suite &lt;a&gt;">
+      <failure message="status &lt;400&gt; &amp; &quot;ok&quot;" type="assert">status &lt;400&gt; &amp; &quot;ok&quot; (src/lib.rs:9:1)
+json atom at path &quot;.id&quot; is missing</failure>
+      <system-out>PASS step GET /users (src/lib.rs:7:5)
+FAIL assert status &lt;400&gt; &amp; &quot;ok&quot; (src/lib.rs:9:1)
+json atom at path &quot;.id&quot; is missing
+</system-out>
+    </testcase>
+    <testcase name="[9102]" classname="suite &lt;a&gt;">
+      <system-out>PASS assert body # &apos;x&apos; (src/lib.rs:9:1)
+</system-out>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        assert_eq!(builder().to_junit("suite <a>"), expected);
+    }
+
+    #[test]
+    fn tap_fixture() {
+        let expected = r#"TAP version 13
+1..3
+ok 1 - setup
+# PASS step login
+not ok 2 - [9101] get <user> & "id"
+# PASS step GET /users
+# FAIL assert status <400> & "ok"
+  ---
+  failures:
+    - |
+      status <400> & "ok" (src/lib.rs:9:1)
+      json atom at path ".id" is missing
+  ...
+ok 3 - [9102]
+# PASS assert body # 'x'
+"#;
+        assert_eq!(builder().to_tap(), expected);
+    }
+
+    #[test]
+    fn tap_escapes_hash_in_names() {
+        COMMAND_MAP.lock().unwrap().insert(9103, String::from("issue #12"));
+        let mut report = ReportBuilder::default();
+        report.push(step(true, "a\nb", Some(9103)));
+        assert_eq!(report.to_tap(), "TAP version 13\n1..1\nok 1 - [9103] issue \\#12\n# PASS step a b\n");
+    }
+
+    #[test]
+    fn empty_report() {
+        let report = ReportBuilder::default();
+        assert_eq!(
+            report.render("s", ReportFormat::parse("JUnit")),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"0\" failures=\"0\">\n  <testsuite name=\"s\" tests=\"0\" failures=\"0\">\n  </testsuite>\n</testsuites>\n"
+        );
+        assert_eq!(report.render("s", ReportFormat::parse(" tap ")), "TAP version 13\n1..0\n");
+    }
+}
@@ -0,0 +1,305 @@
+//! Polling of eventually consistent backends.
+//!
+//! [`Retry`] calls an attempt until it succeeds and its result satisfies a condition, sleeping between
+//! attempts with the host `sleep` and giving up once the deadline, measured with the host `now`, has passed.
+//! The steps reported by the attempts are dropped, and so are their `foo_assert*` results but those of the
+//! last attempt; the retry reports a single step with the number of attempts and the outcome.
+//!
+//! ```ignore
+//! let job = retry_until(
+//!     "job done",
+//!     30_000,
+//!     || http::send(HttpRequestBuilder::get(&format!("http://localhost:3000/job/{}", id)))?.json::<Value>(),
+//!     |job| job["status"] == "done",
+//! )?;
+//! ```
+use std::fmt;
+use std::panic::Location;
+
+use crate::host::{self, HostClient, HostError, WapcHost};
+use crate::record::{report_assertion, report_step, QuietReports, SourceLocation, StepRecord};
+
+/// Delay between attempts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backoff {
+    /// Same delay after every attempt
+    Fixed(u64),
+    /// `initial`, then `step` more after each attempt
+    Linear { initial: u64, step: u64 },
+    /// `initial` multiplied by `factor` after each attempt, up to `max`
+    Exponential { initial: u64, factor: f64, max: u64 },
+}
+
+impl Backoff {
+    pub fn fixed(millis: u64) -> Self {
+        Backoff::Fixed(millis)
+    }
+    pub fn linear(initial: u64, step: u64) -> Self {
+        Backoff::Linear { initial, step }
+    }
+    pub fn exponential(initial: u64, factor: f64, max: u64) -> Self {
+        Backoff::Exponential { initial, factor, max }
+    }
+    /// Delay in milliseconds after the attempt `attempt`, counted from 1
+    pub fn delay(&self, attempt: u32) -> u64 {
+        let n = attempt.saturating_sub(1);
+        match *self {
+            Backoff::Fixed(millis) => millis,
+            Backoff::Linear { initial, step } => initial.saturating_add(step.saturating_mul(n as u64)),
+            Backoff::Exponential { initial, factor, max } => {
+                let delay = initial as f64 * factor.powi(n.min(i32::MAX as u32) as i32);
+                if delay.is_finite() {
+                    (delay as u64).min(max)
+                } else {
+                    max
+                }
+            }
+        }
+    }
+}
+
+/// Retry that gave up
+#[derive(Debug)]
+pub enum RetryError {
+    /// The deadline passed or the attempts ran out; `last` tells why the last attempt did not succeed
+    GaveUp { attempts: u32, elapsed: u64, last: String },
+    /// The host clock or sleep failed
+    Host(HostError),
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryError::GaveUp { attempts, elapsed, last } => {
+                write!(f, "gave up after {} attempts in {} ms: {}", attempts, elapsed, last)
+            }
+            RetryError::Host(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RetryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RetryError::Host(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<HostError> for RetryError {
+    fn from(e: HostError) -> Self {
+        RetryError::Host(e)
+    }
+}
+
+/// Retry policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct Retry {
+    pub description: String,
+    /// Overall deadline in milliseconds
+    pub timeout: u64,
+    pub backoff: Backoff,
+    pub max_attempts: Option<u32>,
+}
+
+impl Retry {
+    /// Retries for 30 seconds, every 500 ms
+    pub fn new(description: &str) -> Self {
+        Retry {
+            description: description.to_string(),
+            timeout: 30_000,
+            backoff: Backoff::Fixed(500),
+            max_attempts: None,
+        }
+    }
+    pub fn timeout(mut self, millis: u64) -> Self {
+        self.timeout = millis;
+        self
+    }
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Calls `attempt` until it returns `Ok` with a value satisfying `done`, and reports one step
+    #[track_caller]
+    pub fn until<T, E, F, P>(&self, attempt: F, done: P) -> Result<T, RetryError>
+    where
+        E: fmt::Display,
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&T) -> bool,
+    {
+        let caller = Location::caller();
+        let location = SourceLocation::new(caller.file(), caller.line(), caller.column());
+        let result = self.poll(&WapcHost, attempt, done);
+        let description = match &result {
+            Ok((_, attempts, elapsed)) => format!(
+                "{}: done after {} attempt{} in {} ms",
+                self.description,
+                attempts,
+                if *attempts == 1 { "" } else { "s" },
+                elapsed
+            ),
+            Err(e) => format!("{}: {}", self.description, e),
+        };
+        report_step(StepRecord::new(result.is_ok(), description, location))?;
+        result.map(|(value, _, _)| value)
+    }
+
+    /// Calls `attempt` until it returns `Ok`, and reports one step
+    #[track_caller]
+    pub fn run<T, E, F>(&self, attempt: F) -> Result<T, RetryError>
+    where
+        E: fmt::Display,
+        F: FnMut() -> Result<T, E>,
+    {
+        self.until(attempt, |_| true)
+    }
+
+    /// Attempts until one is done or the retry gives up, with the number of attempts and the elapsed time.
+    /// Only the assertions of the last attempt are reported.
+    fn poll<H, T, E, F, P>(&self, host: &H, mut attempt: F, done: P) -> Result<(T, u32, u64), RetryError>
+    where
+        H: HostClient,
+        E: fmt::Display,
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&T) -> bool,
+    {
+        let start = host.now()?;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let quiet = QuietReports::new();
+            let outcome = attempt();
+            let elapsed = host.now()?.saturating_sub(start).max(0) as u64;
+            let last = match outcome {
+                Ok(value) if done(&value) => {
+                    report_held(quiet)?;
+                    return Ok((value, attempts, elapsed));
+                }
+                Ok(_) => String::from("condition not met"),
+                Err(e) => e.to_string(),
+            };
+            let out_of_attempts = self.max_attempts.is_some_and(|max| attempts >= max);
+            if elapsed >= self.timeout || out_of_attempts {
+                report_held(quiet)?;
+                return Err(RetryError::GaveUp { attempts, elapsed, last });
+            }
+            drop(quiet);
+            let delay = self.backoff.delay(attempts).min(self.timeout - elapsed);
+            host.sleep(&host::uid(), delay)?;
+        }
+    }
+}
+
+/// Reports the assertions held back by `quiet`
+fn report_held(quiet: QuietReports) -> Result<(), HostError> {
+    quiet.assertions().into_iter().try_for_each(|record| report_assertion(record).map(|_| ()))
+}
+
+/// Calls `attempt` until its value satisfies `done`, for at most `timeout` milliseconds
+#[track_caller]
+pub fn retry_until<T, E, F, P>(description: &str, timeout: u64, attempt: F, done: P) -> Result<T, RetryError>
+where
+    E: fmt::Display,
+    F: FnMut() -> Result<T, E>,
+    P: Fn(&T) -> bool,
+{
+    Retry::new(description).timeout(timeout).until(attempt, done)
+}
+
+/// Calls `attempt` until it succeeds, for at most `timeout` milliseconds
+#[track_caller]
+pub fn eventually<T, E, F>(description: &str, timeout: u64, attempt: F) -> Result<T, RetryError>
+where
+    E: fmt::Display,
+    F: FnMut() -> Result<T, E>,
+{
+    Retry::new(description).timeout(timeout).run(attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_mock_host_abi::MockHost;
+    use crate::record::AssertionRecord;
+    use crate::report::{ReportEvent, REPORT};
+
+    #[test]
+    fn backoff_delays() {
+        assert_eq!((1..=3).map(|n| Backoff::fixed(250).delay(n)).collect::<Vec<_>>(), [250, 250, 250]);
+        assert_eq!((1..=3).map(|n| Backoff::linear(100, 50).delay(n)).collect::<Vec<_>>(), [100, 150, 200]);
+        let exponential = Backoff::exponential(100, 2.0, 1_000);
+        assert_eq!((1..=6).map(|n| exponential.delay(n)).collect::<Vec<_>>(), [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(exponential.delay(0), 100);
+        assert_eq!(exponential.delay(u32::MAX), 1_000);
+        assert_eq!(Backoff::linear(u64::MAX, 1).delay(3), u64::MAX);
+    }
+
+    #[test]
+    fn gives_up_at_the_deadline() {
+        let host = MockHost::new();
+        let retry = Retry::new("down").timeout(1_000).backoff(Backoff::fixed(300));
+        match retry.poll(&WapcHost, || Err::<(), _>("connection refused"), |_| true) {
+            Err(RetryError::GaveUp { attempts, elapsed, last }) => {
+                assert_eq!((attempts, elapsed, last.as_str()), (5, 1_000, "connection refused"));
+            }
+            other => panic!("{:?}", other),
+        }
+        // the last sleep stops at the deadline
+        assert_eq!(host.now(), 1_000);
+        assert_eq!(host.calls_to("sleep").len(), 4);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        MockHost::new();
+        let retry = Retry::new("never").backoff(Backoff::linear(100, 100)).max_attempts(3);
+        let e = retry.poll(&WapcHost, || Ok::<_, String>(0), |n| *n > 0).unwrap_err();
+        assert_eq!(e.to_string(), "gave up after 3 attempts in 300 ms: condition not met");
+    }
+
+    #[test]
+    fn done_once_the_condition_holds() {
+        MockHost::new();
+        let retry = Retry::new("count").backoff(Backoff::exponential(100, 2.0, 1_000));
+        let mut n = 0;
+        let attempt = || -> Result<u32, String> {
+            n += 1;
+            Ok(n)
+        };
+        assert_eq!(retry.poll(&WapcHost, attempt, |n| *n >= 3).unwrap(), (3, 3, 300));
+    }
+
+    #[test]
+    fn only_the_last_attempt_reports_its_assertions() {
+        MockHost::new();
+        let retry = Retry::new("ready").backoff(Backoff::fixed(10));
+        let mut n = 0;
+        let attempt = || -> Result<u32, HostError> {
+            n += 1;
+            let location = SourceLocation::new("src/lib.rs", 1, 1);
+            report_step(StepRecord::new(true, format!("retry-quiet step {}", n), location.clone()))?;
+            report_assertion(AssertionRecord::new(n == 3, format!("retry-quiet ready {}", n), location))?;
+            Ok(n)
+        };
+        retry.poll(&WapcHost, attempt, |n| *n == 3).unwrap();
+        let reported: Vec<(bool, String)> = REPORT
+            .lock()
+            .unwrap()
+            .events()
+            .iter()
+            .filter_map(|e| match e {
+                ReportEvent::Assertion(a) if a.message.starts_with("retry-quiet") => Some((a.passed, a.message.clone())),
+                ReportEvent::Step(s) if s.description.starts_with("retry-quiet") => Some((s.passed, s.description.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reported, [(true, String::from("retry-quiet ready 3"))]);
+    }
+}
@@ -0,0 +1,113 @@
+//! Pseudo random numbers for the guest, which has no entropy source of its own.
+//!
+//! [`SplitMix64`] is seeded from the host clock on first use, or with [`seed_rng`] to make a run reproducible.
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+use crate::host::{HostClient, WapcHost};
+
+/// SplitMix64 generator
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Uniform in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    /// Uniform in `[low, high]`
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        if high <= low {
+            return low;
+        }
+        low + self.next_u64() % (high - low + 1)
+    }
+}
+
+lazy_static! {
+    static ref RNG: Mutex<Option<SplitMix64>> = Mutex::new(None);
+}
+
+/// Restarts the guest generator from `seed`
+pub fn seed_rng(seed: u64) {
+    *RNG.lock().unwrap() = Some(SplitMix64::new(seed));
+}
+
+/// Runs `f` on the guest generator, seeding it from the host clock when [`seed_rng`] was not called
+pub fn with_rng<T>(f: impl FnOnce(&mut SplitMix64) -> T) -> T {
+    let mut rng = RNG.lock().unwrap();
+    let rng = rng.get_or_insert_with(|| SplitMix64::new(WapcHost.now().unwrap_or(0) as u64));
+    f(rng)
+}
+
+/// Random version 4 UUID
+pub fn uuid_v4() -> String {
+    let (hi, lo) = with_rng(|r| (r.next_u64(), r.next_u64()));
+    let hi = (hi & 0xFFFF_FFFF_FFFF_0FFF) | 0x4000;
+    let lo = (lo & 0x3FFF_FFFF_FFFF_FFFF) | 0x8000_0000_0000_0000;
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xFFFF,
+        hi & 0xFFFF,
+        lo >> 48,
+        lo & 0xFFFF_FFFF_FFFF
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splitmix64_reference_values() {
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let (mut a, mut b) = (SplitMix64::new(42), SplitMix64::new(42));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(SplitMix64::new(1).next_u64(), SplitMix64::new(2).next_u64());
+    }
+
+    #[test]
+    fn f64_and_range_bounds() {
+        let mut rng = SplitMix64::new(7);
+        for _ in 0..1000 {
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            assert!((3..=5).contains(&rng.range(3, 5)));
+        }
+        assert_eq!(rng.range(9, 9), 9);
+        assert_eq!(rng.range(9, 2), 9);
+        assert!((0..1000).map(|_| rng.range(0, 1)).any(|v| v == 1));
+    }
+
+    #[test]
+    fn uuid_v4_format() {
+        seed_rng(11);
+        let first = uuid_v4();
+        let parts: Vec<&str> = first.split('-').collect();
+        assert_eq!(parts.iter().map(|p| p.len()).collect::<Vec<_>>(), [8, 4, 4, 4, 12]);
+        assert!(first.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+        assert!(parts[2].starts_with('4'));
+        assert!(matches!(parts[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(uuid_v4(), first);
+    }
+}
@@ -0,0 +1,200 @@
+//! Stateful scenarios for the HTTP fiddler mocks.
+//!
+//! A scenario is a named state machine starting in [`STARTED`]. Stubs of `mock_suite!` bound to it with
+//! `scenario(..)` only match in the state given by `when_state(..)` and move it with `set_state(..)` once they
+//! answered. The state lives in the guest, or in the mock server's indexdb (`scenario_{name}`) when the
+//! scenario is declared with `in_indexdb()` so it is shared between wasm instances. The guest call
+//! `reset_scenarios` puts one scenario (payload: its name) or all of them (empty payload) back in their
+//! initial state.
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::host::{HostClient, HostError, WapcHost};
+
+/// Initial state of a scenario that does not set one
+pub const STARTED: &str = "Started";
+
+/// Where the state of a scenario is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScenarioStore {
+    Guest,
+    IndexDb,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub initial: String,
+    pub store: ScenarioStore,
+    state: String,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            initial: STARTED.to_string(),
+            store: ScenarioStore::Guest,
+            state: STARTED.to_string(),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Scenario::default()
+    }
+    pub fn initial(mut self, state: &str) -> Self {
+        self.initial = state.to_string();
+        self.state = state.to_string();
+        self
+    }
+    /// Keeps the state in the mock server's indexdb
+    pub fn in_indexdb(mut self) -> Self {
+        self.store = ScenarioStore::IndexDb;
+        self
+    }
+}
+
+lazy_static! {
+    /// Declared scenarios, undeclared ones are created in the guest on first use
+    pub static ref SCENARIO_MAP: Arc<Mutex<HashMap<String, Scenario>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+fn indexdb_key(name: &str) -> String {
+    format!("scenario_{}", name)
+}
+
+/// Declares the scenario `name`. A scenario in indexdb keeps the state other instances left there
+pub fn add_scenario(name: &str, scenario: Scenario) {
+    SCENARIO_MAP.lock().unwrap().insert(name.to_string(), scenario);
+}
+
+/// Current state of the scenario `name`
+pub fn scenario_state(name: &str) -> Result<String, HostError> {
+    scenario_state_with(&WapcHost, name)
+}
+
+/// [`scenario_state`] with the indexdb of `host`
+pub fn scenario_state_with<H: HostClient>(host: &H, name: &str) -> Result<String, HostError> {
+    let scenario = SCENARIO_MAP.lock().unwrap().entry(name.to_string()).or_default().clone();
+    match scenario.store {
+        ScenarioStore::Guest => Ok(scenario.state),
+        ScenarioStore::IndexDb => host.indexdb_get(&indexdb_key(name), &scenario.initial),
+    }
+}
+
+/// Moves the scenario `name` to `state`
+pub fn set_scenario_state(name: &str, state: &str) -> Result<(), HostError> {
+    set_scenario_state_with(&WapcHost, name, state)
+}
+
+/// [`set_scenario_state`] with the indexdb of `host`
+pub fn set_scenario_state_with<H: HostClient>(host: &H, name: &str, state: &str) -> Result<(), HostError> {
+    let store = {
+        let mut map = SCENARIO_MAP.lock().unwrap();
+        let scenario = map.entry(name.to_string()).or_default();
+        scenario.state = state.to_string();
+        scenario.store
+    };
+    if store == ScenarioStore::IndexDb {
+        host.indexdb_store(&indexdb_key(name), state.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Puts the scenario `name` back in its initial state
+pub fn reset_scenario(name: &str) -> Result<(), HostError> {
+    reset_scenario_with(&WapcHost, name)
+}
+
+/// [`reset_scenario`] with the indexdb of `host`
+pub fn reset_scenario_with<H: HostClient>(host: &H, name: &str) -> Result<(), HostError> {
+    let initial = SCENARIO_MAP.lock().unwrap().entry(name.to_string()).or_default().initial.clone();
+    set_scenario_state_with(host, name, &initial)
+}
+
+/// Puts every scenario back in its initial state
+pub fn reset_scenarios() -> Result<(), HostError> {
+    reset_scenarios_with(&WapcHost)
+}
+
+/// [`reset_scenarios`] with the indexdb of `host`
+pub fn reset_scenarios_with<H: HostClient>(host: &H) -> Result<(), HostError> {
+    let names: Vec<String> = SCENARIO_MAP.lock().unwrap().keys().cloned().collect();
+    for name in names {
+        reset_scenario_with(host, &name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_mock_host_abi::MockHost;
+
+    lazy_static! {
+        /// `reset_scenarios` resets the scenarios of every test
+        static ref SERIAL: Mutex<()> = Mutex::new(());
+    }
+
+    #[test]
+    fn guest_state_transitions() {
+        let _serial = SERIAL.lock().unwrap();
+        let host = MockHost::new();
+        add_scenario("checkout", Scenario::new());
+        assert_eq!(scenario_state_with(&WapcHost, "checkout").unwrap(), STARTED);
+        set_scenario_state_with(&WapcHost, "checkout", "PaymentPending").unwrap();
+        assert_eq!(scenario_state_with(&WapcHost, "checkout").unwrap(), "PaymentPending");
+        set_scenario_state_with(&WapcHost, "checkout", "Paid").unwrap();
+        assert_eq!(scenario_state_with(&WapcHost, "checkout").unwrap(), "Paid");
+        reset_scenario_with(&WapcHost, "checkout").unwrap();
+        assert_eq!(scenario_state_with(&WapcHost, "checkout").unwrap(), STARTED);
+        assert!(host.calls().is_empty());
+    }
+
+    #[test]
+    fn undeclared_scenario_starts_in_the_guest() {
+        let _serial = SERIAL.lock().unwrap();
+        assert_eq!(scenario_state_with(&WapcHost, "undeclared").unwrap(), STARTED);
+        assert_eq!(SCENARIO_MAP.lock().unwrap()["undeclared"].store, ScenarioStore::Guest);
+    }
+
+    #[test]
+    fn indexdb_store_is_shared_through_the_host() {
+        let _serial = SERIAL.lock().unwrap();
+        let host = MockHost::new();
+        add_scenario("shared", Scenario::new().initial("Idle").in_indexdb());
+        assert_eq!(scenario_state_with(&WapcHost, "shared").unwrap(), "Idle");
+        set_scenario_state_with(&WapcHost, "shared", "Busy").unwrap();
+        assert_eq!(host.indexdb("scenario_shared").unwrap(), b"Busy");
+        // another instance moved the scenario on
+        host.set_indexdb("scenario_shared", b"Done");
+        assert_eq!(scenario_state_with(&WapcHost, "shared").unwrap(), "Done");
+        let operations: Vec<String> = host.calls().into_iter().map(|c| c.operation).collect();
+        assert_eq!(operations, ["indexdb_get", "indexdb_store", "indexdb_get"]);
+    }
+
+    #[test]
+    fn indexdb_errors_are_returned() {
+        let _serial = SERIAL.lock().unwrap();
+        let host = MockHost::new();
+        host.fail("foo", "indexdb_get", "indexdb down").fail("foo", "indexdb_store", "indexdb down");
+        add_scenario("down", Scenario::new().in_indexdb());
+        assert!(matches!(scenario_state_with(&WapcHost, "down"), Err(HostError::Call(_))));
+        assert!(set_scenario_state_with(&WapcHost, "down", "X").is_err());
+    }
+
+    #[test]
+    fn reset_scenarios_resets_every_store() {
+        let _serial = SERIAL.lock().unwrap();
+        let host = MockHost::new();
+        add_scenario("reset_guest", Scenario::new().initial("A"));
+        add_scenario("reset_indexdb", Scenario::new().initial("B").in_indexdb());
+        set_scenario_state_with(&WapcHost, "reset_guest", "A2").unwrap();
+        set_scenario_state_with(&WapcHost, "reset_indexdb", "B2").unwrap();
+        reset_scenarios_with(&WapcHost).unwrap();
+        assert_eq!(scenario_state_with(&WapcHost, "reset_guest").unwrap(), "A");
+        assert_eq!(scenario_state_with(&WapcHost, "reset_indexdb").unwrap(), "B");
+        assert_eq!(host.indexdb("scenario_reset_indexdb").unwrap(), b"B");
+    }
+}
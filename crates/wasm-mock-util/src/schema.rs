@@ -0,0 +1,286 @@
+//! JSON Schema validation for `foo_assert_json_schema!`.
+//!
+//! Covers the draft-07 keywords used to describe API payloads: `type`, `enum`, `const`, `properties`,
+//! `required`, `additionalProperties`, `patternProperties`, `min/maxProperties`, `items`, `min/maxItems`,
+//! `uniqueItems`, `contains`, `min/maxLength`, `pattern`, `minimum`, `maximum`, `exclusiveMinimum`,
+//! `exclusiveMaximum`, `multipleOf`, `allOf`, `anyOf`, `oneOf`, `not`, `if`/`then`/`else` and local `$ref`s
+//! (`#/definitions/..`, `#/$defs/..`). Other keywords, `format` included, are ignored.
+use regex::Regex;
+use serde_json::Value;
+
+/// Validation errors of `instance` against `schema`, each one prefixed with the JSON pointer of the value.
+/// Empty when `instance` is valid
+///
+/// # Examples
+///
+/// ```
+/// use serde_json::json;
+/// use wasm_mock_util::schema::validate;
+/// let schema = json!({"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}});
+/// assert!(validate(&schema, &json!({"id": 1})).is_empty());
+/// assert_eq!(validate(&schema, &json!({"id": "1"})), vec!["/id: expected integer, got string"]);
+/// ```
+pub fn validate(schema: &Value, instance: &Value) -> Vec<String> {
+    let mut errors = vec![];
+    Validator { root: schema }.check(schema, instance, "", &mut errors);
+    errors
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_type(v: &Value, t: &str) -> bool {
+    match t {
+        "integer" => match v {
+            Value::Number(n) => n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0),
+            _ => false,
+        },
+        "number" => v.is_number(),
+        t => type_name(v) == t,
+    }
+}
+
+fn pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+impl<'a> Validator<'a> {
+    fn is_valid(&self, schema: &Value, instance: &Value) -> bool {
+        let mut errors = vec![];
+        self.check(schema, instance, "", &mut errors);
+        errors.is_empty()
+    }
+
+    fn check(&self, schema: &Value, instance: &Value, path: &str, errors: &mut Vec<String>) {
+        let s = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                errors.push(format!("{}: not allowed", path));
+                return;
+            }
+            Value::Object(s) => s,
+            _ => return,
+        };
+        if let Some(Value::String(r)) = s.get("$ref") {
+            match r.strip_prefix('#').and_then(|p| self.root.pointer(p)) {
+                Some(target) => self.check(target, instance, path, errors),
+                None => errors.push(format!("{}: unresolved $ref {}", path, r)),
+            }
+            return;
+        }
+        match s.get("type") {
+            Some(Value::String(t)) if !is_type(instance, t) => {
+                errors.push(format!("{}: expected {}, got {}", path, t, type_name(instance)));
+                return;
+            }
+            Some(Value::Array(ts)) if !ts.iter().any(|t| t.as_str().is_some_and(|t| is_type(instance, t))) => {
+                let ts: Vec<&str> = ts.iter().filter_map(|t| t.as_str()).collect();
+                errors.push(format!("{}: expected {}, got {}", path, ts.join(" or "), type_name(instance)));
+                return;
+            }
+            _ => {}
+        }
+        if let Some(Value::Array(values)) = s.get("enum") {
+            if !values.contains(instance) {
+                errors.push(format!("{}: {} is not one of {}", path, instance, Value::Array(values.clone())));
+            }
+        }
+        if let Some(c) = s.get("const") {
+            if c != instance {
+                errors.push(format!("{}: expected {}, got {}", path, c, instance));
+            }
+        }
+        match instance {
+            Value::Object(o) => self.check_object(s, o, path, errors),
+            Value::Array(a) => self.check_array(s, a, path, errors),
+            Value::String(st) => self.check_string(s, st, path, errors),
+            Value::Number(n) => {
+                if let Some(n) = n.as_f64() {
+                    self.check_number(s, n, path, errors)
+                }
+            }
+            _ => {}
+        }
+        if let Some(Value::Array(all)) = s.get("allOf") {
+            for sub in all {
+                self.check(sub, instance, path, errors);
+            }
+        }
+        if let Some(Value::Array(any)) = s.get("anyOf") {
+            if !any.iter().any(|sub| self.is_valid(sub, instance)) {
+                errors.push(format!("{}: does not match any schema of anyOf", path));
+            }
+        }
+        if let Some(Value::Array(one)) = s.get("oneOf") {
+            let n = one.iter().filter(|sub| self.is_valid(sub, instance)).count();
+            if n != 1 {
+                errors.push(format!("{}: matches {} schemas of oneOf", path, n));
+            }
+        }
+        if let Some(not) = s.get("not") {
+            if self.is_valid(not, instance) {
+                errors.push(format!("{}: must not match the schema of not", path));
+            }
+        }
+        if let Some(cond) = s.get("if") {
+            let branch = if self.is_valid(cond, instance) { s.get("then") } else { s.get("else") };
+            if let Some(branch) = branch {
+                self.check(branch, instance, path, errors);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        s: &serde_json::Map<String, Value>,
+        o: &serde_json::Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        if let Some(Value::Array(required)) = s.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !o.contains_key(key) {
+                    errors.push(format!("{}: missing required property {}", path, key));
+                }
+            }
+        }
+        if let Some(n) = s.get("minProperties").and_then(|n| n.as_u64()) {
+            if (o.len() as u64) < n {
+                errors.push(format!("{}: expected at least {} properties, got {}", path, n, o.len()));
+            }
+        }
+        if let Some(n) = s.get("maxProperties").and_then(|n| n.as_u64()) {
+            if o.len() as u64 > n {
+                errors.push(format!("{}: expected at most {} properties, got {}", path, n, o.len()));
+            }
+        }
+        let properties = s.get("properties").and_then(|p| p.as_object());
+        let patterns: Vec<(Regex, &Value)> = s
+            .get("patternProperties")
+            .and_then(|p| p.as_object())
+            .map(|p| p.iter().filter_map(|(k, v)| Regex::new(k).ok().map(|r| (r, v))).collect())
+            .unwrap_or_default();
+        for (key, value) in o {
+            let child = format!("{}/{}", path, pointer_token(key));
+            let mut known = false;
+            if let Some(sub) = properties.and_then(|p| p.get(key)) {
+                known = true;
+                self.check(sub, value, &child, errors);
+            }
+            for (re, sub) in &patterns {
+                if re.is_match(key) {
+                    known = true;
+                    self.check(sub, value, &child, errors);
+                }
+            }
+            if !known {
+                match s.get("additionalProperties") {
+                    Some(Value::Bool(false)) => errors.push(format!("{}: additional property {} is not allowed", path, key)),
+                    Some(sub) => self.check(sub, value, &child, errors),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    fn check_array(&self, s: &serde_json::Map<String, Value>, a: &[Value], path: &str, errors: &mut Vec<String>) {
+        match s.get("items") {
+            Some(Value::Array(items)) => {
+                for (i, (sub, value)) in items.iter().zip(a.iter()).enumerate() {
+                    self.check(sub, value, &format!("{}/{}", path, i), errors);
+                }
+            }
+            Some(sub) => {
+                for (i, value) in a.iter().enumerate() {
+                    self.check(sub, value, &format!("{}/{}", path, i), errors);
+                }
+            }
+            None => {}
+        }
+        if let Some(n) = s.get("minItems").and_then(|n| n.as_u64()) {
+            if (a.len() as u64) < n {
+                errors.push(format!("{}: expected at least {} items, got {}", path, n, a.len()));
+            }
+        }
+        if let Some(n) = s.get("maxItems").and_then(|n| n.as_u64()) {
+            if a.len() as u64 > n {
+                errors.push(format!("{}: expected at most {} items, got {}", path, n, a.len()));
+            }
+        }
+        if let Some(Value::Bool(true)) = s.get("uniqueItems") {
+            for (i, v) in a.iter().enumerate() {
+                if a[..i].contains(v) {
+                    errors.push(format!("{}/{}: duplicate item {}", path, i, v));
+                }
+            }
+        }
+        if let Some(sub) = s.get("contains") {
+            if !a.iter().any(|v| self.is_valid(sub, v)) {
+                errors.push(format!("{}: no item matches the schema of contains", path));
+            }
+        }
+    }
+
+    fn check_string(&self, s: &serde_json::Map<String, Value>, st: &str, path: &str, errors: &mut Vec<String>) {
+        let len = st.chars().count() as u64;
+        if let Some(n) = s.get("minLength").and_then(|n| n.as_u64()) {
+            if len < n {
+                errors.push(format!("{}: expected at least {} characters, got {}", path, n, len));
+            }
+        }
+        if let Some(n) = s.get("maxLength").and_then(|n| n.as_u64()) {
+            if len > n {
+                errors.push(format!("{}: expected at most {} characters, got {}", path, n, len));
+            }
+        }
+        if let Some(Value::String(p)) = s.get("pattern") {
+            match Regex::new(p) {
+                Ok(re) if !re.is_match(st) => errors.push(format!("{}: {:?} does not match {}", path, st, p)),
+                Err(e) => errors.push(format!("{}: invalid pattern {}: {}", path, p, e)),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_number(&self, s: &serde_json::Map<String, Value>, n: f64, path: &str, errors: &mut Vec<String>) {
+        let limit = |k: &str| s.get(k).and_then(|v| v.as_f64());
+        if let Some(m) = limit("minimum") {
+            if n < m {
+                errors.push(format!("{}: {} is less than {}", path, n, m));
+            }
+        }
+        if let Some(m) = limit("maximum") {
+            if n > m {
+                errors.push(format!("{}: {} is greater than {}", path, n, m));
+            }
+        }
+        if let Some(m) = limit("exclusiveMinimum") {
+            if n <= m {
+                errors.push(format!("{}: {} is not greater than {}", path, n, m));
+            }
+        }
+        if let Some(m) = limit("exclusiveMaximum") {
+            if n >= m {
+                errors.push(format!("{}: {} is not less than {}", path, n, m));
+            }
+        }
+        if let Some(m) = limit("multipleOf") {
+            if m > 0.0 && ((n / m).round() * m - n).abs() > f64::EPSILON * n.abs().max(1.0) {
+                errors.push(format!("{}: {} is not a multiple of {}", path, n, m));
+            }
+        }
+    }
+}
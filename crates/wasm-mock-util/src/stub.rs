@@ -0,0 +1,449 @@
+//! Declarative HTTP stubs for the fiddler mocks, declared with `stub` in `mock_suite!`.
+//!
+//! Every stub of a mock matches the request on method, path, query parameters, headers, cookies and body.
+//! `{name}_http_modify_res` answers with the response of the first matching stub, stubs with a lower
+//! `priority` first and declaration order between equal priorities. Without a match the response of the
+//! mock server is returned unchanged. Stubs bound to a [`scenario`](crate::scenario) also have to match
+//! its current state and can move it.
+use assert_json_diff::{assert_json_matches_no_panic, CompareMode, Config};
+use base64::{engine::general_purpose, Engine as _};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::fault::inject_fault;
+use crate::json_assert::json_select;
+use crate::mock::{pair_requests, remember_request, request_of};
+use crate::scenario::{scenario_state, set_scenario_state};
+use crate::template::render_response;
+use crate::{HttpResponse, RequestReceivedInMock};
+
+lazy_static! {
+    /// Stubs of each mock in matching order
+    pub static ref STUB_MAP: Arc<Mutex<HashMap<String, Vec<Stub>>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Priority of a stub that does not set one
+pub const DEFAULT_PRIORITY: i32 = 5;
+
+/// Condition on a single request value
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Equals(String),
+    Regex(Regex),
+    Present,
+}
+
+impl Matcher {
+    /// Matcher of the regular expression `regex`, compiled once
+    pub fn regex(regex: &str) -> Result<Self, regex::Error> {
+        Regex::new(regex).map(Matcher::Regex)
+    }
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Equals(s) => s == value,
+            Matcher::Regex(re) => re.is_match(value),
+            Matcher::Present => true,
+        }
+    }
+}
+
+impl PartialEq for Matcher {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Matcher::Equals(a), Matcher::Equals(b)) => a == b,
+            (Matcher::Regex(a), Matcher::Regex(b)) => a.as_str() == b.as_str(),
+            (Matcher::Present, Matcher::Present) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Condition on the request body
+#[derive(Debug, Clone, PartialEq)]
+pub enum BodyMatcher {
+    /// Every field of the value is in the JSON body
+    Include(Value),
+    /// The value at a JSON pointer or JSONPath equals the value
+    Path(String, Value),
+    /// The raw body matches the regular expression
+    Regex(Matcher),
+}
+
+/// Response of a stub
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StubResponse {
+    pub status: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Value>,
+    pub body_raw: Option<String>,
+    /// Body that is not UTF-8 text, sent as `HttpBodyBase64`
+    pub body_bytes: Option<Vec<u8>>,
+}
+
+impl StubResponse {
+    /// Response with `status` and without a body
+    pub fn new(status: u16) -> Self {
+        StubResponse {
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+    /// JSON response, `Content-Type: application/json`
+    pub fn json(status: u16, body: Value) -> Self {
+        StubResponse {
+            body: Some(body),
+            ..StubResponse::new(status)
+        }
+        .header("Content-Type", "application/json")
+    }
+    /// Text response
+    pub fn text(status: u16, body: &str) -> Self {
+        StubResponse {
+            body_raw: Some(body.to_string()),
+            ..StubResponse::new(status)
+        }
+    }
+    /// Binary response
+    pub fn bytes(status: u16, body: &[u8]) -> Self {
+        StubResponse {
+            body_bytes: Some(body.to_vec()),
+            ..StubResponse::new(status)
+        }
+    }
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+    /// Writes the status, headers and body into `res`
+    pub fn apply(&self, res: &mut HttpResponse) {
+        res.StatusCode = self.status.clone();
+        res.Error = String::new();
+        let headers = res.HttpHeader.get_or_insert_with(HashMap::new);
+        for (name, _) in &self.headers {
+            headers.retain(|k, _| !k.eq_ignore_ascii_case(name));
+        }
+        for (name, value) in &self.headers {
+            if let Value::Array(values) = headers.entry(name.clone()).or_insert_with(|| Value::Array(vec![])) {
+                values.push(Value::String(value.clone()));
+            }
+        }
+        if let Some(body) = &self.body {
+            res.HttpBody = body.clone();
+            res.HttpBodyRaw = body.to_string();
+            res.HttpBodyBase64 = String::new();
+        } else if let Some(raw) = &self.body_raw {
+            res.HttpBody = serde_json::from_str(raw).unwrap_or(Value::Null);
+            res.HttpBodyRaw = raw.clone();
+            res.HttpBodyBase64 = String::new();
+        } else if let Some(bytes) = &self.body_bytes {
+            res.HttpBody = Value::Null;
+            res.HttpBodyRaw = String::new();
+            res.HttpBodyBase64 = general_purpose::STANDARD.encode(bytes);
+        }
+    }
+}
+
+/// Request matchers and the response they produce
+///
+/// # Examples
+///
+/// ```
+/// # extern crate wasm_mock_host_abi;
+/// use serde_json::json;
+/// use wasm_mock_util::{RequestReceivedInMock, Stub, StubResponse};
+/// let stub = Stub::new()
+///     .method("GET")
+///     .path_regex(r"^/users/\d+$")
+///     .query("verbose", "true")
+///     .respond(StubResponse::json(200, json!({"id": 1})));
+/// let mut req = RequestReceivedInMock::default();
+/// req.HttpMethod = "GET".into();
+/// req.HttpPath = "/users/1".into();
+/// assert!(!stub.matches(&req));
+/// req.HttpParam = Some([("verbose".to_string(), vec!["true".to_string()])].into_iter().collect());
+/// assert!(stub.matches(&req));
+/// ```
+///
+/// An invalid regular expression is kept as the stub's [`error`](Stub::error) and makes [`add_stub`] fail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stub {
+    pub priority: i32,
+    method: Option<String>,
+    path: Option<Matcher>,
+    query: Vec<(String, Matcher)>,
+    headers: Vec<(String, Matcher)>,
+    cookies: Vec<(String, Matcher)>,
+    body: Vec<BodyMatcher>,
+    scenario: Option<String>,
+    when_state: Option<String>,
+    set_state: Option<String>,
+    pub response: StubResponse,
+    error: Option<regex::Error>,
+}
+
+impl Default for Stub {
+    fn default() -> Self {
+        Stub {
+            priority: DEFAULT_PRIORITY,
+            method: None,
+            path: None,
+            query: vec![],
+            headers: vec![],
+            cookies: vec![],
+            body: vec![],
+            scenario: None,
+            when_state: None,
+            set_state: None,
+            response: StubResponse::new(200),
+            error: None,
+        }
+    }
+}
+
+impl Stub {
+    /// Stub matching every request and answering an empty 200
+    pub fn new() -> Self {
+        Stub::default()
+    }
+    /// Lower priorities are matched first
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+    /// Method, case insensitive
+    pub fn method(mut self, method: &str) -> Self {
+        self.method = Some(method.to_ascii_uppercase());
+        self
+    }
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(Matcher::Equals(path.to_string()));
+        self
+    }
+    pub fn path_regex(mut self, regex: &str) -> Self {
+        self.path = self.compile(regex);
+        self
+    }
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), Matcher::Equals(value.to_string())));
+        self
+    }
+    pub fn query_regex(mut self, name: &str, regex: &str) -> Self {
+        if let Some(m) = self.compile(regex) {
+            self.query.push((name.to_string(), m));
+        }
+        self
+    }
+    pub fn query_present(mut self, name: &str) -> Self {
+        self.query.push((name.to_string(), Matcher::Present));
+        self
+    }
+    /// Header, name case insensitive
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), Matcher::Equals(value.to_string())));
+        self
+    }
+    pub fn header_regex(mut self, name: &str, regex: &str) -> Self {
+        if let Some(m) = self.compile(regex) {
+            self.headers.push((name.to_string(), m));
+        }
+        self
+    }
+    pub fn header_present(mut self, name: &str) -> Self {
+        self.headers.push((name.to_string(), Matcher::Present));
+        self
+    }
+    pub fn cookie(mut self, name: &str, value: &str) -> Self {
+        self.cookies.push((name.to_string(), Matcher::Equals(value.to_string())));
+        self
+    }
+    pub fn cookie_regex(mut self, name: &str, regex: &str) -> Self {
+        if let Some(m) = self.compile(regex) {
+            self.cookies.push((name.to_string(), m));
+        }
+        self
+    }
+    /// Every field of `body` has to be in the JSON body, extra fields are allowed
+    pub fn body_json(mut self, body: Value) -> Self {
+        self.body.push(BodyMatcher::Include(body));
+        self
+    }
+    /// The value at a JSON pointer (`/user/id`) or JSONPath (`$.user.id`) of the body equals `value`
+    pub fn body_path(mut self, path: &str, value: Value) -> Self {
+        self.body.push(BodyMatcher::Path(path.to_string(), value));
+        self
+    }
+    pub fn body_regex(mut self, regex: &str) -> Self {
+        if let Some(m) = self.compile(regex) {
+            self.body.push(BodyMatcher::Regex(m));
+        }
+        self
+    }
+    /// Binds the stub to the scenario `name`
+    pub fn scenario(mut self, name: &str) -> Self {
+        self.scenario = Some(name.to_string());
+        self
+    }
+    /// Only matches while the scenario is in `state`
+    pub fn when_state(mut self, state: &str) -> Self {
+        self.when_state = Some(state.to_string());
+        self
+    }
+    /// Moves the scenario to `state` after answering
+    pub fn set_state(mut self, state: &str) -> Self {
+        self.set_state = Some(state.to_string());
+        self
+    }
+    pub fn respond(mut self, response: StubResponse) -> Self {
+        self.response = response;
+        self
+    }
+    /// First invalid regular expression given to the builder
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.error.as_ref()
+    }
+
+    fn compile(&mut self, regex: &str) -> Option<Matcher> {
+        Matcher::regex(regex).map_err(|e| self.error.get_or_insert(e)).ok()
+    }
+
+    pub fn matches(&self, req: &RequestReceivedInMock) -> bool {
+        if let Some(m) = &self.method {
+            if !req.HttpMethod.eq_ignore_ascii_case(m) {
+                return false;
+            }
+        }
+        if let Some(p) = &self.path {
+            if !p.matches(&req.HttpPath) {
+                return false;
+            }
+        }
+        let params = req.HttpParam.as_ref();
+        let query_ok = self.query.iter().all(|(name, m)| {
+            params
+                .and_then(|p| p.get(name))
+                .is_some_and(|values| values.iter().any(|v| m.matches(v)))
+        });
+        let headers = req.HttpHeader.as_ref();
+        let headers_ok = self.headers.iter().all(|(name, m)| {
+            headers
+                .and_then(|h| h.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)))
+                .is_some_and(|(_, v)| match v {
+                    Value::Array(values) => values.iter().filter_map(|v| v.as_str()).any(|v| m.matches(v)),
+                    Value::String(v) => m.matches(v),
+                    _ => false,
+                })
+        });
+        let cookies = req.HttpCookie.as_ref();
+        let cookies_ok = self
+            .cookies
+            .iter()
+            .all(|(name, m)| cookies.and_then(|c| c.get(name)).is_some_and(|v| m.matches(v)));
+        query_ok && headers_ok && cookies_ok && self.body_matches(req) && self.state_matches()
+    }
+
+    fn state_matches(&self) -> bool {
+        match (&self.scenario, &self.when_state) {
+            (Some(scenario), Some(state)) => scenario_state(scenario).is_ok_and(|s| &s == state),
+            _ => true,
+        }
+    }
+
+    fn body_matches(&self, req: &RequestReceivedInMock) -> bool {
+        if self.body.is_empty() {
+            return true;
+        }
+        let json = if req.HttpBody.is_null() {
+            serde_json::from_str(&req.HttpBodyRaw).unwrap_or(Value::Null)
+        } else {
+            req.HttpBody.clone()
+        };
+        let raw = if req.HttpBodyRaw.is_empty() && !json.is_null() {
+            json.to_string()
+        } else {
+            req.HttpBodyRaw.clone()
+        };
+        self.body.iter().all(|m| match m {
+            BodyMatcher::Include(expected) => {
+                assert_json_matches_no_panic(&json, expected, Config::new(CompareMode::Inclusive)).is_ok()
+            }
+            BodyMatcher::Path(path, expected) => json_select(&json, path).is_ok_and(|v| &v == expected),
+            BodyMatcher::Regex(m) => m.matches(&raw),
+        })
+    }
+}
+
+/// Adds `stub` to the mock `name`, keeping the stubs ordered by priority. Fails when the stub was given an
+/// invalid regular expression
+pub fn add_stub(name: &str, stub: Stub) -> Result<(), regex::Error> {
+    if let Some(e) = stub.error {
+        return Err(e);
+    }
+    pair_requests(name);
+    let mut map = STUB_MAP.lock().unwrap();
+    let stubs = map.entry(name.to_string()).or_default();
+    let at = stubs.iter().position(|s| s.priority > stub.priority).unwrap_or(stubs.len());
+    stubs.insert(at, stub);
+    Ok(())
+}
+
+/// First stub of the mock `name` matching `req`
+pub fn find_stub(name: &str, req: &RequestReceivedInMock) -> Option<Stub> {
+    STUB_MAP.lock().unwrap().get(name)?.iter().find(|s| s.matches(req)).cloned()
+}
+
+/// `{name}_http_modify_req` of a stubbed mock, remembers the request and returns it unchanged
+pub fn stub_http_req(name: &str, msg: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Sync + Send>> {
+    let req: RequestReceivedInMock = serde_json::from_slice(msg)?;
+    remember_request(name, &req);
+    Ok(msg.to_vec())
+}
+
+/// `{name}_http_modify_res` of a stubbed mock, answers with the first matching stub and injects the
+/// [`fault`](crate::fault) of the mock
+pub fn stub_http_res(name: &str, msg: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Sync + Send>> {
+    let mut res: HttpResponse = serde_json::from_slice(msg)?;
+    let req = request_of(name, &res)?;
+    if let Some(stub) = find_stub(name, &req) {
+        stub.response.apply(&mut res);
+        render_response(&mut res, &req);
+        if let (Some(scenario), Some(state)) = (&stub.scenario, &stub.set_state) {
+            set_scenario_state(scenario, state)?;
+        }
+    }
+    inject_fault(name, &mut res)?;
+    Ok(serde_json::to_vec(&res)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> RequestReceivedInMock {
+        RequestReceivedInMock {
+            HttpMethod: "GET".into(),
+            HttpPath: path.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn invalid_regex_is_returned_by_add_stub() {
+        let stub = Stub::new().path("/a").header_regex("x-id", "(").body_regex("[");
+        assert!(stub.error().unwrap().to_string().contains("unclosed group"));
+        assert!(add_stub("/invalid", stub).is_err());
+        assert!(!STUB_MAP.lock().unwrap().contains_key("/invalid"));
+    }
+
+    #[test]
+    fn regex_is_compiled_once() {
+        let stub = Stub::new().path_regex(r"^/users/\d+$");
+        assert_eq!(stub.path, Some(Matcher::regex(r"^/users/\d+$").unwrap()));
+        assert!(stub.error().is_none());
+        assert!(stub.matches(&request("/users/12")));
+        assert!(!stub.matches(&request("/users/x")));
+    }
+}
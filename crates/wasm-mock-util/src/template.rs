@@ -0,0 +1,161 @@
+//! Response templates filled from the request that produced the response.
+//!
+//! Placeholders:
+//!
+//! * `{{request.method}}`, `{{request.path}}`, `{{request.path[1]}}` (segments counted from 0 after the
+//!   leading `/`)
+//! * `{{request.query.page}}`, `{{request.headers.X-Id}}`, `{{request.cookies.sid}}` (first value)
+//! * `{{request.body}}`, `{{request.body.user.id}}`, `{{request.body.items[0]}}`
+//! * `{{now}}` (host time stamp in ms) and `{{uuid}}`
+//!
+//! A JSON string made of a single placeholder is replaced by the JSON value, so `"{{request.body.user.id}}"`
+//! stays a number. Unknown placeholders are left as they are.
+use serde_json::Value;
+
+use crate::host::{HostClient, WapcHost};
+use crate::rng::uuid_v4;
+use crate::{HttpResponse, RequestReceivedInMock};
+
+/// Replaces every `{{key}}` of `template` with `lookup(key)`. Strings are inserted without quotes, other
+/// values as JSON; keys without a value are left as they are
+pub fn fill_with<F: Fn(&str) -> Option<Value>>(template: &str, lookup: F) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                match lookup(after[..end].trim()) {
+                    Some(Value::String(s)) => out.push_str(&s),
+                    Some(v) => out.push_str(&v.to_string()),
+                    None => out.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Walks `path` (`a.b[0].c` or `a.b.0.c`) into `value`
+pub fn value_at<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .filter(|k| !k.is_empty())
+        .try_fold(value, |v, key| match v {
+            Value::Object(m) => m.get(key),
+            Value::Array(a) => key.parse::<usize>().ok().and_then(|i| a.get(i)),
+            _ => None,
+        })
+}
+
+fn first_header(req: &RequestReceivedInMock, name: &str) -> Option<Value> {
+    let (_, v) = req.HttpHeader.as_ref()?.iter().find(|(k, _)| k.eq_ignore_ascii_case(name))?;
+    match v {
+        Value::Array(values) => values.first().cloned(),
+        v => Some(v.clone()),
+    }
+}
+
+/// Value of the placeholder `key` for `req`
+pub fn lookup(key: &str, req: &RequestReceivedInMock) -> Option<Value> {
+    match key {
+        "now" => return WapcHost.now().ok().map(Value::from),
+        "uuid" => return Some(Value::String(uuid_v4())),
+        _ => {}
+    }
+    let rest = key.strip_prefix("request.")?;
+    let (field, sub) = match rest.find(['.', '[']) {
+        Some(i) => (&rest[..i], rest[i..].trim_start_matches('.')),
+        None => (rest, ""),
+    };
+    match field {
+        "method" => Some(Value::String(req.HttpMethod.clone())),
+        "path" if sub.is_empty() => Some(Value::String(req.HttpPath.clone())),
+        "path" => {
+            let segments: Vec<Value> = req
+                .HttpPath
+                .split('?')
+                .next()
+                .unwrap_or("")
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .map(|s| Value::String(s.to_string()))
+                .collect();
+            value_at(&Value::Array(segments), sub).cloned()
+        }
+        "query" => req.HttpParam.as_ref()?.get(sub)?.first().map(|v| Value::String(v.clone())),
+        "headers" => first_header(req, sub),
+        "cookies" => req.HttpCookie.as_ref()?.get(sub).map(|v| Value::String(v.clone())),
+        "body" => {
+            let body = if req.HttpBody.is_null() {
+                serde_json::from_str(&req.HttpBodyRaw).unwrap_or_else(|_| Value::String(req.HttpBodyRaw.clone()))
+            } else {
+                req.HttpBody.clone()
+            };
+            value_at(&body, sub).cloned()
+        }
+        _ => None,
+    }
+}
+
+/// `template` with its placeholders filled from `req`
+///
+/// # Examples
+///
+/// ```
+/// # extern crate wasm_mock_host_abi;
+/// use wasm_mock_util::RequestReceivedInMock;
+/// use wasm_mock_util::template::render;
+/// let mut req = RequestReceivedInMock::default();
+/// req.HttpPath = "/users/42/orders".into();
+/// assert_eq!(render("user {{request.path[1]}}", &req), "user 42");
+/// ```
+pub fn render(template: &str, req: &RequestReceivedInMock) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+    fill_with(template, |key| lookup(key, req))
+}
+
+/// `value` with the placeholders of its strings filled from `req`
+pub fn render_json(value: &Value, req: &RequestReceivedInMock) -> Value {
+    match value {
+        Value::String(s) => {
+            let t = s.trim();
+            if t.starts_with("{{") && t.ends_with("}}") && t[2..].find("{{").is_none() {
+                if let Some(v) = lookup(t[2..t.len() - 2].trim(), req) {
+                    return v;
+                }
+            }
+            Value::String(render(s, req))
+        }
+        Value::Array(a) => Value::Array(a.iter().map(|v| render_json(v, req)).collect()),
+        Value::Object(m) => Value::Object(m.iter().map(|(k, v)| (k.clone(), render_json(v, req))).collect()),
+        v => v.clone(),
+    }
+}
+
+/// Fills the placeholders of the body and header values of `res` from `req`
+pub fn render_response(res: &mut HttpResponse, req: &RequestReceivedInMock) {
+    if !res.HttpBody.is_null() {
+        if res.HttpBody.to_string().contains("{{") {
+            res.HttpBody = render_json(&res.HttpBody, req);
+            res.HttpBodyRaw = res.HttpBody.to_string();
+        }
+    } else {
+        res.HttpBodyRaw = render(&res.HttpBodyRaw, req);
+    }
+    if let Some(headers) = res.HttpHeader.as_mut() {
+        for v in headers.values_mut() {
+            *v = render_json(v, req);
+        }
+    }
+}
@@ -1,12 +1,14 @@
 use bytecodec::io::{IoDecodeExt, IoEncodeExt, ReadBuf};
 use httpcodec::{RequestDecoder,BodyDecoder,ResponseDecoder,RequestEncoder,NoBodyEncoder,StatusCode,Request,HeaderField};
-use websocket_codec::MessageCodec;
+use websocket_codec::{DeflateConfig,MessageCodec};
 use bytecodec::{Encode,Decode};
 use bytecodec::bytes::RemainingBytesDecoder;
-use wapc_guest::prelude::CallResult;
 use base64::{Engine as _, engine::{general_purpose}};
 use crate::handshake::{Handshake,HandshakeRes};
+use crate::frames::FrameBuffer;
+use crate::rules::WsDirection;
 use crate::{TcpItem};
+use std::error::Error;
 use std::mem;
 const BUF_SIZE: usize = 4096;
 pub struct Channel{
@@ -16,6 +18,10 @@ pub struct Channel{
     pub handshake_res: HandshakeRes,
     pub frame_req_decoder: MessageCodec,
     pub frame_res_decoder: MessageCodec,
+    pub req_frames: FrameBuffer,
+    pub res_frames: FrameBuffer,
+    /// permessage-deflate parameters the server accepted
+    pub deflate: Option<DeflateConfig>,
     pub laddr:String,
     pub raddr:String,
 }
@@ -26,21 +32,32 @@ impl Channel{
             ws_resbuf: ReadBuf::new(vec![0;BUF_SIZE]),
             handshake: Handshake::RecvRequest(RequestDecoder::default()),
             handshake_res: HandshakeRes::RecvResponse( ResponseDecoder::<BodyDecoder<RemainingBytesDecoder>>::default()),
-            frame_req_decoder: MessageCodec::client(),
-            frame_res_decoder: MessageCodec::server(),
+            frame_req_decoder: frame_codec(WsDirection::Req,None),
+            frame_res_decoder: frame_codec(WsDirection::Res,None),
+            req_frames: FrameBuffer::new(),
+            res_frames: FrameBuffer::new(),
+            deflate: None,
             laddr:laddr,
             raddr:raddr
         }
     }
 }
 impl Channel{
-    pub fn process_handshake_req(&mut self,origin:&str)->CallResult{
+    /// Inflates the intercepted messages and deflates them again with the permessage-deflate parameters the
+    /// server accepted
+    pub fn use_deflate(&mut self,config:DeflateConfig){
+        self.deflate = Some(config);
+        self.frame_req_decoder = frame_codec(WsDirection::Req,self.deflate);
+        self.frame_res_decoder = frame_codec(WsDirection::Res,self.deflate);
+    }
+    /// Handshake request sent in place of the decoded one, `None` while it is incomplete or invalid
+    pub fn process_handshake_req(&mut self,origin:&str)->Result<Option<TcpItem>,Box<dyn Error + Sync + Send>>{
         match mem::replace(&mut self.handshake, Handshake::Done){
             Handshake::RecvRequest(mut decoder)=>{
                 let result = decoder.decode_from_read_buf(&mut self.ws_reqbuf);
                 if result.is_ok() && !decoder.is_idle() {
                     self.handshake = Handshake::RecvRequest(decoder);
-                    return Ok(b"/continue".to_vec());
+                    return Ok(None);
                 }
                 match decoder.finish_decoding(){
                     Ok(request)=>{
@@ -50,7 +67,6 @@ impl Channel{
                         encoder.start_encoding(new_req).unwrap();
                         let mut buf = Vec::new();
                         encoder.encode_all(&mut buf).unwrap();
-                        let mut consolidated:Vec<TcpItem> = vec![];
                         let item = TcpItem{
                             Payload:general_purpose::STANDARD.encode(buf.clone()),
                             String:std::str::from_utf8(&buf).unwrap().to_string(),
@@ -58,41 +74,54 @@ impl Channel{
                             Laddr:self.laddr.clone(),
                             Raddr:self.raddr.clone()
                         };
-                        consolidated.push(item);
-                        let buf = rmp_serde::to_vec(&consolidated)?;
-                        Ok(buf)
+                        Ok(Some(item))
                     },
                     Err(_)=>{
                         //track_assert_eq!(Some(format!("finish_decoding not {:?}",e)),None,ErrorKind::InvalidInput);
-                        Ok(b"/continue".to_vec())
+                        Ok(None)
                     }
                 }
             }
             _=>{
                 //track_assert_eq!(Some(format!("continue {:?} handshake {:?}",ROW_INDEX.lock().unwrap(),channel.handshake)),None,ErrorKind::InvalidInput);
-                Ok(b"/continue".to_vec())
+                Ok(None)
             }
         }
         
     }
-    pub fn process_handshake_res(&mut self,original_message:String)->CallResult{ //bool: continue
-        let mut consolidated:Vec<TcpItem> = vec![];
+    /// Handshake response item carrying `original_message`, `None` while the response is incomplete.
+    /// Bytes decoded as the response body are frames sent right after it, and go to `res_frames`.
+    pub fn process_handshake_res(&mut self,original_message:String)->Result<Option<TcpItem>,Box<dyn Error + Sync + Send>>{
         match mem::replace(&mut self.handshake_res, HandshakeRes::Done) {
             HandshakeRes::RecvResponse(mut decoder)=>{
                 // let mut decoder = ResponseDecoder::<BodyDecoder<RemainingBytesDecoder>>::default();
                 let result = decoder.decode_from_read_buf(&mut self.ws_resbuf);
                 if result.is_ok() && !decoder.is_idle() {
                     self.handshake_res = HandshakeRes::RecvResponse(decoder);
-                    return Ok(b"/continue".to_vec());
+                    return Ok(None);
                 }
                 match decoder.finish_decoding(){
                     Ok(response)=>{
-                        let handshake_ok =  if response.status_code() == StatusCode::new(101)?{
+                        let mut handshake_ok =  if response.status_code() == StatusCode::new(101)?{
                             String::from("handshake res ok")
                         }else{
                             format!("handshake res not ok, status code {:?}",response.status_code())
                         };
-                        let mut consolidated:Vec<TcpItem> = vec![];
+                        let extensions:Vec<String> = response.header().fields()
+                            .filter(|f|f.name().eq_ignore_ascii_case("Sec-WebSocket-Extensions"))
+                            .map(|f|f.value().to_string())
+                            .collect();
+                        match DeflateConfig::parse(&extensions.join(",")){
+                            Ok(Some(config))=>{
+                                self.use_deflate(config);
+                                handshake_ok = format!("{}, {}",handshake_ok,config);
+                            }
+                            Ok(None)=>{}
+                            Err(e)=>{
+                                handshake_ok = format!("{}, {}",handshake_ok,e);
+                            }
+                        }
+                        self.res_frames.extend_from_slice(response.body());
                         let item = TcpItem{
                             Payload:original_message,
                             String:handshake_ok,
@@ -100,9 +129,7 @@ impl Channel{
                             Laddr:self.laddr.clone(),
                             Raddr:self.raddr.clone()
                         };
-                        consolidated.push(item);
-                        let buf = rmp_serde::to_vec(&consolidated)?;
-                        return Ok(buf);
+                        return Ok(Some(item));
                     }
                     _=>{}
                 }
@@ -117,9 +144,16 @@ impl Channel{
             Laddr:self.laddr.clone(),
             Raddr:self.raddr.clone()
         };
-        consolidated.push(item);
-        let buf = rmp_serde::to_vec(&consolidated)?;
-        return Ok(buf);
+        Ok(Some(item))
+    }
+}
+/// Codec of the frames going in `direction`, inflating and deflating them with `deflate`
+pub fn frame_codec(direction:WsDirection,deflate:Option<DeflateConfig>)->MessageCodec{
+    match (direction,deflate){
+        (WsDirection::Req,None)=>MessageCodec::client(),
+        (WsDirection::Res,None)=>MessageCodec::server(),
+        (WsDirection::Req,Some(config))=>MessageCodec::client().with_deflate(config.client(),config.client()),
+        (WsDirection::Res,Some(config))=>MessageCodec::server().with_deflate(config.server(),config.server()),
     }
 }
 fn modify_request_origin(request:&Request<()>,origin:&str)->Request<()>{
@@ -128,7 +162,7 @@ fn modify_request_origin(request:&Request<()>,origin:&str)->Request<()>{
         for field in request.header().fields() {
             let name = field.name();
             let value = field.value();
-            if name=="origin"|| name=="Origin"{
+            if (name=="origin"|| name=="Origin") && !origin.is_empty(){
                 new_request.header_mut().add_field(HeaderField::new_unchecked(name,origin));
             }else{
                 new_request.header_mut().add_field(HeaderField::new_unchecked(name,value));
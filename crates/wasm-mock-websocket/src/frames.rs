@@ -0,0 +1,176 @@
+//! Frames of one direction of a connection, accumulated across TCP payloads.
+//!
+//! A TCP payload may carry several frames, or only part of one. The bytes are kept per channel until the
+//! decoder has whole messages to return; the fragments of a fragmented message are held by the decoder itself.
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::Decoder;
+use websocket_codec::protocol::FrameHeader;
+use websocket_codec::{Message, MessageCodec, Result};
+
+/// Bytes received in one direction that do not make a whole message yet
+#[derive(Debug, Default)]
+pub struct FrameBuffer {
+    bytes: BytesMut,
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        FrameBuffer::default()
+    }
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    /// Next whole message, `None` until more bytes arrive. Frames are handed to `decoder` one at a time once
+    /// they are complete, and only removed when decoded, so the bytes of a frame that is not valid are kept.
+    pub fn next_message(&mut self, decoder: &mut MessageCodec) -> Result<Option<Message>> {
+        while let Some(len) = self.frame_len()? {
+            // the decoder unmasks in place: it gets a copy of the frame alone
+            let message = decoder.decode(&mut BytesMut::from(&self.bytes[..len]))?;
+            self.bytes.advance(len);
+            if message.is_some() {
+                return Ok(message);
+            }
+        }
+        Ok(None)
+    }
+    /// Length of the first frame, `None` while some of its bytes are missing
+    fn frame_len(&self) -> Result<Option<usize>> {
+        let (header, header_len) = match FrameHeader::parse_slice(&self.bytes) {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        let len = usize::try_from(header.data_len())?
+            .checked_add(header_len)
+            .ok_or("frame is too long")?;
+        Ok(Some(len).filter(|len| *len <= self.bytes.len()))
+    }
+    /// Empties the buffer, returning the bytes not decoded yet
+    pub fn take(&mut self) -> Vec<u8> {
+        self.bytes.split().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::codec::Encoder;
+    use websocket_codec::CloseCode;
+
+    /// Masked frames of a client: a short text, a text fragmented in three with a ping in between, a binary
+    /// message long enough for a 16 bit length, and a close
+    fn recorded() -> (Vec<u8>, Vec<Message>) {
+        let mut codec = MessageCodec::client();
+        let mut stream = BytesMut::new();
+        // "over9000" as sent by Safari
+        stream.extend_from_slice(&[129, 136, 187, 233, 66, 87, 212, 159, 39, 37, 130, 217, 114, 103]);
+        for (fin, opcode, data) in [(false, 1u8, &b"frag"[..]), (false, 0, b"men"), (true, 0, b"ted")] {
+            stream.extend_from_slice(&[(fin as u8) << 7 | opcode, 0x80 | data.len() as u8, 1, 2, 3, 4]);
+            stream.extend(data.iter().zip([1, 2, 3, 4].iter().cycle()).map(|(b, m)| b ^ m));
+            if opcode == 1 {
+                codec.encode(Message::ping("p"), &mut stream).unwrap();
+            }
+        }
+        let binary: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        codec.encode(Message::binary(binary.clone()), &mut stream).unwrap();
+        let close = Message::close_with_reason(CloseCode::Normal, "bye".to_string());
+        codec.encode(close.clone(), &mut stream).unwrap();
+        let messages = vec![
+            Message::text("over9000"),
+            Message::ping("p"),
+            Message::text("fragmented"),
+            Message::binary(binary),
+            close,
+        ];
+        (stream.to_vec(), messages)
+    }
+
+    fn decode_chunks<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<Message> {
+        let mut decoder = MessageCodec::client();
+        let mut buffer = FrameBuffer::new();
+        let mut messages = vec![];
+        for chunk in chunks {
+            buffer.extend_from_slice(chunk);
+            while let Some(message) = buffer.next_message(&mut decoder).unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(buffer.len(), 0);
+        messages
+    }
+
+    #[test]
+    fn recorded_stream_in_one_payload() {
+        let (stream, messages) = recorded();
+        assert_eq!(decode_chunks([&stream[..]]), messages);
+    }
+
+    #[test]
+    fn every_split_in_two() {
+        let (stream, _) = recorded();
+        let whole = decode_chunks([&stream[..]]);
+        for i in 0..=stream.len() {
+            assert_eq!(decode_chunks([&stream[..i], &stream[i..]]), whole, "split at {}", i);
+        }
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        let (stream, _) = recorded();
+        assert_eq!(decode_chunks(stream.chunks(1)), decode_chunks([&stream[..]]));
+    }
+
+    #[test]
+    fn random_segmentations() {
+        let (stream, _) = recorded();
+        let whole = decode_chunks([&stream[..]]);
+        let mut seed: u32 = 0x2545_f491;
+        for _ in 0..200 {
+            let mut chunks = vec![];
+            let mut rest = &stream[..];
+            while !rest.is_empty() {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let (chunk, tail) = rest.split_at(1 + seed as usize % 40 % rest.len());
+                chunks.push(chunk);
+                rest = tail;
+            }
+            assert_eq!(decode_chunks(chunks.iter().copied()), whole, "chunks {:?}", chunks.iter().map(|c| c.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn partial_frame_is_kept() {
+        let (stream, _) = recorded();
+        let mut decoder = MessageCodec::client();
+        let mut buffer = FrameBuffer::new();
+        buffer.extend_from_slice(&stream[..20]);
+        assert!(buffer.next_message(&mut decoder).unwrap().is_some());
+        assert!(buffer.next_message(&mut decoder).unwrap().is_none());
+        assert_eq!(buffer.take(), stream[14..20].to_vec());
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn invalid_frame_after_a_message_is_kept() {
+        let (stream, _) = recorded();
+        let mut buffer = FrameBuffer::new();
+        buffer.extend_from_slice(&stream[..14]);
+        buffer.extend_from_slice(&[0x8f, 0x80, 0, 0, 0, 0]);
+        let mut decoder = MessageCodec::client();
+        assert_eq!(buffer.next_message(&mut decoder).unwrap(), Some(Message::text("over9000")));
+        assert!(buffer.next_message(&mut decoder).is_err());
+        assert_eq!(buffer.take(), vec![0x8f, 0x80, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_frame_is_kept() {
+        let mut buffer = FrameBuffer::new();
+        buffer.extend_from_slice(&[0x8f, 0x80, 0, 0, 0, 0]);
+        assert!(buffer.next_message(&mut MessageCodec::client()).is_err());
+        assert_eq!(buffer.take(), vec![0x8f, 0x80, 0, 0, 0, 0]);
+    }
+}
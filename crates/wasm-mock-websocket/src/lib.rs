@@ -1,19 +1,28 @@
+#[cfg(test)]
+extern crate wasm_mock_host_abi;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use bytecodec::io::{ReadBuf};
 use std::sync::{Mutex,Arc};
-use tokio_util::codec::{Encoder,Decoder};
+use tokio_util::codec::Encoder;
 use wapc_guest::prelude::CallResult;
 use websocket_codec::MessageCodec;
 use bytes::BytesMut;
 use base64::{Engine as _, engine::{general_purpose}};
-use wasm_mock_util::{TcpPayload,TcpItem};
+use wasm_mock_util::{TcpPayload,TcpItem,TcpReq};
+use wasm_mock_util::host::{HostClient,HostError,WapcHost};
 use std::io::Read;
 use std::io::Cursor;
 mod channel;
-use channel::{Channel};
+use channel::{frame_codec,Channel};
+mod frames;
 mod handshake;
 use handshake::{Handshake,HandshakeRes};
+pub mod preview;
+pub use preview::{set_ws_preview, Preview};
+pub mod rules;
+pub use rules::{add_ws_rule, apply_ws_rules, set_ws_origin, ws_rules_req, ws_rules_res, WsAction, WsDirection, WsEmit, WsMatch, WsRule};
+use std::error::Error;
 lazy_static! {
     static ref CHANNEL_MAP: Arc<Mutex<HashMap<String,Channel>>> =
         Arc::new(Mutex::new(HashMap::new()));
@@ -31,17 +40,21 @@ lazy_static! {
 /// use wasm_mock_util::*;
 /// fn _req(msg: &[u8]) -> CallResult{
 ///     let tcp_payload:TcpPayload = rmp_serde::from_read_ref(msg)?;
-///     let c = |_c: &mut websocket_codec::Message|->CallResult{
-///          Ok(vec![])
+///     let c = |_c: &mut websocket_codec::Message|{
+///          Ok(WsAction::Forward)
 ///     };
 ///     //change origin from 3334 to 3335 ( as the page is served in localhost:3334, but the mock server dial from port 3335)
 ///     handle_ws_req(&tcp_payload,"http://localhost:3335",c)
 /// }
 /// fn _res(msg: &[u8]) -> CallResult{
 ///     let tcp_payload:TcpPayload = rmp_serde::from_read_ref(msg)?;
-///     let c = |c: &mut websocket_codec::Message|->CallResult{
+///     let c = |c: &mut websocket_codec::Message|{
+///         if c.as_text() == Some("ping") {
+///             // answered by the guest, the server never sees the ping
+///             return Ok(WsAction::Reply(websocket_codec::Message::text("pong")));
+///         }
 ///         *c = websocket_codec::Message::text("echo");
-///         Ok(vec![])
+///         Ok(WsAction::Forward)
 ///     };
 ///     handle_ws_res(&tcp_payload,c)
 /// }
@@ -59,41 +72,34 @@ lazy_static! {
 ///
 /// * `tcp_payload` - TcpPayload
 /// * `change_origin` - Change of http origin
-/// * `c` - User defined closure to handle Websocket messages
+/// * `c` - User defined closure to handle Websocket messages, returning what to send in their place
 ///
 /// # Returns
 ///
 /// CallResult
-pub fn handle_ws_req<F>(tcp_payload:&TcpPayload,change_origin:&str,c:F)->CallResult where F: Fn(&mut websocket_codec::Message)->CallResult{
+pub fn handle_ws_req<F>(tcp_payload:&TcpPayload,change_origin:&str,c:F)->CallResult where F: FnMut(&mut websocket_codec::Message)->Result<WsAction,Box<dyn Error + Sync + Send>>{
     let mut p = CHANNEL_MAP.lock().unwrap();
     let conn = format!("{}-{}",tcp_payload.Laddr,tcp_payload.Raddr);
     let payload = general_purpose::STANDARD.decode(tcp_payload.Payload.clone())?;
-    let mut file = Cursor::new(payload);
-    if let Some(channel)= p.get_mut(&conn){
-        channel.ws_reqbuf.fill(&mut file)?;
-        let result = match channel.handshake{
-            Handshake::RecvRequest(_)=>{
-                channel.process_handshake_req(change_origin)
-            },
-            Handshake::Done=>{
-                process_closure(&mut channel.ws_reqbuf,&mut channel.frame_req_decoder,channel.laddr.clone(),channel.raddr.clone(),c)
-            }
-        };
-        return result;
-    }else{
-        let mut channel = Channel::new(tcp_payload.Laddr.clone(),tcp_payload.Raddr.clone());
-        channel.ws_reqbuf.fill(&mut file)?;
-        let result = match channel.handshake{
-            Handshake::RecvRequest(_)=>{
-                channel.process_handshake_req(change_origin)
-            },
-            Handshake::Done=>{
-                process_closure(&mut channel.ws_reqbuf,&mut channel.frame_req_decoder,channel.laddr.clone(),channel.raddr.clone(),c)
+    let channel = p.entry(conn).or_insert_with(||Channel::new(tcp_payload.Laddr.clone(),tcp_payload.Raddr.clone()));
+    let mut consolidated = vec![];
+    match channel.handshake{
+        Handshake::RecvRequest(_)=>{
+            let mut file = Cursor::new(payload);
+            channel.ws_reqbuf.fill(&mut file)?;
+            match channel.process_handshake_req(change_origin)?{
+                Some(item)=>consolidated.push(item),
+                None=>return Ok(b"/continue".to_vec()),
             }
-        };
-        p.insert(conn,channel);
-        return result;
+            // frames sent right behind the handshake request
+            let leftover = unread(&mut channel.ws_reqbuf,file)?;
+            channel.req_frames.extend_from_slice(&leftover);
+        },
+        Handshake::Done=>{
+            channel.req_frames.extend_from_slice(&payload);
+        }
     }
+    process_closure(channel,WsDirection::Req,consolidated,c)
 }
 /// Handles conversion of tcp packets from local to remote connection into websocket framed messages
 ///
@@ -107,17 +113,21 @@ pub fn handle_ws_req<F>(tcp_payload:&TcpPayload,change_origin:&str,c:F)->CallRes
 /// use wasm_mock_util::*;
 /// fn _req(msg: &[u8]) -> CallResult{
 ///     let tcp_payload:TcpPayload = rmp_serde::from_read_ref(msg)?;
-///     let c = |_c: &mut websocket_codec::Message|->CallResult{
-///          Ok(vec![])
+///     let c = |_c: &mut websocket_codec::Message|{
+///          Ok(WsAction::Forward)
 ///     };
 ///     //change origin from 3334 to 3335 ( as the page is served in localhost:3334, but the mock server dial from port 3335)
 ///     handle_ws_req(&tcp_payload,"http://localhost:3335",c)
 /// }
 /// fn _res(msg: &[u8]) -> CallResult{
 ///     let tcp_payload:TcpPayload = rmp_serde::from_read_ref(msg)?;
-///     let c = |c: &mut websocket_codec::Message|->CallResult{
+///     let c = |c: &mut websocket_codec::Message|{
+///         if c.as_text() == Some("ping") {
+///             // answered by the guest, the server never sees the ping
+///             return Ok(WsAction::Reply(websocket_codec::Message::text("pong")));
+///         }
 ///         *c = websocket_codec::Message::text("echo");
-///         Ok(vec![])
+///         Ok(WsAction::Forward)
 ///     };
 ///     handle_ws_res(&tcp_payload,c)
 /// }
@@ -134,92 +144,245 @@ pub fn handle_ws_req<F>(tcp_payload:&TcpPayload,change_origin:&str,c:F)->CallRes
 /// # Arguments
 ///
 /// * `tcp_payload` - TcpPayload
-/// * `c` - User defined closure to handle Websocket messages
+/// * `c` - User defined closure to handle Websocket messages, returning what to send in their place
 ///
 /// # Returns
 ///
 /// CallResult
-pub fn handle_ws_res<F>(tcp_payload:&TcpPayload,c:F)->CallResult where F: Fn(&mut websocket_codec::Message)->CallResult{
+pub fn handle_ws_res<F>(tcp_payload:&TcpPayload,c:F)->CallResult where F: FnMut(&mut websocket_codec::Message)->Result<WsAction,Box<dyn Error + Sync + Send>>{
     let mut p = CHANNEL_MAP.lock().unwrap();
     let conn = format!("{}-{}",tcp_payload.Laddr,tcp_payload.Raddr);
     let payload = general_purpose::STANDARD.decode(tcp_payload.Payload.clone())?;
-    let mut file = Cursor::new(payload);
-    if let Some(channel)= p.get_mut(&conn){
-        channel.ws_resbuf.fill(&mut file)?;
-        let result = match channel.handshake_res{
-            HandshakeRes::RecvResponse(_)=>{
-                channel.process_handshake_res(tcp_payload.Payload.clone())
-            }
-            HandshakeRes::Done=>{
-                process_closure(&mut channel.ws_resbuf,&mut channel.frame_res_decoder,channel.laddr.clone(),channel.raddr.clone(),c)
-            }
-        };
-        return result;
-    }else{
-        let mut channel = Channel::new(tcp_payload.Laddr.clone(),tcp_payload.Raddr.clone());
-        channel.ws_resbuf.fill(&mut file)?;
-        let result = match channel.handshake_res{
-            HandshakeRes::RecvResponse(_)=>{
-                channel.process_handshake_res(tcp_payload.Payload.clone())
+    let channel = p.entry(conn).or_insert_with(||Channel::new(tcp_payload.Laddr.clone(),tcp_payload.Raddr.clone()));
+    let mut consolidated = vec![];
+    match channel.handshake_res{
+        HandshakeRes::RecvResponse(_)=>{
+            let payload_len = payload.len();
+            let mut file = Cursor::new(payload);
+            channel.ws_resbuf.fill(&mut file)?;
+            let buffered = channel.res_frames.len();
+            match channel.process_handshake_res(tcp_payload.Payload.clone())?{
+                Some(item)=>consolidated.push(item),
+                None=>return Ok(b"/continue".to_vec()),
             }
-            HandshakeRes::Done=>{
-                process_closure(&mut channel.ws_resbuf,&mut channel.frame_res_decoder,channel.laddr.clone(),channel.raddr.clone(),c)
+            let head = file.get_ref().clone();
+            let leftover = unread(&mut channel.ws_resbuf,file)?;
+            channel.res_frames.extend_from_slice(&leftover);
+            // the handshake item only carries the response head, the frames behind it are framed again below
+            let frames_len = channel.res_frames.len() - buffered;
+            if frames_len > 0{
+                consolidated[0].Payload = general_purpose::STANDARD.encode(&head[..payload_len.saturating_sub(frames_len)]);
             }
-        };
-        p.insert(conn,channel);
-        return result;
+        }
+        HandshakeRes::Done=>{
+            channel.res_frames.extend_from_slice(&payload);
+        }
     }
+    process_closure(channel,WsDirection::Res,consolidated,c)
+}
+/// Bytes of a payload left behind a handshake: those still in `read_buf` and those it could not take from `file`
+fn unread(read_buf:&mut ReadBuf<Vec<u8>>,file:Cursor<Vec<u8>>)->Result<Vec<u8>,Box<dyn Error + Sync + Send>>{
+    let mut buf = vec![];
+    read_buf.read_to_end(&mut buf)?;
+    let position = file.position() as usize;
+    buf.extend_from_slice(&file.into_inner()[position..]);
+    Ok(buf)
 }
-fn process_closure<F>(read_buf:&mut ReadBuf<Vec<u8>>,frame_decoder:&mut MessageCodec,laddr:String,raddr:String,closure:F )->CallResult where
-F: Fn(&mut websocket_codec::Message)->CallResult
+/// Decodes every whole message in `frames` and hands it to `closure`, keeping a partial frame for the next payload.
+/// Items come after `consolidated`; bytes that are not valid frames are forwarded as they are.
+fn process_closure<F>(channel:&mut Channel,direction:WsDirection,mut consolidated:Vec<TcpItem>,mut closure:F )->CallResult where
+F: FnMut(&mut websocket_codec::Message)->Result<WsAction,Box<dyn Error + Sync + Send>>
 {
-    let mut consolidated = vec![];
-    if read_buf.len()==0{
-        return Ok(b"/continue".to_vec());
-    }
-    let mut buf:Vec<u8> =vec![];
-    let r = read_buf.read_to_end(&mut buf);
-    let buf_len = buf.len();
-    if r.is_ok(){
-        let mut bm = BytesMut::with_capacity(0);
-        bm.extend_from_slice(&buf);
-        let result = frame_decoder.decode(&mut bm);
-        if let Some(f_l) = frame_decoder.frame_length{
-            if f_l > buf_len{
-            }else{
-                match result{
-                    Ok(r)=>{
-                        if let Some(mut rr) = r{
-                            //track_assert_eq!(rr.as_text(),None,ErrorKind::InvalidInput);
-                            closure(&mut rr)?;
-                            let mut bytes = BytesMut::new();
-                            match frame_decoder.encode(rr.clone(),&mut bytes){
-                                Ok(_)=>{
-                                    let encoded_message = general_purpose::STANDARD.encode(bytes.to_vec());
-                                    consolidated.push(TcpItem{
-                                        Payload:encoded_message,
-                                        String:rr.as_text().unwrap().to_string(),
-                                        Id:format!("{}-{} ",laddr,raddr),
-                                        Laddr:laddr.clone(),
-                                        Raddr:raddr.clone()
-                                    });
-                                }
-                                _=>{}
-                            }
-                            // track_assert_eq!(Some(format!("len.. {:?}",bytes.len())),None,ErrorKind::InvalidInput);
-                            
-                            
+    let Channel{req_frames,res_frames,frame_req_decoder,frame_res_decoder,deflate,laddr,raddr,..} = channel;
+    let (frames,frame_decoder,reply_encoder) = match direction{
+        WsDirection::Req=>(req_frames,frame_req_decoder,frame_res_decoder),
+        WsDirection::Res=>(res_frames,frame_res_decoder,frame_req_decoder),
+    };
+    let mut replies = vec![];
+    loop{
+        match frames.next_message(frame_decoder){
+            Ok(Some(mut rr))=>{
+                let action = closure(&mut rr)?;
+                for emit in action.into_emits(rr){
+                    match emit{
+                        WsEmit::Forward(message)=>{
+                            consolidated.push(tcp_item(frame_decoder,&message,direction,&laddr,&raddr)?);
+                        }
+                        WsEmit::Reply(message)=>{
+                            replies.push(tcp_item(reply_encoder,&message,direction.reverse(),&laddr,&raddr)?);
                         }
-                    },
-                    Err(_)=>{}
+                    }
                 }
-                
+            }
+            Ok(None)=>break,
+            Err(_)=>{
+                // the stream can no longer be framed: pass what is left through and start over
+                let bytes = frames.take();
+                *frame_decoder = frame_codec(direction,*deflate);
+                consolidated.push(TcpItem{
+                    Payload:general_purpose::STANDARD.encode(&bytes),
+                    String:format!("invalid frames {} bytes: {}",bytes.len(),preview::hex_preview(&bytes)),
+                    Id:format!("{}-{} ",laddr,raddr),
+                    Laddr:laddr.clone(),
+                    Raddr:raddr.clone()
+                });
+                break;
             }
         }
     }
-    if consolidated.len() >0{
-        let buf = rmp_serde::to_vec(&consolidated)?;
-        return Ok(buf);
+    send_back(direction,&laddr,&raddr,replies)?;
+    // no items while a frame is incomplete or a message was dropped, rather than /continue, which forwards the packet as is
+    let buf = rmp_serde::to_vec(&consolidated)?;
+    Ok(buf)
+}
+/// `message` framed by `encoder`, as sent by the mock server
+fn tcp_item(encoder:&mut MessageCodec,message:&websocket_codec::Message,direction:WsDirection,laddr:&str,raddr:&str)->Result<TcpItem,Box<dyn Error + Sync + Send>>{
+    let mut bytes = BytesMut::new();
+    encoder.encode(message.clone(),&mut bytes)?;
+    Ok(TcpItem{
+        Payload:general_purpose::STANDARD.encode(&bytes),
+        String:preview::preview_for(&format!("{}-:{}",laddr,raddr),direction,message),
+        Id:format!("{}-{} ",laddr,raddr),
+        Laddr:laddr.to_string(),
+        Raddr:raddr.to_string()
+    })
+}
+/// Sends `items` back to the connection the intercepted messages came from: the local connection for
+/// `WsDirection::Req`, the remote one for `WsDirection::Res`
+fn send_back(direction:WsDirection,laddr:&str,raddr:&str,items:Vec<TcpItem>)->Result<(),HostError>{
+    let addr = format!("{}-:{}",laddr,raddr);
+    for item in items{
+        let req = TcpReq{
+            Payload:item.Payload,
+            String:item.String,
+            Index:0,
+            Id:String::new(),
+            Command:String::new(),
+            ReportType:String::new(),
+            Timeout:false,
+            Laddr:item.Laddr,
+            Raddr:item.Raddr,
+        };
+        match direction{
+            WsDirection::Req=>WapcHost.tcp_response(&addr,&req)?,
+            WsDirection::Res=>WapcHost.tcp_request(&addr,&req)?,
+        };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::codec::Decoder;
+    use wasm_mock_host_abi::MockHost;
+    use wasm_mock_util::tcp_foo_unmarshall;
+    use websocket_codec::{DeflateConfig, Message};
+
+    fn masked(message: Message) -> Vec<u8> {
+        let mut bytes = BytesMut::new();
+        MessageCodec::client().encode(message, &mut bytes).unwrap();
+        bytes.to_vec()
+    }
+
+    fn items(out: &[u8]) -> Vec<TcpItem> {
+        tcp_foo_unmarshall::<Vec<TcpItem>>(out).unwrap()
+    }
+
+    /// Frames of a base64 payload sent by the mock server
+    fn payload_bytes(payload: &str) -> BytesMut {
+        BytesMut::from(&general_purpose::STANDARD.decode(payload).unwrap()[..])
+    }
+
+    fn forwarded(channel: &mut Channel, bytes: &[u8]) -> Vec<String> {
+        channel.req_frames.extend_from_slice(bytes);
+        let out = process_closure(channel, WsDirection::Req, vec![], |_| Ok(WsAction::Forward)).unwrap();
+        tcp_foo_unmarshall::<Vec<TcpItem>>(&out).unwrap().into_iter().map(|item| item.String).collect()
+    }
+
+    #[test]
+    fn deflate_is_kept_after_invalid_frames() {
+        let config = DeflateConfig::parse("permessage-deflate; client_no_context_takeover; server_no_context_takeover")
+            .unwrap()
+            .unwrap();
+        let mut channel = Channel::new(String::from("1"), String::from("2"));
+        channel.use_deflate(config);
+        let mut client = MessageCodec::client().with_deflate(config.server(), config.client());
+        let mut deflated = |text: &str| {
+            let mut bytes = BytesMut::new();
+            client.encode(Message::text(text), &mut bytes).unwrap();
+            assert_eq!(bytes[0] & 0x40, 0x40);
+            bytes.to_vec()
+        };
+        assert_eq!(forwarded(&mut channel, &deflated("hello")), ["hello"]);
+        assert_eq!(forwarded(&mut channel, &[0x8f, 0x80, 0, 0, 0, 0]), ["invalid frames 6 bytes: 8f8000000000"]);
+        assert_eq!(forwarded(&mut channel, &deflated("hello again")), ["hello again"]);
+    }
+
+    #[test]
+    fn dropped_message_has_no_item() {
+        let mut channel = Channel::new(String::from("1"), String::from("2"));
+        channel.req_frames.extend_from_slice(&masked(Message::text("secret")));
+        let out = process_closure(&mut channel, WsDirection::Req, vec![], |_| Ok(WsAction::Drop)).unwrap();
+        assert!(items(&out).is_empty());
+        assert_eq!(channel.req_frames.len(), 0);
+    }
+
+    #[test]
+    fn many_has_one_item_per_message() {
+        let mut channel = Channel::new(String::from("1"), String::from("2"));
+        channel.req_frames.extend_from_slice(&masked(Message::text("hello")));
+        let out = process_closure(&mut channel, WsDirection::Req, vec![], |msg| {
+            Ok(WsAction::Many(vec![msg.clone(), Message::text("ack"), Message::binary(vec![1, 2])]))
+        })
+        .unwrap();
+        let items = items(&out);
+        assert_eq!(items.len(), 3);
+        let mut server = MessageCodec::server();
+        let messages: Vec<Message> = items
+            .iter()
+            .map(|item| server.decode(&mut payload_bytes(&item.Payload)).unwrap().unwrap())
+            .collect();
+        assert_eq!(messages, [Message::text("hello"), Message::text("ack"), Message::binary(vec![1, 2])]);
+    }
+
+    #[test]
+    fn reply_is_sent_back_with_the_reverse_codec() {
+        let host = MockHost::new();
+        host.reply_with("foo", "tcp_response", |call| Ok(call.payload.clone()));
+        host.reply_with("foo", "tcp_request", |call| Ok(call.payload.clone()));
+        let mut channel = Channel::new(String::from("1"), String::from("2"));
+
+        channel.req_frames.extend_from_slice(&masked(Message::text("ping")));
+        let out = process_closure(&mut channel, WsDirection::Req, vec![], |_| {
+            Ok(WsAction::Reply(Message::text("pong")))
+        })
+        .unwrap();
+        assert!(items(&out).is_empty());
+        let calls = host.calls_to("tcp_response");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].binding, "1-:2");
+        let sent: TcpReq = rmp_serde::from_slice(&calls[0].payload).unwrap();
+        let mut bytes = payload_bytes(&sent.Payload);
+        // toward the client: not masked
+        assert_eq!(bytes[1] & 0x80, 0);
+        assert_eq!(MessageCodec::client().decode(&mut bytes).unwrap(), Some(Message::text("pong")));
+        assert!(host.calls_to("tcp_request").is_empty());
+
+        let mut frames = BytesMut::new();
+        MessageCodec::server().encode(Message::text("tick"), &mut frames).unwrap();
+        channel.res_frames.extend_from_slice(&frames);
+        let out = process_closure(&mut channel, WsDirection::Res, vec![], |_| {
+            Ok(WsAction::Reply(Message::text("tock")))
+        })
+        .unwrap();
+        assert!(items(&out).is_empty());
+        let calls = host.calls_to("tcp_request");
+        assert_eq!(calls.len(), 1);
+        let sent: TcpReq = rmp_serde::from_slice(&calls[0].payload).unwrap();
+        let mut bytes = payload_bytes(&sent.Payload);
+        // toward the server: masked
+        assert_eq!(bytes[1] & 0x80, 0x80);
+        assert_eq!(MessageCodec::server().decode(&mut bytes).unwrap(), Some(Message::text("tock")));
     }
-    return Ok(b"/continue".to_vec());
 }
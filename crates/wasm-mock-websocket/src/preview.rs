@@ -0,0 +1,108 @@
+//! Text the reports show for intercepted messages.
+//!
+//! Text messages are shown as they are. Binary messages are shown as the [`Preview`] set for their connection
+//! and direction with [`set_ws_preview`], a hex preview by default; ping and pong payloads in hex, and close
+//! frames with their code and reason.
+use base64::{engine::general_purpose, Engine as _};
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use websocket_codec::{Message, Opcode};
+
+use crate::rules::{key, WsDirection};
+
+/// Bytes shown by the hex and base64 previews
+pub const PREVIEW_LIMIT: usize = 64;
+
+lazy_static! {
+    pub static ref WS_PREVIEW_MAP: Arc<Mutex<HashMap<String, Preview>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// How binary messages are shown
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Preview {
+    /// First [`PREVIEW_LIMIT`] bytes in hex
+    #[default]
+    Hex,
+    /// First [`PREVIEW_LIMIT`] bytes in base64
+    Base64,
+    /// JSON text sent in a binary message
+    Json,
+    /// MessagePack, shown as JSON
+    MessagePack,
+    /// User decoder, e.g. of a protobuf message; `None` falls back to hex
+    Decoder(fn(&[u8]) -> Option<String>),
+}
+
+impl Preview {
+    /// Decoded form of `data`, hex when it cannot be decoded
+    pub fn show(&self, data: &[u8]) -> String {
+        let decoded = match self {
+            Preview::Hex => None,
+            Preview::Base64 => Some(format!("binary {} bytes: {}", data.len(), base64_preview(data))),
+            Preview::Json => serde_json::from_slice::<Value>(data).ok().map(|v| v.to_string()),
+            Preview::MessagePack => rmp_serde::from_read_ref::<_, Value>(data).ok().map(|v| v.to_string()),
+            Preview::Decoder(decode) => decode(data),
+        };
+        decoded.unwrap_or_else(|| format!("binary {} bytes: {}", data.len(), hex_preview(data)))
+    }
+}
+
+/// Shows the binary messages of the connection `name` ({local address}-:{remote address}) going in `direction` as `preview`
+pub fn set_ws_preview(name: &str, direction: WsDirection, preview: Preview) {
+    WS_PREVIEW_MAP.lock().unwrap().insert(key(name, direction), preview);
+}
+
+/// Report text of `message`, binary messages shown as `binary`
+///
+/// # Examples
+///
+/// ```
+/// use wasm_mock_websocket::preview::{preview, Preview};
+/// use websocket_codec::{CloseCode, Message};
+/// assert_eq!(preview(&Message::text("hi"), Preview::Hex), "hi");
+/// assert_eq!(preview(&Message::binary(vec![1, 255]), Preview::Hex), "binary 2 bytes: 01ff");
+/// assert_eq!(preview(&Message::binary(&b"{\"a\":1}"[..]), Preview::Json), r#"{"a":1}"#);
+/// assert_eq!(preview(&Message::ping(vec![7]), Preview::Hex), "ping 1 bytes: 07");
+/// assert_eq!(
+///     preview(&Message::close_with_reason(CloseCode::Away, "bye".into()), Preview::Hex),
+///     "close 1001 Away: bye"
+/// );
+/// ```
+pub fn preview(message: &Message, binary: Preview) -> String {
+    let data = message.data();
+    match message.opcode() {
+        Opcode::Text => message.as_text().unwrap_or_default().to_string(),
+        Opcode::Binary => binary.show(data),
+        Opcode::Ping => format!("ping {} bytes: {}", data.len(), hex_preview(data)),
+        Opcode::Pong => format!("pong {} bytes: {}", data.len(), hex_preview(data)),
+        Opcode::Close => match message.as_close() {
+            Some(close) => format!("close {} {:?}: {}", u16::from(close.code()), close.code(), close.reason()),
+            None => String::from("close"),
+        },
+    }
+}
+
+/// Report text of `message` sent on the connection `name` in `direction`
+pub fn preview_for(name: &str, direction: WsDirection, message: &Message) -> String {
+    let binary = WS_PREVIEW_MAP.lock().unwrap().get(&key(name, direction)).copied().unwrap_or_default();
+    preview(message, binary)
+}
+
+/// First [`PREVIEW_LIMIT`] bytes of `data` in hex, followed by `…` when there are more
+pub fn hex_preview(data: &[u8]) -> String {
+    let mut hex: String = data.iter().take(PREVIEW_LIMIT).map(|b| format!("{:02x}", b)).collect();
+    if data.len() > PREVIEW_LIMIT {
+        hex.push('…');
+    }
+    hex
+}
+
+fn base64_preview(data: &[u8]) -> String {
+    let mut encoded = general_purpose::STANDARD.encode(&data[..data.len().min(PREVIEW_LIMIT)]);
+    if data.len() > PREVIEW_LIMIT {
+        encoded.push('…');
+    }
+    encoded
+}
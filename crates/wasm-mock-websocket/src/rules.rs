@@ -0,0 +1,249 @@
+//! Message level rules of the `modify ws_req`/`modify ws_res` arms of `mock_suite!`.
+//!
+//! Each mocked connection keeps a list of [`WsRule`] per direction. The first rule matching a message
+//! decides what is sent on with the [`WsAction`] its handler returns; messages no rule matches are forwarded.
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use wapc_guest::prelude::CallResult;
+use wasm_mock_util::stub::Matcher;
+use wasm_mock_util::{tcp_foo_unmarshall, TcpPayload};
+use websocket_codec::{Message, Opcode};
+
+use crate::{handle_ws_req, handle_ws_res};
+
+lazy_static! {
+    pub static ref WS_RULE_MAP: Arc<Mutex<HashMap<String, Vec<WsRule>>>> = Arc::new(Mutex::new(HashMap::new()));
+    /// Origin the handshake requests of a connection are sent with
+    pub static ref WS_ORIGIN_MAP: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// What happens to an intercepted message
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsAction {
+    /// Sends the message on, with the changes the handler made to it
+    Forward,
+    /// Sends another message on instead
+    Replace(Message),
+    /// Sends nothing
+    Drop,
+    /// Sends a message back to the sender instead of forwarding anything
+    Reply(Message),
+    /// Sends these messages on instead, in order; `vec![msg.clone(), msg.clone()]` duplicates the message
+    Many(Vec<Message>),
+    /// Sends these messages on or back, in order, e.g. an acknowledgement to the client next to the forwarded message
+    Emit(Vec<WsEmit>),
+}
+
+/// A message sent by a [`WsAction::Emit`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsEmit {
+    /// To the connection the intercepted message was going to
+    Forward(Message),
+    /// To the connection the intercepted message came from
+    Reply(Message),
+}
+
+impl WsAction {
+    /// Messages to send in place of `message`
+    pub fn into_emits(self, message: Message) -> Vec<WsEmit> {
+        match self {
+            WsAction::Forward => vec![WsEmit::Forward(message)],
+            WsAction::Replace(m) => vec![WsEmit::Forward(m)],
+            WsAction::Drop => vec![],
+            WsAction::Reply(m) => vec![WsEmit::Reply(m)],
+            WsAction::Many(messages) => messages.into_iter().map(WsEmit::Forward).collect(),
+            WsAction::Emit(emits) => emits,
+        }
+    }
+}
+
+/// Direction of the intercepted messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WsDirection {
+    /// From the local connection to the remote one
+    Req,
+    /// From the remote connection to the local one
+    Res,
+}
+
+impl WsDirection {
+    /// Direction of the messages sent back
+    pub fn reverse(self) -> Self {
+        match self {
+            WsDirection::Req => WsDirection::Res,
+            WsDirection::Res => WsDirection::Req,
+        }
+    }
+}
+
+pub(crate) fn key(name: &str, direction: WsDirection) -> String {
+    match direction {
+        WsDirection::Req => format!("{}_req", name),
+        WsDirection::Res => format!("{}_res", name),
+    }
+}
+
+/// Handler of the messages a rule matches
+pub type WsHandler = fn(&mut Message) -> Result<WsAction, Box<dyn Error + Sync + Send>>;
+
+/// Condition on a message
+#[derive(Debug, Clone)]
+pub enum WsMatch {
+    Opcode(Opcode),
+    /// Text of a text message
+    Text(Matcher),
+    /// Text message holding JSON that satisfies the predicate
+    Json(fn(&Value) -> bool),
+    /// Binary message whose data satisfies the predicate
+    Binary(fn(&[u8]) -> bool),
+}
+
+impl WsMatch {
+    pub fn matches(&self, message: &Message) -> bool {
+        match self {
+            WsMatch::Opcode(opcode) => message.opcode() == *opcode,
+            WsMatch::Text(m) => message.as_text().is_some_and(|text| m.matches(text)),
+            WsMatch::Json(predicate) => message
+                .as_text()
+                .and_then(|text| serde_json::from_str::<Value>(text).ok())
+                .is_some_and(|v| predicate(&v)),
+            WsMatch::Binary(predicate) => message.opcode() == Opcode::Binary && predicate(message.data()),
+        }
+    }
+}
+
+/// Messages matching every condition, and what to do with them
+///
+/// # Examples
+///
+/// ```
+/// use wasm_mock_websocket::{WsAction, WsRule};
+/// use websocket_codec::Message;
+/// let rule = WsRule::new()
+///     .json(|v| v["type"] == "subscribe")
+///     .handle(|msg| Ok(WsAction::Many(vec![msg.clone(), Message::text(r#"{"type":"ack"}"#)])));
+/// assert!(rule.matches(&Message::text(r#"{"type":"subscribe"}"#)));
+/// assert!(!rule.matches(&Message::binary(vec![1, 2])));
+/// ```
+///
+/// An invalid regular expression is kept as the rule's [`error`](WsRule::error) and makes [`add_ws_rule`] fail.
+#[derive(Debug, Clone)]
+pub struct WsRule {
+    pub matchers: Vec<WsMatch>,
+    pub handler: WsHandler,
+    error: Option<regex::Error>,
+}
+
+impl Default for WsRule {
+    fn default() -> Self {
+        WsRule {
+            matchers: vec![],
+            handler: |_| Ok(WsAction::Forward),
+            error: None,
+        }
+    }
+}
+
+impl WsRule {
+    /// Rule matching every message and forwarding it
+    pub fn new() -> Self {
+        WsRule::default()
+    }
+    pub fn opcode(mut self, opcode: Opcode) -> Self {
+        self.matchers.push(WsMatch::Opcode(opcode));
+        self
+    }
+    pub fn text(mut self, text: &str) -> Self {
+        self.matchers.push(WsMatch::Text(Matcher::Equals(text.to_string())));
+        self
+    }
+    pub fn text_regex(mut self, regex: &str) -> Self {
+        match Matcher::regex(regex) {
+            Ok(m) => self.matchers.push(WsMatch::Text(m)),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+        self
+    }
+    pub fn json(mut self, predicate: fn(&Value) -> bool) -> Self {
+        self.matchers.push(WsMatch::Json(predicate));
+        self
+    }
+    pub fn binary(mut self, predicate: fn(&[u8]) -> bool) -> Self {
+        self.matchers.push(WsMatch::Binary(predicate));
+        self
+    }
+    pub fn handle(mut self, handler: WsHandler) -> Self {
+        self.handler = handler;
+        self
+    }
+    pub fn matches(&self, message: &Message) -> bool {
+        self.matchers.iter().all(|m| m.matches(message))
+    }
+    /// First invalid regular expression given to the builder
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.error.as_ref()
+    }
+}
+
+/// Adds a rule for the messages of the connection `name` ({local address}-:{remote address}) going in `direction`.
+/// Fails when the rule was given an invalid regular expression
+pub fn add_ws_rule(name: &str, direction: WsDirection, rule: WsRule) -> Result<(), regex::Error> {
+    if let Some(e) = rule.error {
+        return Err(e);
+    }
+    WS_RULE_MAP.lock().unwrap().entry(key(name, direction)).or_default().push(rule);
+    Ok(())
+}
+
+/// Sends the handshake requests of the connection `name` with the `Origin` header set to `origin`
+pub fn set_ws_origin(name: &str, origin: &str) {
+    WS_ORIGIN_MAP.lock().unwrap().insert(name.to_string(), origin.to_string());
+}
+
+/// Action of the first rule of `name` and `direction` matching `message`, [`WsAction::Forward`] without one
+pub fn apply_ws_rules(name: &str, direction: WsDirection, message: &mut Message) -> Result<WsAction, Box<dyn Error + Sync + Send>> {
+    let handler = WS_RULE_MAP
+        .lock()
+        .unwrap()
+        .get(&key(name, direction))
+        .and_then(|rules| rules.iter().find(|r| r.matches(message)))
+        .map(|r| r.handler);
+    match handler {
+        Some(handler) => handler(message),
+        None => Ok(WsAction::Forward),
+    }
+}
+
+/// `{name}_modify_req` handler applying the rules of `name`
+pub fn ws_rules_req(name: &str, msg: &[u8]) -> CallResult {
+    let tcp_payload: TcpPayload = tcp_foo_unmarshall(msg)?;
+    let origin = WS_ORIGIN_MAP.lock().unwrap().get(name).cloned().unwrap_or_default();
+    handle_ws_req(&tcp_payload, &origin, |m| apply_ws_rules(name, WsDirection::Req, m))
+}
+
+/// `{name}_modify_res` handler applying the rules of `name`
+pub fn ws_rules_res(name: &str, msg: &[u8]) -> CallResult {
+    let tcp_payload: TcpPayload = tcp_foo_unmarshall(msg)?;
+    handle_ws_res(&tcp_payload, |m| apply_ws_rules(name, WsDirection::Res, m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_text_regex_is_returned_by_add_ws_rule() {
+        let rule = WsRule::new().text_regex("^(ping");
+        assert!(rule.error().is_some());
+        assert!(add_ws_rule("1-:2", WsDirection::Req, rule).is_err());
+        assert!(!WS_RULE_MAP.lock().unwrap().contains_key(&key("1-:2", WsDirection::Req)));
+        let rule = WsRule::new().text_regex("^ping");
+        assert!(rule.matches(&Message::text("ping 1")) && !rule.matches(&Message::text("pong")));
+        assert!(add_ws_rule("1-:2", WsDirection::Req, rule).is_ok());
+    }
+}
@@ -47,7 +47,7 @@ fn main() {
     //println!("z {:?}",z.decode(&mut buf));
 
     let result = h.decode(&mut buf);
-    println!("result {:?} buf len{:?} frame_length{:?}",result,buf.len(),h.frame_length);
+    println!("result {:?} buf len{:?}",result,buf.len());
     println!("buf {:?}",std::str::from_utf8(&buf.to_vec()).unwrap());
     let mut rd : RequestDecoder<NoBodyDecoder> = RequestDecoder::default();
     let mut s = String::from("R0VUIC9lY2hvIEhUVFAvMS4xDQpIb3N0OiBsb2NhbGhvc3Q6MzMzNQ0KUHJhZ21hOiBuby1jYWNoZQ0KQWNjZXB0OiAqLyoNClNlYy1XZWJTb2NrZXQtS2V5OiBzUGVROFFaYU0rdkNQZjlZVHE4dU9nPT0NClNlYy1XZWJTb2NrZXQtVmVyc2lvbjogMTMNCkFjY2VwdC1MYW5ndWFnZTogZW4tU0csZW4tR0I7cT0wLjksZW47cT0wLjgNClNlYy1XZWJTb2NrZXQtRXh0ZW5zaW9uczogcGVybWVzc2FnZS1kZWZsYXRlDQpDYWNoZS1Db250cm9sOiBuby1jYWNoZQ0KQWNjZXB0LUVuY29kaW5nOiBnemlwLCBkZWZsYXRlDQpPcmlnaW46IGh0dHA6Ly9sb2NhbGhvc3Q6MzMzNA0KVXNlci1BZ2VudDogTW96aWxsYS81LjAgKE1hY2ludG9zaDsgSW50ZWwgTWFjIE9TIFggMTBfMTVfNykgQXBwbGVXZWJLaXQvNjA1LjEuMTUgKEtIVE1MLCBsaWtlIEdlY2tvKSBWZXJzaW9uLzE2LjEgU2FmYXJpLzYwNS4xLjE1DQpDb25uZWN0aW9uOiBVcGdyYWRlDQpVcGdyYWRlOiB3ZWJzb2NrZXQNCg0K");
@@ -0,0 +1,388 @@
+//! The permessage-deflate extension ([RFC 7692](https://tools.ietf.org/html/rfc7692)).
+
+use std::fmt;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::Result;
+
+/// Name of the extension in the `Sec-WebSocket-Extensions` header.
+pub const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+
+/// Trailer that senders remove from each compressed message and receivers append back.
+const TRAILER: [u8; 4] = [0, 0, 0xff, 0xff];
+
+/// Largest LZ77 window, in bits, and the one used when no `max_window_bits` parameter was agreed.
+pub const MAX_WINDOW_BITS: u8 = 15;
+
+/// permessage-deflate parameters agreed in the opening handshake.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DeflateConfig {
+    /// The server starts a new compression context for each message it sends.
+    pub server_no_context_takeover: bool,
+    /// The client starts a new compression context for each message it sends.
+    pub client_no_context_takeover: bool,
+    /// Size, as a power of two, of the LZ77 window of the messages the server sends.
+    pub server_max_window_bits: u8,
+    /// Size, as a power of two, of the LZ77 window of the messages the client sends.
+    pub client_max_window_bits: u8,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        DeflateConfig {
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            server_max_window_bits: MAX_WINDOW_BITS,
+            client_max_window_bits: MAX_WINDOW_BITS,
+        }
+    }
+}
+
+impl DeflateConfig {
+    /// Parses the `Sec-WebSocket-Extensions` header of a handshake response.
+    ///
+    /// Returns `Ok(None)` when the server did not accept permessage-deflate.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` when the permessage-deflate parameters are unknown, repeated or out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use websocket_codec::DeflateConfig;
+    /// let config = DeflateConfig::parse("permessage-deflate; client_no_context_takeover; server_max_window_bits=10")
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert!(config.client_no_context_takeover);
+    /// assert_eq!(config.server_max_window_bits, 10);
+    /// assert_eq!(DeflateConfig::parse("x-webkit-deflate-frame").unwrap(), None);
+    /// ```
+    pub fn parse(extensions: &str) -> Result<Option<Self>> {
+        for extension in extensions.split(',') {
+            let mut params = extension.split(';').map(str::trim);
+            if params.next() != Some(PERMESSAGE_DEFLATE) {
+                continue;
+            }
+
+            let mut config = DeflateConfig::default();
+            let mut seen = Vec::new();
+            for param in params.filter(|param| !param.is_empty()) {
+                let (name, value) = match param.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                    None => (param, None),
+                };
+
+                if seen.contains(&name) {
+                    return Err(format!("{} parameter {} is repeated", PERMESSAGE_DEFLATE, name).into());
+                }
+
+                seen.push(name);
+                match (name, value) {
+                    ("server_no_context_takeover", None) => config.server_no_context_takeover = true,
+                    ("client_no_context_takeover", None) => config.client_no_context_takeover = true,
+                    ("server_max_window_bits", Some(value)) => config.server_max_window_bits = window_bits(value)?,
+                    ("client_max_window_bits", Some(value)) => config.client_max_window_bits = window_bits(value)?,
+                    // offered by the client without a value: it accepts any window
+                    ("client_max_window_bits", None) => {}
+                    _ => return Err(format!("{} parameter {} is not supported", PERMESSAGE_DEFLATE, param).into()),
+                }
+            }
+
+            return Ok(Some(config));
+        }
+
+        Ok(None)
+    }
+
+    /// Compression of the messages the client sends.
+    #[must_use]
+    pub fn client(&self) -> DeflateParams {
+        DeflateParams {
+            no_context_takeover: self.client_no_context_takeover,
+            max_window_bits: self.client_max_window_bits,
+        }
+    }
+
+    /// Compression of the messages the server sends.
+    #[must_use]
+    pub fn server(&self) -> DeflateParams {
+        DeflateParams {
+            no_context_takeover: self.server_no_context_takeover,
+            max_window_bits: self.server_max_window_bits,
+        }
+    }
+}
+
+impl fmt::Display for DeflateConfig {
+    /// Formats the config as a `Sec-WebSocket-Extensions` header value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PERMESSAGE_DEFLATE)?;
+        if self.server_no_context_takeover {
+            f.write_str("; server_no_context_takeover")?;
+        }
+
+        if self.client_no_context_takeover {
+            f.write_str("; client_no_context_takeover")?;
+        }
+
+        if self.server_max_window_bits != MAX_WINDOW_BITS {
+            write!(f, "; server_max_window_bits={}", self.server_max_window_bits)?;
+        }
+
+        if self.client_max_window_bits != MAX_WINDOW_BITS {
+            write!(f, "; client_max_window_bits={}", self.client_max_window_bits)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn window_bits(value: &str) -> Result<u8> {
+    match value.parse() {
+        Ok(bits @ 8..=15) => Ok(bits),
+        _ => Err(format!("max_window_bits must be between 8 and 15, not {:?}", value).into()),
+    }
+}
+
+/// Compression of the messages one endpoint sends.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DeflateParams {
+    /// A new compression context is started for each message.
+    pub no_context_takeover: bool,
+    /// Size, as a power of two, of the LZ77 window.
+    pub max_window_bits: u8,
+}
+
+/// Decompresses the messages of one endpoint.
+pub(crate) struct Inflater {
+    params: DeflateParams,
+    decompress: Decompress,
+}
+
+impl Inflater {
+    pub(crate) fn new(params: DeflateParams) -> Self {
+        Inflater {
+            params,
+            decompress: Decompress::new(false),
+        }
+    }
+
+    pub(crate) fn inflate(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut input = Vec::with_capacity(data.len() + TRAILER.len());
+        input.extend_from_slice(data);
+        input.extend_from_slice(&TRAILER);
+
+        let mut output = Vec::with_capacity(data.len() * 2 + 64);
+        let start = self.decompress.total_in();
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(output.capacity());
+            }
+
+            #[allow(clippy::cast_possible_truncation)]
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)?;
+
+            #[allow(clippy::cast_possible_truncation)]
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if status == Status::StreamEnd {
+                // the sender ended the stream with a final block, the next message starts a new one
+                self.decompress.reset(false);
+                break;
+            }
+
+            if consumed == input.len() && output.len() < output.capacity() {
+                break;
+            }
+        }
+
+        if self.params.no_context_takeover {
+            self.decompress.reset(false);
+        }
+
+        Ok(output)
+    }
+}
+
+impl Clone for Inflater {
+    /// The clone starts with a new decompression context.
+    fn clone(&self) -> Self {
+        Inflater::new(self.params)
+    }
+}
+
+/// Compresses the messages one endpoint sends.
+pub(crate) struct Deflater {
+    params: DeflateParams,
+    compress: Compress,
+}
+
+impl Deflater {
+    pub(crate) fn new(params: DeflateParams) -> Self {
+        Deflater {
+            params,
+            compress: Compress::new(Compression::default(), false),
+        }
+    }
+
+    /// Compressed `data`, or `None` when the message has to be sent uncompressed.
+    ///
+    /// The compressor always uses a window of 2^15 bytes, so messages are not compressed when the receiver agreed to
+    /// a smaller one.
+    pub(crate) fn deflate(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        if self.params.max_window_bits < MAX_WINDOW_BITS {
+            return Ok(None);
+        }
+
+        let mut output = Vec::with_capacity(data.len() + 64);
+        let start = self.compress.total_in();
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(output.capacity());
+            }
+
+            #[allow(clippy::cast_possible_truncation)]
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&data[consumed..], &mut output, FlushCompress::Sync)?;
+
+            #[allow(clippy::cast_possible_truncation)]
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == data.len() && output.len() < output.capacity() {
+                break;
+            }
+        }
+
+        if output.ends_with(&TRAILER) {
+            output.truncate(output.len() - TRAILER.len());
+        }
+
+        if self.params.no_context_takeover {
+            self.compress.reset();
+        }
+
+        Ok(Some(output))
+    }
+}
+
+impl Clone for Deflater {
+    /// The clone starts with a new compression context.
+    fn clone(&self) -> Self {
+        Deflater::new(self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::deflate::{DeflateConfig, DeflateParams, Deflater, Inflater};
+    use crate::message::{Message, MessageCodec};
+
+    fn params(no_context_takeover: bool) -> DeflateParams {
+        DeflateParams {
+            no_context_takeover,
+            max_window_bits: 15,
+        }
+    }
+
+    #[test]
+    fn rfc_7692_examples() {
+        // "Hello" compressed in one message, section 7.2.3.1
+        let mut inflater = Inflater::new(params(false));
+        assert_eq!(inflater.inflate(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00]).unwrap(), b"Hello");
+        // the same message again, using the context of the first one, section 7.2.3.2
+        assert_eq!(inflater.inflate(&[0xf2, 0x00, 0x11, 0x00, 0x00]).unwrap(), b"Hello");
+
+        // a frame with RSV1 set, section 7.2.3.1
+        let mut codec = MessageCodec::client().with_deflate(params(false), params(false));
+        let mut frame = BytesMut::from(&[0xc1, 0x07, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00][..]);
+        assert_eq!(codec.decode(&mut frame).unwrap(), Some(Message::text("Hello")));
+
+        // fragmented, section 7.2.3.3
+        let mut frame = BytesMut::from(&[0x41, 0x03, 0xf2, 0x48, 0xcd, 0x80, 0x04, 0xc9, 0xc9, 0x07, 0x00][..]);
+        assert_eq!(codec.decode(&mut frame).unwrap(), Some(Message::text("Hello")));
+    }
+
+    #[test]
+    fn context_takeover() {
+        for no_context_takeover in [false, true] {
+            let mut deflater = Deflater::new(params(no_context_takeover));
+            let mut inflater = Inflater::new(params(no_context_takeover));
+            let first = deflater.deflate(b"a repeated sentence").unwrap().unwrap();
+            let second = deflater.deflate(b"a repeated sentence").unwrap().unwrap();
+            assert_eq!(no_context_takeover, first == second);
+            assert!(second.len() < first.len() || no_context_takeover);
+            assert_eq!(inflater.inflate(&first).unwrap(), b"a repeated sentence");
+            assert_eq!(inflater.inflate(&second).unwrap(), b"a repeated sentence");
+        }
+    }
+
+    #[test]
+    fn smaller_window_is_sent_uncompressed() {
+        let config = DeflateConfig::parse("permessage-deflate; client_max_window_bits=10").unwrap().unwrap();
+        let mut codec = MessageCodec::client().with_deflate(config.server(), config.client());
+        let mut frame = BytesMut::new();
+        codec.encode(Message::text("plain"), &mut frame).unwrap();
+        assert_eq!(frame[0], 0x81);
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = DeflateConfig::parse("permessage-deflate; server_no_context_takeover").unwrap().unwrap();
+        let mut server = MessageCodec::server().with_deflate(config.client(), config.server());
+        let mut client = MessageCodec::client().with_deflate(config.server(), config.client());
+        let large: Vec<u8> = (0..100_000_u32).map(|i| (i % 251) as u8).collect();
+        let messages = vec![
+            Message::text("hello"),
+            Message::binary(large),
+            Message::ping("not compressed"),
+            Message::text(""),
+            Message::text("hello"),
+        ];
+
+        let mut frames = BytesMut::new();
+        for message in &messages {
+            client.encode(message, &mut frames).unwrap();
+        }
+
+        assert_eq!(frames[0] & 0x40, 0x40);
+        for message in messages {
+            assert_eq!(server.decode(&mut frames).unwrap(), Some(message));
+        }
+
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            DeflateConfig::parse("foo, permessage-deflate; server_no_context_takeover; client_max_window_bits=\"9\"")
+                .unwrap(),
+            Some(DeflateConfig {
+                server_no_context_takeover: true,
+                client_max_window_bits: 9,
+                ..DeflateConfig::default()
+            })
+        );
+        assert!(DeflateConfig::parse("permessage-deflate; server_max_window_bits=16").is_err());
+        assert!(DeflateConfig::parse("permessage-deflate; foo").is_err());
+        assert!(DeflateConfig::parse("permessage-deflate; server_no_context_takeover; server_no_context_takeover").is_err());
+
+        let config = DeflateConfig {
+            client_no_context_takeover: true,
+            server_max_window_bits: 12,
+            ..DeflateConfig::default()
+        };
+        assert_eq!(
+            config.to_string(),
+            "permessage-deflate; client_no_context_takeover; server_max_window_bits=12"
+        );
+        assert_eq!(DeflateConfig::parse(&config.to_string()).unwrap(), Some(config));
+    }
+}
@@ -164,7 +164,10 @@ impl FrameHeader {
         len
     }
 
-    pub(crate) fn parse_slice(buf: &[u8]) -> Option<(Self, usize)> {
+    /// Parses the frame header at the start of `buf`, returning it and its length in bytes.
+    ///
+    /// Returns `None` if `buf` is too short to hold the whole header.
+    pub fn parse_slice(buf: &[u8]) -> Option<(Self, usize)> {
         if buf.len() < 2 {
             return None;
         }
@@ -15,6 +15,7 @@ extern crate quickcheck_macros;
 extern crate test;
 
 mod close;
+mod deflate;
 mod frame;
 mod mask;
 mod message;
@@ -24,6 +25,7 @@ mod upgrade;
 pub mod protocol;
 
 pub use crate::close::{CloseCode, CloseFrame};
+pub use crate::deflate::{DeflateConfig, DeflateParams, MAX_WINDOW_BITS, PERMESSAGE_DEFLATE};
 pub use crate::message::{Message, MessageCodec};
 pub use crate::opcode::Opcode;
 pub use crate::upgrade::{ClientRequest, UpgradeCodec};
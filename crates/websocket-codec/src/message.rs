@@ -5,6 +5,7 @@ use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
 use crate::close::{CloseCode, CloseFrame};
+use crate::deflate::{DeflateParams, Deflater, Inflater};
 use crate::frame::FrameHeader;
 use crate::mask::Mask;
 use crate::opcode::Opcode;
@@ -166,12 +167,14 @@ impl Message {
 }
 
 /// Tokio codec for WebSocket messages. This codec can send and receive [`Message`] structs.
+///
+/// A clone of a codec using permessage-deflate starts with new compression contexts.
 #[derive(Clone)]
 pub struct MessageCodec {
-    interrupted_message: Option<(Opcode, BytesMut)>,
+    interrupted_message: Option<(Opcode, bool, BytesMut)>,
     use_mask: bool,
-    ///frame_length: total required size
-    pub frame_length:Option<usize>,
+    inflater: Option<Inflater>,
+    deflater: Option<Deflater>,
 }
 
 impl MessageCodec {
@@ -197,9 +200,22 @@ impl MessageCodec {
         Self {
             use_mask,
             interrupted_message: None,
-            frame_length:None,
+            inflater: None,
+            deflater: None,
         }
     }
+
+    /// Uses the permessage-deflate extension: text and binary messages with the RSV1 bit set are decompressed
+    /// following `decode`, and encoded messages are compressed following `encode`.
+    ///
+    /// A client decodes with [`DeflateConfig::server`](crate::DeflateConfig::server) and encodes with
+    /// [`DeflateConfig::client`](crate::DeflateConfig::client), a server the other way round.
+    #[must_use]
+    pub fn with_deflate(mut self, decode: DeflateParams, encode: DeflateParams) -> Self {
+        self.inflater = Some(Inflater::new(decode));
+        self.deflater = Some(Deflater::new(encode));
+        self
+    }
 }
 
 fn truncate_floor_char_boundary(s: &mut String, new_len: usize) -> usize {
@@ -224,7 +240,7 @@ impl Decoder for MessageCodec {
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>> {
         let mut state = self.interrupted_message.take();
-        let (opcode, data) = loop {
+        let (opcode, compressed, data) = loop {
             let (header, header_len) = if let Some(tuple) = FrameHeader::parse_slice(src) {
                 tuple
             } else {
@@ -236,9 +252,7 @@ impl Decoder for MessageCodec {
             };
 
             let data_len = usize::try_from(header.data_len)?;
-            println!("data_len {:?} header.data_len {:?} src len{:?}",data_len,header.data_len, src.len() );
             let frame_len = header_len + data_len;
-            self.frame_length = Some(header_len+ 8);
             if frame_len > src.remaining() {
                 // The buffer contains the frame header but it's not big enough for the data. Reserve additional
                 // space for the frame data, plus the next frame header.
@@ -273,7 +287,9 @@ impl Decoder for MessageCodec {
                 data_len: _data_len,
             } = header;
 
-            if rsv != 0 {
+            // RSV1 marks the first frame of a compressed message
+            let compressed = rsv == 0x40 && self.inflater.is_some();
+            if rsv != 0 && !compressed {
                 return Err(format!("reserved bits are not supported: 0x{:x}", rsv).into());
             }
 
@@ -298,11 +314,15 @@ impl Decoder for MessageCodec {
                 Some(opcode)
             };
 
-            state = if let Some((partial_opcode, mut partial_data)) = state {
+            if compressed && !matches!(opcode, Some(Opcode::Text | Opcode::Binary)) {
+                return Err("only the first frame of a data message can be compressed".into());
+            }
+
+            state = if let Some((partial_opcode, partial_compressed, mut partial_data)) = state {
                 if let Some(opcode) = opcode {
                     if fin && opcode.is_control() {
-                        self.interrupted_message = Some((partial_opcode, partial_data));
-                        break (opcode, data);
+                        self.interrupted_message = Some((partial_opcode, partial_compressed, partial_data));
+                        break (opcode, false, data);
                     }
 
                     return Err(format!("continuation frame must have continuation opcode, not {:?}", opcode).into());
@@ -311,24 +331,27 @@ impl Decoder for MessageCodec {
                 partial_data.extend_from_slice(&data);
 
                 if fin {
-                    break (partial_opcode, partial_data);
+                    break (partial_opcode, partial_compressed, partial_data);
                 }
 
-                Some((partial_opcode, partial_data))
+                Some((partial_opcode, partial_compressed, partial_data))
             } else if let Some(opcode) = opcode {
                 if fin {
-                    break (opcode, data);
+                    break (opcode, compressed, data);
                 }
                 if opcode.is_control() {
                     return Err("control frames must not be fragmented".into());
                 }
-                Some((opcode, data))
+                Some((opcode, compressed, data))
             } else {
                 return Err("continuation must not be first frame".into());
             }
         };
 
-        Ok(Some(Message::new(opcode, data.freeze())?))
+        match (compressed, &mut self.inflater) {
+            (true, Some(inflater)) => Ok(Some(Message::new(opcode, inflater.inflate(&data)?)?)),
+            _ => Ok(Some(Message::new(opcode, data.freeze())?)),
+        }
     }
 }
 
@@ -345,20 +368,31 @@ impl<'a> Encoder<&'a Message> for MessageCodec {
 
     fn encode(&mut self, item: &Message, dst: &mut BytesMut) -> Result<()> {
         let mask = if self.use_mask { Some(Mask::new()) } else { None };
-        let header = item.header(mask);
+        let compressed = match &mut self.deflater {
+            Some(deflater) if !item.opcode.is_control() => deflater.deflate(&item.data)?,
+            _ => None,
+        };
+
+        let data = compressed.as_deref().unwrap_or(&item.data);
+        let mut header = item.header(mask);
+        if compressed.is_some() {
+            header.rsv = 0x40;
+            header.data_len = data.len().into();
+        }
+
         header.write_to_bytes(dst);
 
         if let Some(mask) = mask {
             let offset = dst.len();
-            dst.reserve(item.data.len());
+            dst.reserve(data.len());
 
             unsafe {
-                dst.set_len(offset + item.data.len());
+                dst.set_len(offset + data.len());
             }
 
-            mask::mask_slice_copy(&mut dst[offset..], &item.data, mask);
+            mask::mask_slice_copy(&mut dst[offset..], data, mask);
         } else {
-            dst.put_slice(&item.data);
+            dst.put_slice(data);
         }
 
         Ok(())
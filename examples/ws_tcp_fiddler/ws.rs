@@ -5,17 +5,17 @@ use wasm_mock_websocket::*;
 use wasm_mock_util::*;
 fn _req(msg: &[u8]) -> CallResult{
     let tcp_payload:TcpPayload = rmp_serde::from_read_ref(msg)?;
-    let c = |_c: &mut websocket_codec::Message|->CallResult{
-         Ok(vec![])
+    let c = |_c: &mut websocket_codec::Message|{
+         Ok(WsAction::Forward)
     };
     //change origin from 3334 to 3335 ( as the page is served in localhost:3334, but the mock server dial from port 3335)
     handle_ws_req(&tcp_payload,"http://localhost:3335",c)
 }
 fn _res(msg: &[u8]) -> CallResult{
     let tcp_payload:TcpPayload = rmp_serde::from_read_ref(msg)?;
-    let c = |c: &mut websocket_codec::Message|->CallResult{
+    let c = |c: &mut websocket_codec::Message|{
         *c = websocket_codec::Message::text("echo");
-        Ok(vec![])
+        Ok(WsAction::Forward)
     };
     handle_ws_res(&tcp_payload,c)
 }